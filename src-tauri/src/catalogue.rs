@@ -0,0 +1,1074 @@
+use chrono::Local;
+use log::{debug, error};
+use rusqlite::{
+    params, params_from_iter, types::Value, Connection, OptionalExtension, Transaction,
+    TransactionBehavior,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tauri::{AppHandle, Manager};
+
+use super::models::catalogue::{
+    CatalogueEntry, CatalogueFilter, CatalogueOutput, CataloguePage, CatalogueSource,
+    DocumentStatus, FileStatus, MovedOutput, ReconcileReport,
+};
+use super::models::search::IndexedDocument;
+use super::models::sidecar::{SidecarPart, SourceDocument};
+use super::models::workflows::{
+    DocumentProcessStageSuccess, PagePreprocessStageResult, PageReference,
+};
+use super::scope::PathScope;
+use super::utilities::sha256_file;
+
+const CATALOGUE_FILE_NAME: &str = "catalogue.sqlite3";
+/// Where processed documents are written inside the data directory.
+pub const DOCUMENTS_DIRECTORY_NAME: &str = "documents";
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Schema changes, applied in order. `PRAGMA user_version` holds how many
+/// have been applied; never edit an entry once released, add a new one.
+const MIGRATIONS: [&str; 2] = [
+    r#"
+    CREATE TABLE documents (
+        id TEXT PRIMARY KEY,
+        status TEXT NOT NULL,
+        selected_pages TEXT NOT NULL,
+        source_pages TEXT NOT NULL DEFAULT '[]',
+        result TEXT,
+        type_name TEXT NOT NULL DEFAULT '',
+        type_abbr TEXT NOT NULL DEFAULT '',
+        summary TEXT NOT NULL DEFAULT '',
+        suggested_file_name TEXT NOT NULL DEFAULT '',
+        error_message TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE INDEX documents_updated_at ON documents (updated_at);
+    CREATE TABLE sources (
+        path TEXT PRIMARY KEY,
+        sha256 TEXT,
+        size INTEGER,
+        status TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE document_sources (
+        document_id TEXT NOT NULL REFERENCES documents (id) ON DELETE CASCADE,
+        source_path TEXT NOT NULL REFERENCES sources (path),
+        PRIMARY KEY (document_id, source_path)
+    );
+    CREATE TABLE outputs (
+        document_id TEXT NOT NULL REFERENCES documents (id) ON DELETE CASCADE,
+        part_number INTEGER NOT NULL,
+        path TEXT NOT NULL,
+        sha256 TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        PRIMARY KEY (document_id, part_number)
+    );
+    CREATE INDEX outputs_path ON outputs (path);
+"#,
+    r#"
+    CREATE TABLE page_hashes (
+        images_directory TEXT NOT NULL,
+        page_number INTEGER NOT NULL,
+        hash INTEGER NOT NULL,
+        PRIMARY KEY (images_directory, page_number)
+    );
+"#,
+];
+
+/// App-wide counterpart of the catalogues, for what must be compared or
+/// counted across source documents: fingerprints of processed documents, so
+/// two scans of the same paper are matched, the Bates counters of cases and
+/// the search index, one row per document.
+const LIBRARY_FILE_NAME: &str = "library.sqlite3";
+const LIBRARY_MIGRATIONS: [&str; 2] = [
+    r#"
+    CREATE TABLE fingerprints (
+        document_id TEXT PRIMARY KEY,
+        data_directory TEXT NOT NULL,
+        page_hashes TEXT NOT NULL,
+        text_signature TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE bates_counters (
+        prefix TEXT PRIMARY KEY,
+        next_number INTEGER NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+"#,
+    r#"
+    CREATE TABLE search_documents (
+        document_id TEXT PRIMARY KEY,
+        document TEXT NOT NULL,
+        trashed INTEGER NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+"#,
+];
+
+fn sql_error(e: rusqlite::Error) -> String {
+    error!("Catalogue query failed: {}", e);
+    format!("Catalogue query failed: {}", e)
+}
+
+fn now() -> String {
+    Local::now().to_rfc3339()
+}
+
+/// Opens the catalogue of `data_directory`, creating or migrating it.
+fn open(data_directory: &Path) -> Result<Connection, String> {
+    open_database(&data_directory.join(CATALOGUE_FILE_NAME), &MIGRATIONS)
+}
+
+/// Opens the app-wide library, creating or migrating it.
+fn open_library(handle: &AppHandle) -> Result<Connection, String> {
+    let data_directory = handle.path().app_data_dir().map_err(|e| {
+        error!("Failed to resolve app data directory: {}", e);
+        format!("Failed to resolve app data directory: {}", e)
+    })?;
+    fs::create_dir_all(&data_directory).map_err(|e| {
+        error!("Failed to create app data directory: {}", e);
+        format!("Failed to create app data directory: {}", e)
+    })?;
+    open_database(&data_directory.join(LIBRARY_FILE_NAME), &LIBRARY_MIGRATIONS)
+}
+
+fn open_database(path: &Path, migrations: &[&str]) -> Result<Connection, String> {
+    let mut connection = Connection::open(path).map_err(|e| {
+        error!("Failed to open catalogue {}: {}", path.display(), e);
+        format!("Failed to open catalogue {}: {}", path.display(), e)
+    })?;
+    connection.busy_timeout(BUSY_TIMEOUT).map_err(sql_error)?;
+    connection
+        .pragma_update(None, "foreign_keys", true)
+        .map_err(sql_error)?;
+    connection
+        .pragma_update(None, "journal_mode", "WAL")
+        .map_err(sql_error)?;
+
+    let version: usize = connection
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(sql_error)?;
+    if version > migrations.len() {
+        return Err(format!(
+            "Catalogue {} was created by a newer version of the app",
+            path.display()
+        ));
+    }
+    if version < migrations.len() {
+        let transaction = connection.transaction().map_err(sql_error)?;
+        for migration in &migrations[version..] {
+            transaction.execute_batch(migration).map_err(sql_error)?;
+        }
+        transaction
+            .pragma_update(None, "user_version", migrations.len())
+            .map_err(sql_error)?;
+        transaction.commit().map_err(sql_error)?;
+        debug!("Catalogue {} migrated to version {}", path.display(), migrations.len());
+    }
+    Ok(connection)
+}
+
+/// The data directory of a processed document, when it sits in the documents
+/// directory of one that has a catalogue.
+pub fn data_directory_of(document_path: &Path) -> Option<PathBuf> {
+    let directory = document_path.parent()?;
+    if directory.file_name()? != DOCUMENTS_DIRECTORY_NAME {
+        return None;
+    }
+    let data_directory = directory.parent()?;
+    data_directory
+        .join(CATALOGUE_FILE_NAME)
+        .is_file()
+        .then(|| data_directory.to_path_buf())
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| format!("Failed to serialise catalogue entry: {}", e))
+}
+
+/// Inserts or updates the page group row. A processed document keeps that
+/// status when a later run fails or is only preprocessed, since its output is
+/// still in place. Without a stage result the stored one is kept.
+fn upsert_document(
+    transaction: &Transaction,
+    document_id: &str,
+    status: DocumentStatus,
+    selected_pages: &[u32],
+    source_pages: &[PageReference],
+    result: Option<&PagePreprocessStageResult>,
+    error_message: Option<&str>,
+) -> Result<(), String> {
+    let timestamp = now();
+    transaction
+        .execute(
+            "INSERT INTO documents (
+                id, status, selected_pages, source_pages, result, type_name, type_abbr,
+                summary, suggested_file_name, error_message, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?11)
+            ON CONFLICT (id) DO UPDATE SET
+                status = CASE
+                    WHEN documents.status = 'processed' THEN documents.status
+                    ELSE excluded.status
+                END,
+                selected_pages = excluded.selected_pages,
+                source_pages = CASE
+                    WHEN excluded.source_pages = '[]' THEN documents.source_pages
+                    ELSE excluded.source_pages
+                END,
+                result = COALESCE(excluded.result, documents.result),
+                type_name = IIF(excluded.result IS NULL, documents.type_name, excluded.type_name),
+                type_abbr = IIF(excluded.result IS NULL, documents.type_abbr, excluded.type_abbr),
+                summary = IIF(excluded.result IS NULL, documents.summary, excluded.summary),
+                suggested_file_name = IIF(
+                    excluded.result IS NULL,
+                    documents.suggested_file_name,
+                    excluded.suggested_file_name
+                ),
+                error_message = excluded.error_message,
+                updated_at = excluded.updated_at",
+            params![
+                document_id,
+                status.as_str(),
+                to_json(&selected_pages)?,
+                to_json(&source_pages)?,
+                result.map(to_json).transpose()?,
+                result.map(|result| result.type_name.as_str()).unwrap_or_default(),
+                result.map(|result| result.type_abbr.as_str()).unwrap_or_default(),
+                result.map(|result| result.summary.as_str()).unwrap_or_default(),
+                result
+                    .map(|result| result.suggested_file_name.as_str())
+                    .unwrap_or_default(),
+                error_message,
+                timestamp,
+            ],
+        )
+        .map_err(sql_error)?;
+    Ok(())
+}
+
+pub fn record_preprocessed(
+    data_directory: &Path,
+    document_id: &str,
+    selected_pages: &[u32],
+    result: &PagePreprocessStageResult,
+) -> Result<(), String> {
+    let mut connection = open(data_directory)?;
+    let transaction = connection.transaction().map_err(sql_error)?;
+    upsert_document(
+        &transaction,
+        document_id,
+        DocumentStatus::Preprocessed,
+        selected_pages,
+        &[],
+        Some(result),
+        None,
+    )?;
+    transaction.commit().map_err(sql_error)
+}
+
+pub fn record_failure(
+    data_directory: &Path,
+    document_id: &str,
+    selected_pages: &[u32],
+    source_pages: &[PageReference],
+    error_message: &str,
+) -> Result<(), String> {
+    let mut connection = open(data_directory)?;
+    let transaction = connection.transaction().map_err(sql_error)?;
+    upsert_document(
+        &transaction,
+        document_id,
+        DocumentStatus::Failed,
+        selected_pages,
+        source_pages,
+        None,
+        Some(error_message),
+    )?;
+    transaction.commit().map_err(sql_error)
+}
+
+/// Records a successful run with the hashes of its sources and of every
+/// output file, replacing the outputs of earlier runs.
+pub fn record_processed(
+    data_directory: &Path,
+    success: &DocumentProcessStageSuccess,
+    sources: &[SourceDocument],
+    parts: &[SidecarPart],
+) -> Result<(), String> {
+    let mut connection = open(data_directory)?;
+    let transaction = connection.transaction().map_err(sql_error)?;
+    upsert_document(
+        &transaction,
+        &success.id,
+        DocumentStatus::Processed,
+        &success.selected_pages,
+        &success.source_pages,
+        Some(&success.page_preprocess_stage_result),
+        None,
+    )?;
+
+    let timestamp = now();
+    transaction
+        .execute(
+            "DELETE FROM document_sources WHERE document_id = ?1",
+            params![success.id],
+        )
+        .map_err(sql_error)?;
+    for source in sources {
+        let size = fs::metadata(&source.path).map(|metadata| metadata.len()).ok();
+        transaction
+            .execute(
+                "INSERT INTO sources (path, sha256, size, status, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT (path) DO UPDATE SET
+                    sha256 = excluded.sha256,
+                    size = excluded.size,
+                    status = excluded.status,
+                    updated_at = excluded.updated_at",
+                params![
+                    source.path,
+                    source.sha256,
+                    size,
+                    FileStatus::Present.as_str(),
+                    timestamp
+                ],
+            )
+            .map_err(sql_error)?;
+        transaction
+            .execute(
+                "INSERT INTO document_sources (document_id, source_path) VALUES (?1, ?2)",
+                params![success.id, source.path],
+            )
+            .map_err(sql_error)?;
+    }
+
+    transaction
+        .execute("DELETE FROM outputs WHERE document_id = ?1", params![success.id])
+        .map_err(sql_error)?;
+    for (index, (part, path)) in parts.iter().zip(&success.part_paths).enumerate() {
+        transaction
+            .execute(
+                "INSERT INTO outputs (document_id, part_number, path, sha256, size, status, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    success.id,
+                    index + 1,
+                    path,
+                    part.sha256,
+                    part.size,
+                    FileStatus::Present.as_str(),
+                    timestamp
+                ],
+            )
+            .map_err(sql_error)?;
+    }
+    transaction.commit().map_err(sql_error)
+}
+
+/// Stores the perceptual hashes of extracted page images, keyed by their
+/// images directory, or by the PDF for pages hashed without extraction.
+/// SQLite integers are signed, so the hashes are stored with their bits
+/// reinterpreted.
+pub fn record_page_hashes(
+    data_directory: &Path,
+    images_directory: &Path,
+    hashes: &[(u32, u64)],
+) -> Result<(), String> {
+    let mut connection = open(data_directory)?;
+    let transaction = connection.transaction().map_err(sql_error)?;
+    let images_directory = images_directory.display().to_string();
+    for (page_number, hash) in hashes {
+        transaction
+            .execute(
+                "INSERT OR REPLACE INTO page_hashes (images_directory, page_number, hash)
+                VALUES (?1, ?2, ?3)",
+                params![images_directory, page_number, *hash as i64],
+            )
+            .map_err(sql_error)?;
+    }
+    transaction.commit().map_err(sql_error)
+}
+
+/// The stored hashes of those `pages` that have one.
+pub fn load_page_hashes(
+    data_directory: &Path,
+    images_directory: &Path,
+    pages: &[u32],
+) -> Result<HashMap<u32, u64>, String> {
+    let connection = open(data_directory)?;
+    let mut statement = connection
+        .prepare("SELECT hash FROM page_hashes WHERE images_directory = ?1 AND page_number = ?2")
+        .map_err(sql_error)?;
+    let images_directory = images_directory.display().to_string();
+    let mut hashes = HashMap::new();
+    for &page in pages {
+        let hash = statement
+            .query_row(params![images_directory, page], |row| row.get::<_, i64>(0))
+            .optional()
+            .map_err(sql_error)?;
+        if let Some(hash) = hash {
+            hashes.insert(page, hash as u64);
+        }
+    }
+    Ok(hashes)
+}
+
+/// What duplicates are matched on, for one processed document.
+pub struct StoredFingerprint {
+    pub document_id: String,
+    pub data_directory: PathBuf,
+    pub page_hashes: Vec<u64>,
+    pub text_signature: Vec<u64>,
+}
+
+/// Replaces the fingerprint of a document recorded as processed in the
+/// catalogue of `data_directory`.
+pub fn record_fingerprint(
+    handle: &AppHandle,
+    data_directory: &Path,
+    document_id: &str,
+    page_hashes: &[u64],
+    text_signature: &[u64],
+) -> Result<(), String> {
+    let connection = open_library(handle)?;
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO fingerprints (
+                document_id, data_directory, page_hashes, text_signature, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                document_id,
+                data_directory.display().to_string(),
+                to_json(&page_hashes)?,
+                to_json(&text_signature)?,
+                now()
+            ],
+        )
+        .map_err(sql_error)?;
+    Ok(())
+}
+
+/// Fingerprints of all documents processed so far other than `document_id`,
+/// whatever source they came from.
+pub fn load_fingerprints(
+    handle: &AppHandle,
+    document_id: &str,
+) -> Result<Vec<StoredFingerprint>, String> {
+    let connection = open_library(handle)?;
+    let mut statement = connection
+        .prepare(
+            "SELECT document_id, data_directory, page_hashes, text_signature
+            FROM fingerprints WHERE document_id <> ?1",
+        )
+        .map_err(sql_error)?;
+    let rows = statement
+        .query_map(params![document_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })
+        .map_err(sql_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(sql_error)?;
+    rows.into_iter()
+        .map(|(document_id, data_directory, page_hashes, text_signature)| {
+            let parse_error =
+                |e: serde_json::Error| format!("Corrupt fingerprint of {}: {}", document_id, e);
+            Ok(StoredFingerprint {
+                page_hashes: serde_json::from_str(&page_hashes).map_err(parse_error)?,
+                text_signature: serde_json::from_str(&text_signature).map_err(parse_error)?,
+                document_id,
+                data_directory: PathBuf::from(data_directory),
+            })
+        })
+        .collect()
+}
+
+/// Every document of the search index, split into those in search and those
+/// whose outputs are all in the trash.
+pub fn load_search_documents(
+    handle: &AppHandle,
+) -> Result<(Vec<IndexedDocument>, Vec<IndexedDocument>), String> {
+    let connection = open_library(handle)?;
+    let mut statement = connection
+        .prepare("SELECT document_id, document, trashed FROM search_documents")
+        .map_err(sql_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+            ))
+        })
+        .map_err(sql_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(sql_error)?;
+    let mut documents = Vec::new();
+    let mut trashed_documents = Vec::new();
+    for (document_id, document, trashed) in rows {
+        let document: IndexedDocument = serde_json::from_str(&document)
+            .map_err(|e| format!("Corrupt search entry of {}: {}", document_id, e))?;
+        if trashed {
+            trashed_documents.push(document);
+        } else {
+            documents.push(document);
+        }
+    }
+    Ok((documents, trashed_documents))
+}
+
+/// Writes the search entry of one document, replacing the stored one.
+pub fn save_search_document(
+    handle: &AppHandle,
+    document: &IndexedDocument,
+    trashed: bool,
+) -> Result<(), String> {
+    let connection = open_library(handle)?;
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO search_documents (document_id, document, trashed, updated_at)
+            VALUES (?1, ?2, ?3, ?4)",
+            params![document.document_id, to_json(document)?, trashed, now()],
+        )
+        .map_err(sql_error)?;
+    Ok(())
+}
+
+pub fn delete_search_documents(handle: &AppHandle, document_ids: &[String]) -> Result<(), String> {
+    let mut connection = open_library(handle)?;
+    let transaction = connection.transaction().map_err(sql_error)?;
+    for document_id in document_ids {
+        transaction
+            .execute(
+                "DELETE FROM search_documents WHERE document_id = ?1",
+                params![document_id],
+            )
+            .map_err(sql_error)?;
+    }
+    transaction.commit().map_err(sql_error)
+}
+
+/// The summary and first output in place of a document that is still
+/// processed in the catalogue of `data_directory`. `None` when the document
+/// was deleted since, or the data directory is gone.
+pub fn load_processed_document(
+    data_directory: &Path,
+    document_id: &str,
+) -> Result<Option<(String, Option<String>)>, String> {
+    if !data_directory.join(CATALOGUE_FILE_NAME).is_file() {
+        return Ok(None);
+    }
+    let connection = open(data_directory)?;
+    connection
+        .query_row(
+            "SELECT d.summary, (
+                SELECT o.path FROM outputs o
+                WHERE o.document_id = d.id AND o.status = ?2
+                ORDER BY o.part_number LIMIT 1
+            )
+            FROM documents d WHERE d.id = ?1 AND d.status = ?3",
+            params![
+                document_id,
+                FileStatus::Present.as_str(),
+                DocumentStatus::Processed.as_str()
+            ],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+        .optional()
+        .map_err(sql_error)
+}
+
+/// Takes the next `count` Bates numbers of `prefix`, starting the counter at
+/// `start` when the case has none yet, and returns the first of them. The
+/// counters are app-wide, so numbers run on across the sources of a case.
+pub fn allocate_bates_numbers(
+    handle: &AppHandle,
+    prefix: &str,
+    count: u64,
+    start: u64,
+) -> Result<u64, String> {
+    let mut connection = open_library(handle)?;
+    let transaction = connection
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(sql_error)?;
+    let first = transaction
+        .query_row(
+            "SELECT next_number FROM bates_counters WHERE prefix = ?1",
+            params![prefix],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(sql_error)?
+        .map(|number| number as u64)
+        .unwrap_or(start);
+    transaction
+        .execute(
+            "INSERT OR REPLACE INTO bates_counters (prefix, next_number, updated_at)
+            VALUES (?1, ?2, ?3)",
+            params![prefix, (first + count) as i64, now()],
+        )
+        .map_err(sql_error)?;
+    transaction.commit().map_err(sql_error)?;
+    debug!("Bates numbers {} to {} of {} taken", first, first + count - 1, prefix);
+    Ok(first)
+}
+
+/// Gives back Bates numbers taken by a document that failed, unless later
+/// numbers were taken since.
+pub fn release_bates_numbers(
+    handle: &AppHandle,
+    prefix: &str,
+    first: u64,
+    count: u64,
+) -> Result<(), String> {
+    let connection = open_library(handle)?;
+    let released = connection
+        .execute(
+            "UPDATE bates_counters SET next_number = ?2, updated_at = ?4
+            WHERE prefix = ?1 AND next_number = ?3",
+            params![prefix, first as i64, (first + count) as i64, now()],
+        )
+        .map_err(sql_error)?;
+    if released == 0 {
+        debug!("Bates numbers of {} from {} kept, later ones are taken", prefix, first);
+    }
+    Ok(())
+}
+
+/// Marks the output at `path` as trashed or back in place. A document whose
+/// outputs are all trashed is marked deleted, and processed again on restore.
+pub fn set_output_status(data_directory: &Path, path: &str, status: FileStatus) -> Result<(), String> {
+    let mut connection = open(data_directory)?;
+    let transaction = connection.transaction().map_err(sql_error)?;
+    let timestamp = now();
+    transaction
+        .execute(
+            "UPDATE outputs SET status = ?2, updated_at = ?3 WHERE path = ?1",
+            params![path, status.as_str(), timestamp],
+        )
+        .map_err(sql_error)?;
+    transaction
+        .execute(
+            "UPDATE documents SET
+                status = CASE
+                    WHEN NOT EXISTS (
+                        SELECT 1 FROM outputs
+                        WHERE outputs.document_id = documents.id AND outputs.status <> ?2
+                    ) THEN ?3
+                    ELSE ?4
+                END,
+                updated_at = ?5
+            WHERE id IN (SELECT document_id FROM outputs WHERE path = ?1)",
+            params![
+                path,
+                FileStatus::Deleted.as_str(),
+                DocumentStatus::Deleted.as_str(),
+                DocumentStatus::Processed.as_str(),
+                timestamp
+            ],
+        )
+        .map_err(sql_error)?;
+    transaction.commit().map_err(sql_error)
+}
+
+/// Follows an output renamed from the app.
+pub fn move_output(data_directory: &Path, old_path: &str, new_path: &str) -> Result<(), String> {
+    let connection = open(data_directory)?;
+    connection
+        .execute(
+            "UPDATE outputs SET path = ?2, updated_at = ?3 WHERE path = ?1",
+            params![old_path, new_path, now()],
+        )
+        .map_err(sql_error)?;
+    Ok(())
+}
+
+fn load_entry(connection: &Connection, document_id: &str) -> Result<Option<CatalogueEntry>, String> {
+    let row = connection
+        .query_row(
+            "SELECT status, selected_pages, source_pages, result, error_message, created_at, updated_at
+            FROM documents WHERE id = ?1",
+            params![document_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(sql_error)?;
+    let Some((status, selected_pages, source_pages, result, error_message, created_at, updated_at)) =
+        row
+    else {
+        return Ok(None);
+    };
+    let parse_error = |e: serde_json::Error| format!("Corrupt catalogue entry {}: {}", document_id, e);
+
+    let mut statement = connection
+        .prepare(
+            "SELECT s.path, s.sha256, s.size, s.status FROM document_sources d
+            JOIN sources s ON s.path = d.source_path
+            WHERE d.document_id = ?1 ORDER BY s.path",
+        )
+        .map_err(sql_error)?;
+    let sources = statement
+        .query_map(params![document_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<u64>>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })
+        .map_err(sql_error)?
+        .map(|row| {
+            let (path, sha256, size, status) = row.map_err(sql_error)?;
+            Ok(CatalogueSource {
+                path,
+                sha256,
+                size,
+                status: FileStatus::parse(&status)?,
+            })
+        })
+        .collect::<Result<Vec<CatalogueSource>, String>>()?;
+
+    let mut statement = connection
+        .prepare(
+            "SELECT part_number, path, sha256, size, status FROM outputs
+            WHERE document_id = ?1 ORDER BY part_number",
+        )
+        .map_err(sql_error)?;
+    let outputs = statement
+        .query_map(params![document_id], |row| {
+            Ok((
+                row.get::<_, u32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, u64>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })
+        .map_err(sql_error)?
+        .map(|row| {
+            let (part_number, path, sha256, size, status) = row.map_err(sql_error)?;
+            Ok(CatalogueOutput {
+                part_number,
+                path,
+                sha256,
+                size,
+                status: FileStatus::parse(&status)?,
+            })
+        })
+        .collect::<Result<Vec<CatalogueOutput>, String>>()?;
+
+    Ok(Some(CatalogueEntry {
+        document_id: document_id.to_string(),
+        status: DocumentStatus::parse(&status)?,
+        selected_pages: serde_json::from_str(&selected_pages).map_err(parse_error)?,
+        source_pages: serde_json::from_str(&source_pages).map_err(parse_error)?,
+        page_preprocess_stage_result: result
+            .map(|result| serde_json::from_str(&result))
+            .transpose()
+            .map_err(parse_error)?,
+        error_message,
+        created_at,
+        updated_at,
+        sources,
+        outputs,
+    }))
+}
+
+/// `%text%` for `LIKE … ESCAPE '\'`, with the wildcards in `text` escaped.
+fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+fn list(connection: &Connection, filter: &CatalogueFilter) -> Result<CataloguePage, String> {
+    let mut conditions: Vec<&str> = Vec::new();
+    let mut values: Vec<Value> = Vec::new();
+    if let Some(status) = filter.status {
+        conditions.push("d.status = ?");
+        values.push(Value::Text(status.as_str().to_string()));
+    }
+    if let Some(type_name) = filter.type_name.as_ref().filter(|value| !value.is_empty()) {
+        conditions.push("(d.type_name = ? COLLATE NOCASE OR d.type_abbr = ? COLLATE NOCASE)");
+        values.push(Value::Text(type_name.clone()));
+        values.push(Value::Text(type_name.clone()));
+    }
+    if let Some(source) = filter.source_document.as_ref().filter(|value| !value.is_empty()) {
+        conditions.push(
+            "EXISTS (SELECT 1 FROM document_sources s WHERE s.document_id = d.id AND s.source_path = ?)",
+        );
+        values.push(Value::Text(source.clone()));
+    }
+    if let Some(text) = filter.text.as_ref().filter(|value| !value.trim().is_empty()) {
+        conditions.push(
+            "(d.summary LIKE ? ESCAPE '\\' OR d.suggested_file_name LIKE ? ESCAPE '\\'
+            OR EXISTS (SELECT 1 FROM outputs o WHERE o.document_id = d.id AND o.path LIKE ? ESCAPE '\\'))",
+        );
+        let pattern = Value::Text(like_pattern(text.trim()));
+        values.extend([pattern.clone(), pattern.clone(), pattern]);
+    }
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+
+    let total: u64 = connection
+        .query_row(
+            &format!("SELECT COUNT(*) FROM documents d {}", where_clause),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
+        )
+        .map_err(sql_error)?;
+
+    let offset = filter.offset.unwrap_or(0);
+    let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    values.push(Value::Integer(i64::from(limit)));
+    values.push(Value::Integer(i64::from(offset)));
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT d.id FROM documents d {} ORDER BY d.updated_at DESC, d.id LIMIT ? OFFSET ?",
+            where_clause
+        ))
+        .map_err(sql_error)?;
+    let ids = statement
+        .query_map(params_from_iter(values.iter()), |row| row.get::<_, String>(0))
+        .map_err(sql_error)?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(sql_error)?;
+
+    let mut entries = Vec::with_capacity(ids.len());
+    for id in ids {
+        entries.extend(load_entry(connection, &id)?);
+    }
+    Ok(CataloguePage {
+        total,
+        offset,
+        entries,
+    })
+}
+
+/// Compares the catalogue with the files on disk. Outputs that disappeared
+/// are looked for among the untracked PDFs of the documents directory by size
+/// and hash, so renames made outside the app are followed.
+fn reconcile(connection: &mut Connection, data_directory: &Path) -> Result<ReconcileReport, String> {
+    let mut report = ReconcileReport::default();
+    let outputs = {
+        let mut statement = connection
+            .prepare("SELECT document_id, part_number, path, sha256, size, status FROM outputs")
+            .map_err(sql_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, u32>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, u64>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })
+            .map_err(sql_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sql_error)?;
+        rows
+    };
+    let tracked: HashSet<&str> = outputs.iter().map(|output| output.2.as_str()).collect();
+
+    let documents_directory = data_directory.join(DOCUMENTS_DIRECTORY_NAME);
+    let mut untracked: Vec<(PathBuf, u64)> = match fs::read_dir(&documents_directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"))
+                    && !tracked.contains(path.display().to_string().as_str())
+            })
+            .filter_map(|path| {
+                let size = fs::metadata(&path).ok()?.len();
+                Some((path, size))
+            })
+            .collect(),
+        Err(e) => {
+            debug!("Failed to read {}: {}", documents_directory.display(), e);
+            Vec::new()
+        }
+    };
+    let mut candidate_hashes: HashMap<PathBuf, String> = HashMap::new();
+
+    let transaction = connection.transaction().map_err(sql_error)?;
+    let timestamp = now();
+    for (document_id, part_number, path, sha256, size, status) in &outputs {
+        let status = FileStatus::parse(status)?;
+        if status == FileStatus::Deleted {
+            continue;
+        }
+        report.checked_outputs += 1;
+        let set_status = |status: FileStatus| {
+            transaction
+                .execute(
+                    "UPDATE outputs SET status = ?3, updated_at = ?4
+                    WHERE document_id = ?1 AND part_number = ?2",
+                    params![document_id, part_number, status.as_str(), timestamp],
+                )
+                .map_err(sql_error)
+        };
+
+        if Path::new(path).is_file() {
+            if status == FileStatus::Missing {
+                set_status(FileStatus::Present)?;
+                report.reappeared.push(path.clone());
+            }
+            if sha256_file(Path::new(path))? != *sha256 {
+                report.changed.push(path.clone());
+            }
+            continue;
+        }
+
+        let mut found = None;
+        for (index, (candidate, candidate_size)) in untracked.iter().enumerate() {
+            if candidate_size != size {
+                continue;
+            }
+            let hash = match candidate_hashes.get(candidate) {
+                Some(hash) => hash.clone(),
+                None => {
+                    let hash = sha256_file(candidate)?;
+                    candidate_hashes.insert(candidate.clone(), hash.clone());
+                    hash
+                }
+            };
+            if hash == *sha256 {
+                found = Some(index);
+                break;
+            }
+        }
+        match found {
+            Some(index) => {
+                let (new_path, _) = untracked.remove(index);
+                let new_path = new_path.display().to_string();
+                transaction
+                    .execute(
+                        "UPDATE outputs SET path = ?3, status = ?4, updated_at = ?5
+                        WHERE document_id = ?1 AND part_number = ?2",
+                        params![
+                            document_id,
+                            part_number,
+                            new_path,
+                            FileStatus::Present.as_str(),
+                            timestamp
+                        ],
+                    )
+                    .map_err(sql_error)?;
+                report.moved.push(MovedOutput {
+                    document_id: document_id.clone(),
+                    old_path: path.clone(),
+                    new_path,
+                });
+            }
+            None => {
+                if status != FileStatus::Missing {
+                    set_status(FileStatus::Missing)?;
+                }
+                report.missing.push(path.clone());
+            }
+        }
+    }
+
+    let sources = {
+        let mut statement = transaction
+            .prepare("SELECT path, status FROM sources")
+            .map_err(sql_error)?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(sql_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sql_error)?;
+        rows
+    };
+    for (path, status) in sources {
+        let exists = Path::new(&path).is_file();
+        let status = FileStatus::parse(&status)?;
+        let new_status = if exists {
+            FileStatus::Present
+        } else {
+            FileStatus::Missing
+        };
+        if status != new_status {
+            transaction
+                .execute(
+                    "UPDATE sources SET status = ?2, updated_at = ?3 WHERE path = ?1",
+                    params![path, new_status.as_str(), timestamp],
+                )
+                .map_err(sql_error)?;
+        }
+        if !exists {
+            report.missing_sources.push(path);
+        }
+    }
+    transaction.commit().map_err(sql_error)?;
+
+    report.untracked = untracked
+        .into_iter()
+        .map(|(path, _)| path.display().to_string())
+        .collect();
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn list_catalogue(
+    handle: AppHandle,
+    data_directory: String,
+    filter: Option<CatalogueFilter>,
+) -> Result<CataloguePage, String> {
+    let data_directory = PathScope::current(&handle)?.check(&data_directory)?;
+    let connection = open(&data_directory)?;
+    list(&connection, &filter.unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn get_catalogue_entry(
+    handle: AppHandle,
+    data_directory: String,
+    document_id: String,
+) -> Result<Option<CatalogueEntry>, String> {
+    let data_directory = PathScope::current(&handle)?.check(&data_directory)?;
+    let connection = open(&data_directory)?;
+    load_entry(&connection, &document_id)
+}
+
+#[tauri::command]
+pub async fn reconcile_catalogue(
+    handle: AppHandle,
+    data_directory: String,
+) -> Result<ReconcileReport, String> {
+    let data_directory = PathScope::current(&handle)?.check(&data_directory)?;
+    let mut connection = open(&data_directory)?;
+    reconcile(&mut connection, &data_directory)
+}
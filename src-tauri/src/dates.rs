@@ -0,0 +1,261 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+const MIN_YEAR: i32 = 1000;
+const MAX_YEAR: i32 = 9999;
+
+/// Years a number in free text must fall in to be taken for a date.
+const PLAUSIBLE_YEARS: std::ops::RangeInclusive<i32> = 1900..=2100;
+
+const MONTH_NAMES: [&str; 12] = [
+    "janeiro",
+    "fevereiro",
+    "março",
+    "abril",
+    "maio",
+    "junho",
+    "julho",
+    "agosto",
+    "setembro",
+    "outubro",
+    "novembro",
+    "dezembro",
+];
+
+lazy_static! {
+    static ref ISO_FULL: Regex = Regex::new(r"^(\d{4})-(\d{1,2})-(\d{1,2})$").unwrap();
+    static ref ISO_YEAR_MONTH: Regex = Regex::new(r"^(\d{4})-(\d{1,2})$").unwrap();
+    static ref ISO_MONTH_DAY: Regex = Regex::new(r"^(\d{1,2})-(\d{1,2})$").unwrap();
+    static ref YEAR_ONLY: Regex = Regex::new(r"^(\d{4})$").unwrap();
+    static ref BR_FULL: Regex = Regex::new(r"^(\d{1,2})[/.](\d{1,2})[/.](\d{4})$").unwrap();
+    static ref BR_MONTH_YEAR: Regex = Regex::new(r"^(\d{1,2})/(\d{4})$").unwrap();
+    static ref BR_DAY_MONTH: Regex = Regex::new(r"^(\d{1,2})/(\d{1,2})$").unwrap();
+    static ref WRITTEN: Regex = Regex::new(
+        r"^(?:(\d{1,2})\s*(?:º|°|o)?\s+(?:de\s+)?)?([a-zç]+)\.?(?:\s+(?:de\s+)?(\d{4}))?$"
+    )
+    .unwrap();
+    static ref NUMERIC_TOKEN: Regex = Regex::new(r"\d+(?:[-/.]\d+)+").unwrap();
+    static ref DATE_SHAPE: Regex =
+        Regex::new(r"^(?:\d{4}-\d{2}(?:-\d{2})?|\d{1,2}[/.]\d{1,2}[/.]\d{4})$").unwrap();
+}
+
+/// A calendar date where any component may be missing, as produced by the
+/// dates extraction prompt (`YYYY-MM-DD`, `YYYY-MM`, `MM-DD` or `YYYY`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PartialDate {
+    pub year: Option<i32>,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+}
+
+impl PartialDate {
+    pub fn new(year: Option<i32>, month: Option<u32>, day: Option<u32>) -> Result<Self, String> {
+        match (year, month, day) {
+            (Some(_), _, None) | (_, Some(_), Some(_)) => {}
+            _ => return Err("A date needs a year, a month and day, or both".to_string()),
+        }
+        if let Some(year) = year {
+            if !(MIN_YEAR..=MAX_YEAR).contains(&year) {
+                return Err(format!("Year out of range: {}", year));
+            }
+        }
+        if let Some(month) = month {
+            if !(1..=12).contains(&month) {
+                return Err(format!("Month out of range: {}", month));
+            }
+            if let Some(day) = day {
+                let max_day = days_in_month(year, month);
+                if day == 0 || day > max_day {
+                    return Err(format!("Day out of range for month {}: {}", month, day));
+                }
+            }
+        }
+        Ok(Self { year, month, day })
+    }
+
+    /// Parses ISO partial dates, Brazilian `DD/MM/AAAA` dates and written-out
+    /// Portuguese dates such as "5 de março de 2024".
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let value = input.trim().to_lowercase();
+        if value.is_empty() {
+            return Err("Empty date".to_string());
+        }
+
+        if let Some(caps) = ISO_FULL.captures(&value) {
+            return Self::new(
+                Some(number(&caps[1])?),
+                Some(number(&caps[2])?),
+                Some(number(&caps[3])?),
+            );
+        }
+        if let Some(caps) = ISO_YEAR_MONTH.captures(&value) {
+            return Self::new(Some(number(&caps[1])?), Some(number(&caps[2])?), None);
+        }
+        if let Some(caps) = ISO_MONTH_DAY.captures(&value) {
+            return Self::new(None, Some(number(&caps[1])?), Some(number(&caps[2])?));
+        }
+        if let Some(caps) = YEAR_ONLY.captures(&value) {
+            return Self::new(Some(number(&caps[1])?), None, None);
+        }
+        if let Some(caps) = BR_FULL.captures(&value) {
+            return Self::new(
+                Some(number(&caps[3])?),
+                Some(number(&caps[2])?),
+                Some(number(&caps[1])?),
+            );
+        }
+        if let Some(caps) = BR_MONTH_YEAR.captures(&value) {
+            return Self::new(Some(number(&caps[2])?), Some(number(&caps[1])?), None);
+        }
+        if let Some(caps) = BR_DAY_MONTH.captures(&value) {
+            return Self::new(None, Some(number(&caps[2])?), Some(number(&caps[1])?));
+        }
+        if let Some(caps) = WRITTEN.captures(&value) {
+            let month = month_from_name(&caps[2])
+                .ok_or_else(|| format!("Unknown month name: {}", &caps[2]))?;
+            let day = caps.get(1).map(|m| number(m.as_str())).transpose()?;
+            let year = caps.get(3).map(|m| number(m.as_str())).transpose()?;
+            return Self::new(year, Some(month), day);
+        }
+
+        Err(format!("Unrecognised date: {}", input))
+    }
+
+    /// Formats the date with a strftime-like pattern. Supported specifiers are
+    /// `%Y`, `%y`, `%m`, `%d`, `%B` (Portuguese month name), `%b` and `%%`.
+    /// Specifiers for missing components are dropped along with the separator
+    /// that follows them, so `%Y-%m-%d` on `2024-03` renders as `2024-03`.
+    pub fn format(&self, pattern: &str) -> String {
+        let mut output = String::new();
+        let mut chars = pattern.chars();
+        let mut skip_separator = false;
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                if !skip_separator || c.is_alphanumeric() {
+                    output.push(c);
+                }
+                skip_separator = false;
+                continue;
+            }
+            let rendered = match chars.next() {
+                Some('Y') => self.year.map(|y| format!("{:04}", y)),
+                Some('y') => self.year.map(|y| format!("{:02}", y % 100)),
+                Some('m') => self.month.map(|m| format!("{:02}", m)),
+                Some('d') => self.day.map(|d| format!("{:02}", d)),
+                Some('B') => self.month.map(|m| MONTH_NAMES[m as usize - 1].to_string()),
+                Some('b') => self
+                    .month
+                    .map(|m| MONTH_NAMES[m as usize - 1].chars().take(3).collect()),
+                Some('%') => Some("%".to_string()),
+                Some(other) => Some(format!("%{}", other)),
+                None => Some("%".to_string()),
+            };
+            match rendered {
+                Some(text) => output.push_str(&text),
+                None => skip_separator = true,
+            }
+        }
+        output
+            .trim_end_matches(|c: char| !c.is_alphanumeric())
+            .to_string()
+    }
+}
+
+impl fmt::Display for PartialDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.year, self.month, self.day) {
+            (Some(y), Some(m), Some(d)) => write!(f, "{:04}-{:02}-{:02}", y, m, d),
+            (Some(y), Some(m), None) => write!(f, "{:04}-{:02}", y, m),
+            (Some(y), None, None) => write!(f, "{:04}", y),
+            (None, Some(m), Some(d)) => write!(f, "{:02}-{:02}", m, d),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for PartialDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl PartialOrd for PartialDate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PartialDate {
+    /// Chronological order; dates without a year sort before dated ones and a
+    /// missing month or day sorts before any known one.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.year, self.month, self.day).cmp(&(other.year, other.month, other.day))
+    }
+}
+
+/// Rewrites every date in `text` (`YYYY-MM[-DD]` or `DD/MM/AAAA`, with a
+/// plausible year) to its normalised form. Anything else, such as `NF 2023-15`
+/// or `Processo 1234-5`, is left as written.
+pub fn normalise_dates_in_text(text: &str) -> String {
+    let replaced = NUMERIC_TOKEN.replace_all(text, |caps: &regex::Captures| {
+        if !DATE_SHAPE.is_match(&caps[0]) {
+            return caps[0].to_string();
+        }
+        match PartialDate::parse(&caps[0]) {
+            Ok(date) if date.year.is_some_and(|year| PLAUSIBLE_YEARS.contains(&year)) => {
+                date.to_string()
+            }
+            _ => caps[0].to_string(),
+        }
+    });
+    collapse_separators(&replaced)
+}
+
+fn collapse_separators(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '-' | '_' | ' ') && output.ends_with(['-', '_', ' ']) {
+            continue;
+        }
+        output.push(c);
+    }
+    output
+        .trim_matches(|c| matches!(c, '-' | '_' | ' '))
+        .to_string()
+}
+
+fn number<T: FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid number in date: {}", value))
+}
+
+fn month_from_name(name: &str) -> Option<u32> {
+    let name = name.replace('ç', "c");
+    MONTH_NAMES
+        .iter()
+        .position(|month| {
+            let month = month.replace('ç', "c");
+            month == name || (name.len() >= 3 && month.starts_with(&name))
+        })
+        .map(|index| index as u32 + 1)
+}
+
+fn days_in_month(year: Option<i32>, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 => match year {
+            Some(year) if !is_leap_year(year) => 28,
+            _ => 29,
+        },
+        _ => 0,
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
@@ -1,5 +1,8 @@
 // use super::models::workflows::{ExtractDocumentImagesStage, ExtractDocumentImagesStageSuccess, ExtractDocumentImagesStageError, ProgressState};
 use super::models::workflows::{ExtractDocumentImagesStage, ProgressState};
+use crate::fingerprint::compute_page_hashes;
+use crate::scope::{register_data_directory, PathScope};
+use crate::signatures::{check_document_signatures, write_signature_reports};
 use crate::utilities::call_utility;
 use log::{debug, error, warn};
 use lopdf::Document;
@@ -33,9 +36,14 @@ pub async fn run_extract_document_images_stage(
         "extract_document_images_stage: {:?}",
         extract_document_images_stage
     );
+    let data_directory = register_data_directory(
+        &app,
+        &extract_document_images_stage.document_path,
+        &extract_document_images_stage.data_directory,
+    )?;
+    PathScope::current(&app)?.check_new(&extract_document_images_stage.images_directory)?;
     let document_path = PathBuf::from(&extract_document_images_stage.document_path);
     let document_name = document_path.file_name().unwrap();
-    let data_directory = PathBuf::from(&extract_document_images_stage.data_directory);
     let document_clone_path = data_directory.join(document_name);
     let documents_directory = data_directory.join("documents");
     create_dir_all(&documents_directory).map_err(|e| {
@@ -51,6 +59,7 @@ pub async fn run_extract_document_images_stage(
         error!("Failed to copy document to data directory: {}", e);
         format!("Failed to copy document to data directory: {}", e)
     })?;
+    report_signatures(&app, &data_directory, &document_clone_path);
 
     let document = load_document(&PathBuf::from(&document_clone_path))?;
     let total_pages = document.get_pages().len();
@@ -71,6 +80,7 @@ pub async fn run_extract_document_images_stage(
 
     process_missing_pages(
         app,
+        data_directory,
         PathBuf::from(&document_clone_path),
         PathBuf::from(&images_directory),
         missing_pages,
@@ -80,6 +90,26 @@ pub async fn run_extract_document_images_stage(
     .await
 }
 
+/// Checks the signatures of the copied document, which is identical to the
+/// original, saves the reports for the later stages and sends them to the
+/// frontend. Extraction goes on if this fails.
+fn report_signatures(app: &AppHandle, data_directory: &Path, document_path: &Path) {
+    let reports = check_document_signatures(app, document_path)
+        .and_then(|reports| write_signature_reports(data_directory, &reports).map(|_| reports));
+    match reports {
+        Ok(reports) => {
+            if let Err(e) = app.emit("document-signatures", &reports) {
+                warn!("Failed to emit document-signatures event: {}", e);
+            }
+        }
+        Err(e) => warn!(
+            "Failed to check the signatures of {}: {}",
+            document_path.display(),
+            e
+        ),
+    }
+}
+
 fn load_document(document_path: &PathBuf) -> Result<Document, String> {
     Document::load(document_path).map_err(|e| {
         error!("Failed to load PDF: {}", e);
@@ -135,6 +165,7 @@ fn get_missing_pages(
 
 async fn process_missing_pages(
     app: AppHandle,
+    data_directory: PathBuf,
     document_path: PathBuf,
     images_directory: PathBuf,
     missing_pages: Vec<usize>,
@@ -190,6 +221,14 @@ async fn process_missing_pages(
             &mut progress_state,
             &images_directory,
         )?;
+
+        // Hashes for spotting pages that were processed before
+        let hashed_pages: Vec<u32> = successful_pages.iter().map(|&page| page as u32).collect();
+        if let Err(e) =
+            compute_page_hashes(&app, &data_directory, &images_directory, &hashed_pages).await
+        {
+            warn!("Failed to hash extracted pages: {}", e);
+        }
     }
 
     app.unlisten(cancel_listener);
@@ -0,0 +1,353 @@
+use log::{debug, error};
+use std::{collections::HashMap, fs, path::Path};
+use tauri::AppHandle;
+
+use super::catalogue::{
+    load_fingerprints, load_page_hashes, load_processed_document, record_page_hashes,
+};
+use super::models::workflows::{DuplicateCandidate, PageReference};
+use super::ocr::MAGICK_UTILITY;
+use super::search::terms;
+use super::utilities::call_utility;
+use super::workspace::ProcessingWorkspace;
+
+/// Width and height the page images are reduced to for the difference hash;
+/// one column more than bits per row, as each bit compares two neighbours.
+const HASH_WIDTH: usize = 9;
+const HASH_HEIGHT: usize = 8;
+/// Resolution pages of sources without extracted images are rendered at;
+/// the hash only needs a thumbnail.
+const SOURCE_DENSITY: &str = "36";
+/// Pages whose hashes differ in at most this many of the 64 bits are taken
+/// to be the same page, e.g. the same sheet scanned twice.
+const MAX_PAGE_DISTANCE: u32 = 10;
+/// Words per shingle of the text signature.
+const SHINGLE_SIZE: usize = 3;
+/// Number of min-hashes in a text signature.
+const SIGNATURE_LENGTH: u64 = 64;
+const MIN_PAGE_SIMILARITY: f32 = 0.5;
+const MIN_TEXT_SIMILARITY: f32 = 0.7;
+const MAX_DUPLICATES: usize = 5;
+
+/// Difference hash of a `HASH_WIDTH` by `HASH_HEIGHT` greyscale image: one
+/// bit per pixel, set when it is brighter than its right neighbour.
+fn difference_hash(pixels: &[u8]) -> u64 {
+    let mut hash = 0;
+    for row in pixels.chunks_exact(HASH_WIDTH) {
+        for pair in row.windows(2) {
+            hash = (hash << 1) | u64::from(pair[0] > pair[1]);
+        }
+    }
+    hash
+}
+
+/// Computes the hashes of the extracted images of `pages` with one ImageMagick
+/// run and stores them in the catalogue of `data_directory`.
+pub async fn compute_page_hashes(
+    handle: &AppHandle,
+    data_directory: &Path,
+    images_directory: &Path,
+    pages: &[u32],
+) -> Result<HashMap<u32, u64>, String> {
+    let inputs = pages
+        .iter()
+        .map(|page| {
+            images_directory
+                .join(format!("{}.webp", page))
+                .display()
+                .to_string()
+        })
+        .collect();
+    hash_images(handle, data_directory, images_directory, inputs, pages).await
+}
+
+/// Computes the hashes of `pages` of the PDF at `source`, rendered by
+/// ImageMagick, and stores them in the catalogue under the source path.
+async fn compute_source_hashes(
+    handle: &AppHandle,
+    data_directory: &Path,
+    source: &Path,
+    pages: &[u32],
+) -> Result<HashMap<u32, u64>, String> {
+    let mut inputs = vec!["-density".to_owned(), SOURCE_DENSITY.to_owned()];
+    inputs.extend(
+        pages
+            .iter()
+            .map(|page| format!("{}[{}]", source.display(), page.saturating_sub(1))),
+    );
+    hash_images(handle, data_directory, source, inputs, pages).await
+}
+
+/// Reduces each image of `inputs`, one per page of `pages`, to its difference
+/// hash and records the hashes under `key`. The reduced images are written to
+/// a workspace of their own, so concurrent stages cannot read each other's.
+async fn hash_images(
+    handle: &AppHandle,
+    data_directory: &Path,
+    key: &Path,
+    mut args: Vec<String>,
+    pages: &[u32],
+) -> Result<HashMap<u32, u64>, String> {
+    if pages.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let workspace = ProcessingWorkspace::new(data_directory)?;
+    args.extend([
+        "-colorspace".to_owned(),
+        "Gray".to_owned(),
+        "-resize".to_owned(),
+        format!("{}x{}!", HASH_WIDTH, HASH_HEIGHT),
+        "-depth".to_owned(),
+        "8".to_owned(),
+        "+adjoin".to_owned(),
+        format!("gray:{}", workspace.path("dhash-%d.gray").display()),
+    ]);
+    if !call_utility(handle.clone(), MAGICK_UTILITY.to_owned(), args, false).await {
+        error!("Failed to hash pages {:?} of {}", pages, key.display());
+        return Err(format!(
+            "Failed to hash pages {:?} of {}",
+            pages,
+            key.display()
+        ));
+    }
+
+    let mut hashes = HashMap::new();
+    for (index, &page) in pages.iter().enumerate() {
+        let path = workspace.path(&format!("dhash-{}.gray", index));
+        let pixels = fs::read(&path).map_err(|e| {
+            error!("Failed to read {}: {}", path.display(), e);
+            format!("Failed to read {}: {}", path.display(), e)
+        })?;
+        if pixels.len() != HASH_WIDTH * HASH_HEIGHT {
+            return Err(format!(
+                "Unexpected size of {}: {} bytes",
+                path.display(),
+                pixels.len()
+            ));
+        }
+        hashes.insert(page, difference_hash(&pixels));
+    }
+    record_page_hashes(
+        data_directory,
+        key,
+        &hashes.iter().map(|(&page, &hash)| (page, hash)).collect::<Vec<_>>(),
+    )?;
+    debug!("Hashed pages {:?} of {}", pages, key.display());
+    Ok(hashes)
+}
+
+/// Hashes of those `pages` that could be hashed, computing the ones the
+/// catalogue does not have: from the extracted images in `images_directory`,
+/// or rendered from the PDF at `source` when the pages were not extracted.
+async fn cached_page_hashes(
+    handle: &AppHandle,
+    data_directory: &Path,
+    images_directory: &Path,
+    source: Option<&Path>,
+    pages: &[u32],
+) -> Result<HashMap<u32, u64>, String> {
+    let key = source.unwrap_or(images_directory);
+    let mut hashes = load_page_hashes(data_directory, key, pages)?;
+    let missing: Vec<u32> = pages
+        .iter()
+        .copied()
+        .filter(|page| !hashes.contains_key(page))
+        .collect();
+    let computed = match source {
+        Some(source) => {
+            compute_source_hashes(handle, data_directory, source, &missing).await?
+        }
+        None => compute_page_hashes(handle, data_directory, images_directory, &missing).await?,
+    };
+    hashes.extend(computed);
+    Ok(hashes)
+}
+
+/// Hashes of `pages` in order, computing those the catalogue does not have.
+pub async fn page_hashes(
+    handle: &AppHandle,
+    data_directory: &Path,
+    images_directory: &Path,
+    pages: &[u32],
+) -> Result<Vec<u64>, String> {
+    let hashes = cached_page_hashes(handle, data_directory, images_directory, None, pages).await?;
+    Ok(pages.iter().filter_map(|page| hashes.get(page).copied()).collect())
+}
+
+/// Hashes of the pages of a composition in order. Pages of `document_path`
+/// come from its extracted images in `images_directory`; pages of other
+/// sources, which have none, are rendered from their PDF.
+pub async fn composition_hashes(
+    handle: &AppHandle,
+    data_directory: &Path,
+    images_directory: &Path,
+    document_path: &str,
+    references: &[PageReference],
+) -> Result<Vec<u64>, String> {
+    let mut hashes: HashMap<(&str, u32), u64> = HashMap::new();
+    for (index, reference) in references.iter().enumerate() {
+        let source = reference.document_path.as_str();
+        if references[..index]
+            .iter()
+            .any(|earlier| earlier.document_path == source)
+        {
+            continue;
+        }
+        let pages: Vec<u32> = references
+            .iter()
+            .filter(|reference| reference.document_path == source)
+            .map(|reference| reference.page_number)
+            .collect();
+        let pdf = (source != document_path).then(|| Path::new(source));
+        let found =
+            cached_page_hashes(handle, data_directory, images_directory, pdf, &pages).await?;
+        hashes.extend(found.into_iter().map(|(page, hash)| ((source, page), hash)));
+    }
+    Ok(references
+        .iter()
+        .filter_map(|reference| {
+            hashes
+                .get(&(reference.document_path.as_str(), reference.page_number))
+                .copied()
+        })
+        .collect())
+}
+
+/// 64-bit FNV-1a, stable across builds unlike the standard library hasher.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// SplitMix64 finaliser, to derive independent hashes from one.
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+/// MinHash signature of the word shingles of `texts`; the share of equal
+/// entries in two signatures estimates how much of the texts overlap. Empty
+/// when there is no text.
+pub fn text_signature(texts: &[String]) -> Vec<u64> {
+    let words: Vec<String> = texts.iter().flat_map(|text| terms(text)).collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+    let shingles: Vec<u64> = words
+        .windows(SHINGLE_SIZE.min(words.len()))
+        .map(|window| fnv1a(&window.join(" ")))
+        .collect();
+    (0..SIGNATURE_LENGTH)
+        .map(|seed| {
+            let seed = mix(seed.wrapping_add(1));
+            shingles
+                .iter()
+                .map(|&shingle| mix(shingle ^ seed))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect()
+}
+
+/// Pairs pages of `left` and `right` whose hashes are close, each page used
+/// once, and returns the pair count and its share of all pages.
+fn match_pages(left: &[u64], right: &[u64]) -> (u32, f32) {
+    let mut unmatched: Vec<u64> = right.to_vec();
+    let mut matched = 0;
+    for hash in left {
+        let closest = unmatched
+            .iter()
+            .enumerate()
+            .map(|(index, other)| (index, (hash ^ other).count_ones()))
+            .filter(|(_, distance)| *distance <= MAX_PAGE_DISTANCE)
+            .min_by_key(|(_, distance)| *distance);
+        if let Some((index, _)) = closest {
+            unmatched.swap_remove(index);
+            matched += 1;
+        }
+    }
+    let similarity = 2.0 * matched as f32 / (left.len() + right.len()) as f32;
+    (matched, similarity)
+}
+
+fn compare_signatures(left: &[u64], right: &[u64]) -> f32 {
+    let equal = left.iter().zip(right).filter(|(a, b)| a == b).count();
+    equal as f32 / left.len().max(right.len()) as f32
+}
+
+/// Processed documents from any source that look like the given pages or
+/// text, most similar first. Either side may be empty.
+pub fn find_duplicates(
+    handle: &AppHandle,
+    document_id: &str,
+    page_hashes: &[u64],
+    text_signature: &[u64],
+) -> Result<Vec<DuplicateCandidate>, String> {
+    let mut similar = Vec::new();
+    for fingerprint in load_fingerprints(handle, document_id)? {
+        let (matched_pages, page_similarity) =
+            if page_hashes.is_empty() || fingerprint.page_hashes.is_empty() {
+                (0, None)
+            } else {
+                let (matched, similarity) = match_pages(page_hashes, &fingerprint.page_hashes);
+                (matched, Some(similarity))
+            };
+        let text_similarity = (!text_signature.is_empty()
+            && !fingerprint.text_signature.is_empty())
+        .then(|| compare_signatures(text_signature, &fingerprint.text_signature));
+
+        let is_duplicate = page_similarity.is_some_and(|value| value >= MIN_PAGE_SIMILARITY)
+            || text_similarity.is_some_and(|value| value >= MIN_TEXT_SIMILARITY);
+        if is_duplicate {
+            let similarity = page_similarity
+                .unwrap_or(0.0)
+                .max(text_similarity.unwrap_or(0.0));
+            similar.push((
+                fingerprint,
+                similarity,
+                page_similarity,
+                matched_pages,
+                text_similarity,
+            ));
+        }
+    }
+    similar.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    // Only documents their catalogue still has as processed count.
+    let mut duplicates: Vec<DuplicateCandidate> = Vec::new();
+    for (fingerprint, similarity, page_similarity, matched_pages, text_similarity) in similar {
+        if duplicates.len() == MAX_DUPLICATES {
+            break;
+        }
+        let processed =
+            load_processed_document(&fingerprint.data_directory, &fingerprint.document_id);
+        let (summary, document_path) = match processed {
+            Ok(Some(processed)) => processed,
+            Ok(None) => continue,
+            Err(e) => {
+                debug!("Skipping duplicate {}: {}", fingerprint.document_id, e);
+                continue;
+            }
+        };
+        duplicates.push(DuplicateCandidate {
+            document_id: fingerprint.document_id,
+            document_path,
+            data_directory: fingerprint.data_directory.display().to_string(),
+            summary,
+            similarity,
+            page_similarity,
+            matched_pages,
+            text_similarity,
+        });
+    }
+    if !duplicates.is_empty() {
+        debug!(
+            "{} looks like {} processed document(s)",
+            document_id,
+            duplicates.len()
+        );
+    }
+    Ok(duplicates)
+}
@@ -0,0 +1,441 @@
+use lazy_static::lazy_static;
+use log::{error, warn};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+use tauri::{AppHandle, Manager};
+
+use super::catalogue::{data_directory_of, move_output};
+use super::models::history::{
+    DocumentRenameHistory, FileFingerprint, RenameEntry, RenameHistoryStore, RenameTrigger,
+};
+use super::search::move_indexed_document;
+use super::sidecar::move_sidecars;
+use super::utilities::unix_timestamp;
+
+const HISTORY_FILE_NAME: &str = "rename-history.json";
+
+lazy_static! {
+    static ref HISTORY_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn history_path(handle: &AppHandle) -> Result<PathBuf, String> {
+    let data_directory = handle.path().app_data_dir().map_err(|e| {
+        error!("Failed to resolve app data directory: {}", e);
+        format!("Failed to resolve app data directory: {}", e)
+    })?;
+    Ok(data_directory.join(HISTORY_FILE_NAME))
+}
+
+fn load_store(path: &Path) -> Result<RenameHistoryStore, String> {
+    if !path.exists() {
+        return Ok(RenameHistoryStore::default());
+    }
+    let content = fs::read_to_string(path).map_err(|e| {
+        error!("Failed to read rename history: {}", e);
+        format!("Failed to read rename history: {}", e)
+    })?;
+    serde_json::from_str(&content).map_err(|e| {
+        error!("Failed to parse rename history: {}", e);
+        format!("Failed to parse rename history: {}", e)
+    })
+}
+
+fn save_store(path: &Path, store: &RenameHistoryStore) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            error!("Failed to create app data directory: {}", e);
+            format!("Failed to create app data directory: {}", e)
+        })?;
+    }
+    let content = serde_json::to_string_pretty(store).map_err(|e| {
+        error!("Failed to serialise rename history: {}", e);
+        format!("Failed to serialise rename history: {}", e)
+    })?;
+    let temporary_path = path.with_extension("json.tmp");
+    fs::write(&temporary_path, content)
+        .and_then(|_| fs::rename(&temporary_path, path))
+        .map_err(|e| {
+            error!("Failed to write rename history: {}", e);
+            format!("Failed to write rename history: {}", e)
+        })
+}
+
+/// Reads the persisted history store while holding the history lock.
+fn read_store(handle: &AppHandle) -> Result<RenameHistoryStore, String> {
+    let _guard = HISTORY_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to lock rename history: {}", e))?;
+    load_store(&history_path(handle)?)
+}
+
+/// Runs `operation` on the persisted history store while holding the history
+/// lock, saving the store afterwards if the operation succeeded.
+fn with_store<T>(
+    handle: &AppHandle,
+    operation: impl FnOnce(&mut RenameHistoryStore) -> Result<T, String>,
+) -> Result<T, String> {
+    with_store_or_revert(handle, |_| {}, operation)
+}
+
+/// Like `with_store`, for operations that have already changed files on disk
+/// when they return: `revert` is given their result to change the files back
+/// when the store cannot be saved, so disk and history stay in step.
+fn with_store_or_revert<T>(
+    handle: &AppHandle,
+    revert: impl FnOnce(&T),
+    operation: impl FnOnce(&mut RenameHistoryStore) -> Result<T, String>,
+) -> Result<T, String> {
+    let _guard = HISTORY_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to lock rename history: {}", e))?;
+    let path = history_path(handle)?;
+    let mut store = load_store(&path)?;
+    let result = operation(&mut store)?;
+    if let Err(e) = save_store(&path, &store) {
+        revert(&result);
+        return Err(e);
+    }
+    Ok(result)
+}
+
+pub fn fingerprint(path: &Path) -> Result<FileFingerprint, String> {
+    let metadata = fs::metadata(path)
+        .map_err(|e| format!("Failed to read metadata of {}: {}", path.display(), e))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    Ok(FileFingerprint {
+        size: metadata.len(),
+        modified,
+    })
+}
+
+impl RenameHistoryStore {
+    fn find(&self, document_id: Option<&str>, current_path: &str) -> Option<&DocumentRenameHistory> {
+        self.documents.iter().find(|history| match document_id {
+            Some(document_id) => history.document_id == document_id,
+            None => history.current_path == current_path,
+        })
+    }
+
+    /// The history of `document_id`, or of the document at `old_path`, created
+    /// empty when there is none yet.
+    fn find_or_insert(
+        &mut self,
+        document_id: Option<&str>,
+        old_path: &str,
+        fingerprint: &FileFingerprint,
+    ) -> &mut DocumentRenameHistory {
+        let position = self.documents.iter().position(|history| match document_id {
+            Some(document_id) => history.document_id == document_id,
+            None => history.current_path == old_path,
+        });
+        let position = position.unwrap_or_else(|| {
+            self.documents.push(DocumentRenameHistory {
+                document_id: document_id.unwrap_or(old_path).to_string(),
+                current_path: old_path.to_string(),
+                fingerprint: fingerprint.clone(),
+                entries: Vec::new(),
+                applied: 0,
+                moves: Vec::new(),
+            });
+            self.documents.len() - 1
+        });
+        &mut self.documents[position]
+    }
+
+    fn find_mut(
+        &mut self,
+        document_id: Option<&str>,
+        current_path: &str,
+    ) -> Option<&mut DocumentRenameHistory> {
+        self.documents.iter_mut().find(|history| match document_id {
+            Some(document_id) => history.document_id == document_id,
+            None => history.current_path == current_path,
+        })
+    }
+}
+
+impl DocumentRenameHistory {
+    /// The file names the document has had, oldest first, without repeats.
+    /// Paths in a trash are not names the document had.
+    pub fn file_names(&self) -> Vec<String> {
+        let first = self
+            .moves
+            .first()
+            .map_or(self.current_path.as_str(), |entry| entry.old_path.as_str());
+        let paths = std::iter::once(first).chain(
+            self.moves
+                .iter()
+                .filter(|entry| entry.trigger != RenameTrigger::Trash)
+                .map(|entry| entry.new_path.as_str()),
+        );
+        let mut names: Vec<String> = Vec::new();
+        for name in paths.filter_map(|path| Path::new(path).file_name()) {
+            let name = name.to_string_lossy().into_owned();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    fn log_move(&mut self, old_path: &str, new_path: &str, trigger: RenameTrigger) {
+        self.moves.push(RenameEntry {
+            old_path: old_path.to_string(),
+            new_path: new_path.to_string(),
+            timestamp: unix_timestamp(),
+            trigger,
+        });
+    }
+
+    /// Fails when the file at `current_path` is gone or differs from the
+    /// recorded fingerprint, meaning it was changed or moved outside the app.
+    fn verify_unchanged(&self) -> Result<(), String> {
+        let current_path = Path::new(&self.current_path);
+        if !current_path.exists() {
+            return Err(format!(
+                "Document was moved or deleted outside the app: {}",
+                self.current_path
+            ));
+        }
+        if fingerprint(current_path)? != self.fingerprint {
+            return Err(format!(
+                "Document was changed outside the app: {}",
+                self.current_path
+            ));
+        }
+        Ok(())
+    }
+
+    /// Renames the document to `destination`, putting it back when its new
+    /// fingerprint cannot be read.
+    fn move_to(&mut self, destination: &str) -> Result<(), String> {
+        self.verify_unchanged()?;
+        if Path::new(destination).exists() {
+            return Err(format!("A file already exists at {}", destination));
+        }
+        rename_document(&self.current_path, destination)?;
+        match fingerprint(Path::new(destination)) {
+            Ok(fingerprint) => {
+                self.current_path = destination.to_string();
+                self.fingerprint = fingerprint;
+                Ok(())
+            }
+            Err(e) => {
+                revert_rename(&self.current_path, destination);
+                Err(e)
+            }
+        }
+    }
+}
+
+fn rename_document(from: &str, to: &str) -> Result<(), String> {
+    fs::rename(from, to).map_err(|e| format!("Failed to rename document: {}", e))?;
+    move_sidecars(Path::new(from), Path::new(to));
+    Ok(())
+}
+
+/// Moves a document renamed from `original` to `renamed` back.
+fn revert_rename(original: &str, renamed: &str) {
+    if let Err(e) = rename_document(renamed, original) {
+        error!("Failed to move {} back to {}: {}", renamed, original, e);
+    }
+}
+
+/// Starts a fresh history for a newly produced document, replacing any history
+/// left by an earlier run of the same stage.
+pub fn start_history(handle: &AppHandle, document_id: &str, path: &str) -> Result<(), String> {
+    let fingerprint = fingerprint(Path::new(path))?;
+    with_store(handle, |store| {
+        store
+            .documents
+            .retain(|history| history.document_id != document_id && history.current_path != path);
+        store.documents.push(DocumentRenameHistory {
+            document_id: document_id.to_string(),
+            current_path: path.to_string(),
+            fingerprint,
+            entries: Vec::new(),
+            applied: 0,
+            moves: Vec::new(),
+        });
+        Ok(())
+    })
+}
+
+/// The id the history of part `part` (1-based) of a split document is kept
+/// under. The first part, like a document that was not split, uses the id of
+/// the document itself.
+pub fn part_document_id(document_id: &str, part: usize) -> String {
+    if part == 1 {
+        document_id.to_string()
+    } else {
+        format!("{}-parte-{}", document_id, part)
+    }
+}
+
+/// Records a rename or move that has already happened on disk. Documents
+/// without a history yet get one keyed by `document_id`, or by their previous
+/// path when no id is known. Recording discards any undone entries.
+pub fn record_rename(
+    handle: &AppHandle,
+    document_id: Option<&str>,
+    old_path: &str,
+    new_path: &str,
+    trigger: RenameTrigger,
+) -> Result<(), String> {
+    let fingerprint = fingerprint(Path::new(new_path))?;
+    with_store(handle, |store| {
+        let history = store.find_or_insert(document_id, old_path, &fingerprint);
+        history.entries.truncate(history.applied);
+        history.entries.push(RenameEntry {
+            old_path: old_path.to_string(),
+            new_path: new_path.to_string(),
+            timestamp: unix_timestamp(),
+            trigger,
+        });
+        history.applied = history.entries.len();
+        history.log_move(old_path, new_path, trigger);
+        history.current_path = new_path.to_string();
+        history.fingerprint = fingerprint;
+        Ok(())
+    })
+}
+
+/// Records a move made by the app that cannot be undone as a rename, such as
+/// sending the document to the trash. Undo and redo refuse to run until the
+/// document is back where the last rename left it.
+pub fn record_move(
+    handle: &AppHandle,
+    document_id: Option<&str>,
+    old_path: &str,
+    new_path: &str,
+    trigger: RenameTrigger,
+) -> Result<(), String> {
+    // Nothing to compare against once the file is in the system trash.
+    let new_fingerprint = fingerprint(Path::new(new_path)).ok();
+    with_store(handle, |store| {
+        let fallback = new_fingerprint.clone().unwrap_or(FileFingerprint {
+            size: 0,
+            modified: 0,
+        });
+        let history = store.find_or_insert(document_id, old_path, &fallback);
+        history.log_move(old_path, new_path, trigger);
+        history.current_path = new_path.to_string();
+        if let Some(fingerprint) = new_fingerprint {
+            history.fingerprint = fingerprint;
+        }
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn get_rename_history(
+    handle: AppHandle,
+    document_id: Option<String>,
+    document_path: Option<String>,
+) -> Result<Option<DocumentRenameHistory>, String> {
+    let store = read_store(&handle)?;
+    Ok(store
+        .find(document_id.as_deref(), document_path.as_deref().unwrap_or_default())
+        .cloned())
+}
+
+/// The file names a processed document has had, oldest first; just its
+/// current name when it was never renamed.
+#[tauri::command]
+pub fn get_file_name_history(
+    handle: AppHandle,
+    document_id: Option<String>,
+    document_path: String,
+) -> Result<Vec<String>, String> {
+    let store = read_store(&handle)?;
+    let names = match store.find(document_id.as_deref(), &document_path) {
+        Some(history) => history.file_names(),
+        None => Path::new(&document_path)
+            .file_name()
+            .map(|name| vec![name.to_string_lossy().into_owned()])
+            .unwrap_or_default(),
+    };
+    Ok(names)
+}
+
+#[tauri::command]
+pub fn undo_rename(
+    handle: AppHandle,
+    document_id: Option<String>,
+    document_path: Option<String>,
+) -> Result<String, String> {
+    let revert = |(old_path, new_path): &(String, String)| revert_rename(old_path, new_path);
+    with_store_or_revert(&handle, revert, |store| {
+        let history = store
+            .find_mut(document_id.as_deref(), document_path.as_deref().unwrap_or_default())
+            .ok_or_else(|| "No rename history for this document".to_string())?;
+        if history.applied == 0 {
+            return Err("Nothing to undo".to_string());
+        }
+        let entry = history.entries[history.applied - 1].clone();
+        if history.current_path != entry.new_path {
+            return Err(format!(
+                "Rename history is out of sync: expected {}, found {}",
+                entry.new_path, history.current_path
+            ));
+        }
+        history.move_to(&entry.old_path)?;
+        history.applied -= 1;
+        history.log_move(&entry.new_path, &entry.old_path, RenameTrigger::Undo);
+        Ok((entry.new_path, history.current_path.clone()))
+    })
+    .map(|(old_path, new_path)| follow_rename(&handle, &old_path, new_path))
+}
+
+#[tauri::command]
+pub fn redo_rename(
+    handle: AppHandle,
+    document_id: Option<String>,
+    document_path: Option<String>,
+) -> Result<String, String> {
+    let revert = |(old_path, new_path): &(String, String)| revert_rename(old_path, new_path);
+    with_store_or_revert(&handle, revert, |store| {
+        let history = store
+            .find_mut(document_id.as_deref(), document_path.as_deref().unwrap_or_default())
+            .ok_or_else(|| "No rename history for this document".to_string())?;
+        let entry = history
+            .entries
+            .get(history.applied)
+            .cloned()
+            .ok_or_else(|| "Nothing to redo".to_string())?;
+        if history.current_path != entry.old_path {
+            return Err(format!(
+                "Rename history is out of sync: expected {}, found {}",
+                entry.old_path, history.current_path
+            ));
+        }
+        history.move_to(&entry.new_path)?;
+        history.applied += 1;
+        history.log_move(&entry.old_path, &entry.new_path, RenameTrigger::Redo);
+        Ok((entry.old_path, history.current_path.clone()))
+    })
+    .map(|(old_path, new_path)| follow_rename(&handle, &old_path, new_path))
+}
+
+/// Points the search index and the catalogue at a renamed document. Returns
+/// `new_path` for convenience.
+pub fn follow_rename(handle: &AppHandle, old_path: &str, new_path: String) -> String {
+    if let Err(e) = move_indexed_document(handle, old_path, &new_path) {
+        warn!("Failed to update search index: {}", e);
+    }
+    if let Some(data_directory) = data_directory_of(Path::new(&new_path)) {
+        if let Err(e) = move_output(&data_directory, old_path, &new_path) {
+            warn!("Failed to update the catalogue: {}", e);
+        }
+    }
+    new_path
+}
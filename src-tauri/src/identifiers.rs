@@ -0,0 +1,201 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::models::identifiers::{ExtractedIdentifier, IdentifierKind};
+
+/// Federative units, as used in OAB registrations.
+const STATES: [&str; 27] = [
+    "AC", "AL", "AM", "AP", "BA", "CE", "DF", "ES", "GO", "MA", "MG", "MS", "MT", "PA", "PB",
+    "PE", "PI", "PR", "RJ", "RN", "RO", "RR", "RS", "SC", "SE", "SP", "TO",
+];
+
+lazy_static! {
+    // Unpunctuated numbers are only reported when their check digits match,
+    // as any long number would otherwise qualify.
+    static ref CPF: Regex = Regex::new(r"\b(\d{3}\.\d{3}\.\d{3}-\d{2})\b|\b(\d{11})\b").unwrap();
+    // Letters are allowed in the root and branch since the alphanumeric
+    // CNPJ introduced in July 2026.
+    static ref CNPJ: Regex = Regex::new(
+        r"\b([0-9A-Z]{2}\.[0-9A-Z]{3}\.[0-9A-Z]{3}/[0-9A-Z]{4}-\d{2})\b|\b(\d{14})\b"
+    )
+    .unwrap();
+    static ref CNJ: Regex =
+        Regex::new(r"\b(\d{7}-\d{2}\.\d{4}\.\d\.\d{2}\.\d{4})\b|\b(\d{20})\b").unwrap();
+    static ref OAB: Regex = Regex::new(
+        r"(?i)\bOAB\s*(?:[/-]\s*([a-z]{2})\s*)?(?:n\.?\s*[º°o]?\.?\s*)?(\d{1,3}(?:\.\d{3})+|\d{1,6})\b(?:\s*[/-]\s*([a-z]{2})\b)?"
+    )
+    .unwrap();
+    static ref CEP: Regex = Regex::new(r"\b\d{2}\.?\d{3}-\d{3}\b").unwrap();
+    static ref AMOUNT: Regex =
+        Regex::new(r"R\$\s*(\d{1,3}(?:\.\d{3})+|\d+)(?:,(\d{2}))?\b").unwrap();
+}
+
+/// Values of the characters of an identifier for check digit sums; letters
+/// count as their ASCII code minus 48, as the alphanumeric CNPJ specifies.
+fn values(text: &str) -> Vec<u32> {
+    text.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase() as u32 - '0' as u32)
+        .collect()
+}
+
+/// Modulo 11 check digit over `values` with `weights`.
+fn mod11_digit(values: &[u32], weights: &[u32]) -> u32 {
+    let sum: u32 = values.iter().zip(weights).map(|(value, weight)| value * weight).sum();
+    match sum % 11 {
+        0 | 1 => 0,
+        remainder => 11 - remainder,
+    }
+}
+
+fn is_valid_cpf(values: &[u32]) -> bool {
+    if values.len() != 11 || values.iter().all(|&value| value == values[0]) {
+        return false;
+    }
+    let first = mod11_digit(&values[..9], &[10, 9, 8, 7, 6, 5, 4, 3, 2]);
+    let second = mod11_digit(&values[..10], &[11, 10, 9, 8, 7, 6, 5, 4, 3, 2]);
+    values[9] == first && values[10] == second
+}
+
+fn is_valid_cnpj(values: &[u32]) -> bool {
+    if values.len() != 14 || values.iter().all(|&value| value == values[0]) {
+        return false;
+    }
+    let first = mod11_digit(&values[..12], &[5, 4, 3, 2, 9, 8, 7, 6, 5, 4, 3, 2]);
+    let second = mod11_digit(&values[..13], &[6, 5, 4, 3, 2, 9, 8, 7, 6, 5, 4, 3, 2]);
+    values[12] == first && values[13] == second
+}
+
+/// ISO 7064 MOD 97-10, as set by CNJ Resolution 65/2008: the number with its
+/// check digits moved to the end leaves a remainder of 1.
+fn is_valid_cnj(values: &[u32]) -> bool {
+    if values.len() != 20 {
+        return false;
+    }
+    let reordered = values[..7].iter().chain(&values[9..]).chain(&values[7..9]);
+    reordered.fold(0, |remainder, value| (remainder * 10 + value) % 97) == 1
+}
+
+/// Rewrites the characters of `text` into `pattern`, where `#` stands for one
+/// character, e.g. `###.###.###-##`.
+fn format_as(text: &str, pattern: &str) -> String {
+    let mut characters = text
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase());
+    pattern
+        .chars()
+        .map(|c| match c {
+            '#' => characters.next().unwrap_or('0'),
+            c => c,
+        })
+        .collect()
+}
+
+/// Matches of a numeric identifier, punctuated (group 1) or not (group 2).
+fn find_numbers(
+    regex: &Regex,
+    text: &str,
+    pattern: &str,
+    is_valid: fn(&[u32]) -> bool,
+) -> Vec<(String, bool)> {
+    regex
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let (number, is_punctuated) = match (captures.get(1), captures.get(2)) {
+                (Some(number), _) => (number.as_str(), true),
+                (None, Some(number)) => (number.as_str(), false),
+                (None, None) => return None,
+            };
+            let valid = is_valid(&values(number));
+            (valid || is_punctuated).then(|| (format_as(number, pattern), valid))
+        })
+        .collect()
+}
+
+fn find_oab(text: &str) -> Vec<(String, bool)> {
+    OAB.captures_iter(text)
+        .map(|captures| {
+            let number: String = captures[2].chars().filter(char::is_ascii_digit).collect();
+            let state = captures
+                .get(1)
+                .or_else(|| captures.get(3))
+                .map(|state| state.as_str().to_uppercase());
+            match state {
+                Some(state) => {
+                    let valid = STATES.contains(&state.as_str());
+                    (format!("{}/{}", number, state), valid)
+                }
+                None => (number, false),
+            }
+        })
+        .collect()
+}
+
+fn find_ceps(text: &str) -> Vec<(String, bool)> {
+    CEP.find_iter(text)
+        .map(|cep| {
+            let cep = format_as(cep.as_str(), "#####-###");
+            let valid = cep != "00000-000";
+            (cep, valid)
+        })
+        .collect()
+}
+
+fn find_amounts(text: &str) -> Vec<(String, bool)> {
+    AMOUNT
+        .captures_iter(text)
+        .map(|captures| {
+            let reais = captures[1].replace('.', "");
+            let reais = reais.trim_start_matches('0');
+            let centavos = captures.get(2).map_or("00", |centavos| centavos.as_str());
+            (format!("{}.{}", if reais.is_empty() { "0" } else { reais }, centavos), true)
+        })
+        .collect()
+}
+
+/// Finds CPF, CNPJ, CNJ process numbers, OAB registrations, CEPs and amounts
+/// in the text of each page. Repeats of the same value are merged, keeping
+/// the order in which values first appear within each kind.
+pub fn extract_identifiers(page_texts: &[String]) -> Vec<ExtractedIdentifier> {
+    let mut identifiers: Vec<ExtractedIdentifier> = Vec::new();
+    for (index, text) in page_texts.iter().enumerate() {
+        let page = index as u32 + 1;
+        let found = [
+            (IdentifierKind::Cpf, find_numbers(&CPF, text, "###.###.###-##", is_valid_cpf)),
+            (
+                IdentifierKind::Cnpj,
+                find_numbers(&CNPJ, text, "##.###.###/####-##", is_valid_cnpj),
+            ),
+            (
+                IdentifierKind::Cnj,
+                find_numbers(&CNJ, text, "#######-##.####.#.##.####", is_valid_cnj),
+            ),
+            (IdentifierKind::Oab, find_oab(text)),
+            (IdentifierKind::Cep, find_ceps(text)),
+            (IdentifierKind::Amount, find_amounts(text)),
+        ];
+        for (kind, values) in found {
+            for (value, valid) in values {
+                match identifiers
+                    .iter_mut()
+                    .find(|identifier| identifier.kind == kind && identifier.value == value)
+                {
+                    Some(identifier) => {
+                        if !identifier.pages.contains(&page) {
+                            identifier.pages.push(page);
+                        }
+                    }
+                    None => identifiers.push(ExtractedIdentifier {
+                        kind,
+                        value,
+                        valid,
+                        pages: vec![page],
+                    }),
+                }
+            }
+        }
+    }
+    identifiers.sort_by_key(|identifier| identifier.kind as u8);
+    identifiers
+}
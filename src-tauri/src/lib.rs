@@ -1,10 +1,45 @@
 mod models;
+mod catalogue;
+mod dates;
+mod fingerprint;
+mod naming;
+mod ocr;
+mod optimise;
+mod pages;
+mod pdfa;
+mod history;
+mod identifiers;
+mod metadata;
+mod recycle;
+mod redaction;
+mod reveal;
+mod scope;
+mod search;
+mod sanitizer;
+mod signatures;
+mod signing;
+mod stamping;
+mod watcher;
+mod settings;
+mod sidecar;
 mod extractor;
 mod processor;
 mod utilities;
+mod workspace;
 pub use utilities::{call_utility, call_utility2};
 use extractor::run_extract_document_images_stage;
-use processor::{run_page_preprocess_stage, run_document_process_stage, run_update_file_name, open_in_explorer, delete_processed_document};
+use naming::preview_file_name;
+use history::{get_file_name_history, get_rename_history, redo_rename, undo_rename};
+use metadata::read_document_metadata;
+use recycle::{list_deleted_documents, restore_deleted_document};
+use redaction::run_redaction_stage;
+use scope::{add_allowed_root, remove_allowed_root, restore_scope};
+use search::{get_search_facets, search_documents};
+use catalogue::{get_catalogue_entry, list_catalogue, reconcile_catalogue};
+use settings::{get_settings, update_settings};
+use signatures::verify_document_signatures;
+use watcher::{get_watch_status, start_watcher};
+use processor::{run_page_preprocess_stage, run_document_process_stage, run_update_file_name, normalise_date, open_in_explorer, delete_processed_document};
 // use llm::{anthropic_pipeline, update_file_name, rename_finished_document};
 // use processor::{final_pipeline, open_in_explorer};
 
@@ -16,6 +51,11 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_log::Builder::new().build())
+        .setup(|app| {
+            restore_scope(app.handle());
+            start_watcher(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             // anthropic_pipeline,
             // update_file_name,
@@ -26,8 +66,29 @@ pub fn run() {
             run_page_preprocess_stage,
             run_document_process_stage,
             run_update_file_name,
+            normalise_date,
             open_in_explorer,
-            delete_processed_document
+            delete_processed_document,
+            preview_file_name,
+            get_settings,
+            update_settings,
+            get_rename_history,
+            get_file_name_history,
+            undo_rename,
+            redo_rename,
+            list_deleted_documents,
+            restore_deleted_document,
+            read_document_metadata,
+            search_documents,
+            get_search_facets,
+            list_catalogue,
+            get_catalogue_entry,
+            reconcile_catalogue,
+            run_redaction_stage,
+            verify_document_signatures,
+            get_watch_status,
+            add_allowed_root,
+            remove_allowed_root
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,452 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone};
+use lazy_static::lazy_static;
+use lopdf::{Dictionary, Document, Object, Stream, StringFormat};
+use regex::Regex;
+use std::{collections::HashMap, path::Path};
+use tauri::AppHandle;
+
+use super::models::metadata::DocumentMetadata;
+use super::models::workflows::PagePreprocessStageResult;
+use super::pdfa::{pdfa_identification, xmp_metadata};
+use super::scope::PathScope;
+
+const NAMESPACE_URI: &str = "http://conectbras.com/ns/document/1.0/";
+const NAMESPACE_PREFIX: &str = "cbdoc";
+const CREATOR_TOOL: &str = "conectbras-agent";
+
+lazy_static! {
+    static ref PDF_DATE: Regex = Regex::new(
+        r"^D:(\d{4})(\d{2})?(\d{2})?(\d{2})?(\d{2})?(\d{2})?(?:([+\-Z])(\d{2})?'?(\d{2})?'?)?"
+    )
+    .unwrap();
+    static ref SCHEMA_PROPERTY: Regex = Regex::new(&format!(
+        r"(?s)<{0}:(\w+)>(.*?)</{0}:\w+>",
+        NAMESPACE_PREFIX
+    ))
+    .unwrap();
+}
+
+/// Properties of our XMP schema, with the Info dictionary key they are
+/// mirrored to and the description declared for them in the PDF/A extension
+/// schema.
+const SCHEMA_PROPERTIES: [(&str, &str, &str); 5] = [
+    (
+        "documentId",
+        "DocumentId",
+        "Identifier of the processing job",
+    ),
+    ("typeName", "TypeName", "Document type"),
+    ("typeAbbr", "TypeAbbr", "Abbreviated document type"),
+    (
+        "primaryDate",
+        "PrimaryDate",
+        "Most relevant date of the document",
+    ),
+    ("result", "ExtractionResult", "Extraction result as JSON"),
+];
+
+/// Writes the extraction result into the Info dictionary (Title, Subject,
+/// Keywords and our custom properties) and a new XMP packet that mirrors every
+/// Info entry, keeps the PDF/A identification written by ocrmypdf and declares
+/// our own properties through a PDF/A extension schema, so the file stays
+/// conformant. Info entries the packet has no place for are dropped; Author
+/// and Trapped are kept and mirrored.
+pub fn write_metadata(
+    path: &Path,
+    document_id: &str,
+    result: &PagePreprocessStageResult,
+) -> Result<(), String> {
+    let mut document = Document::load(path)
+        .map_err(|e| format!("Failed to load PDF {}: {}", path.display(), e))?;
+
+    let keywords = keywords(result);
+    let now = Local::now().fixed_offset();
+    let info_id = match document.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(id) => id,
+        Err(_) => {
+            let id = document.add_object(Dictionary::new());
+            document.trailer.set("Info", Object::Reference(id));
+            id
+        }
+    };
+    let info = document
+        .get_dictionary_mut(info_id)
+        .map_err(|e| format!("Failed to read document information: {}", e))?;
+
+    let created = info
+        .get(b"CreationDate")
+        .ok()
+        .and_then(read_text)
+        .and_then(|date| parse_pdf_date(&date))
+        .unwrap_or(now);
+    let producer = info.get(b"Producer").ok().and_then(read_text);
+    let author = info.get(b"Author").ok().and_then(read_text);
+    let trapped = info
+        .get(b"Trapped")
+        .and_then(Object::as_name)
+        .ok()
+        .map(|name| String::from_utf8_lossy(name).into_owned());
+
+    *info = Dictionary::new();
+    info.set("Title", text_string(&result.suggested_file_name));
+    info.set("Subject", text_string(&result.summary));
+    info.set("Keywords", text_string(&keywords.join("; ")));
+    info.set("Creator", text_string(CREATOR_TOOL));
+    info.set("CreationDate", text_string(&pdf_date(&created)));
+    info.set("ModDate", text_string(&pdf_date(&now)));
+    if let Some(producer) = &producer {
+        info.set("Producer", text_string(producer));
+    }
+    if let Some(author) = &author {
+        info.set("Author", text_string(author));
+    }
+    if let Some(trapped) = &trapped {
+        info.set("Trapped", Object::Name(trapped.as_bytes().to_vec()));
+    }
+
+    let pdfa = xmp_metadata(&document)
+        .and_then(|xmp| pdfa_identification(&xmp))
+        .map(|(part, conformance)| {
+            (part, conformance.unwrap_or("B".to_string()).to_uppercase())
+        });
+    let result_json = serde_json::to_string(result)
+        .map_err(|e| format!("Failed to serialise metadata: {}", e))?;
+    let primary_date = result
+        .primary_date()
+        .map(|date| date.to_string())
+        .unwrap_or_default();
+    let properties = [
+        document_id,
+        &result.type_name,
+        &result.type_abbr,
+        &primary_date,
+        &result_json,
+    ];
+    let info = document
+        .get_dictionary_mut(info_id)
+        .map_err(|e| format!("Failed to read document information: {}", e))?;
+    for ((_, key, _), value) in SCHEMA_PROPERTIES.iter().zip(properties) {
+        info.set(*key, text_string(value));
+    }
+
+    let packet = xmp(XmpFields {
+        title: &result.suggested_file_name,
+        description: &result.summary,
+        keywords: &keywords,
+        producer: producer.as_deref(),
+        author: author.as_deref(),
+        trapped: trapped.as_deref(),
+        created: &created,
+        modified: &now,
+        pdfa,
+        properties,
+    });
+
+    // PDF/A does not allow filters on the metadata stream.
+    let mut dictionary = Dictionary::new();
+    dictionary.set("Type", Object::Name(b"Metadata".to_vec()));
+    dictionary.set("Subtype", Object::Name(b"XML".to_vec()));
+    let stream = Stream::new(dictionary, packet.into_bytes()).with_compression(false);
+    let metadata_id = document.add_object(stream);
+    document
+        .catalog_mut()
+        .map_err(|e| format!("Failed to read document catalog: {}", e))?
+        .set("Metadata", Object::Reference(metadata_id));
+    document.prune_objects();
+
+    document
+        .save(path)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// Reads what `write_metadata` stored. Files from other tools still yield
+/// their Info entries.
+pub fn read_metadata(path: &Path) -> Result<DocumentMetadata, String> {
+    let document = Document::load(path)
+        .map_err(|e| format!("Failed to load PDF {}: {}", path.display(), e))?;
+    let info = document
+        .trailer
+        .get(b"Info")
+        .and_then(|info| document.dereference(info))
+        .and_then(|(_, info)| info.as_dict())
+        .ok();
+    let info_text = |key: &[u8]| info.and_then(|info| info.get(key).ok()).and_then(read_text);
+
+    let xmp = xmp_metadata(&document).unwrap_or_default();
+    let xmp_properties = SCHEMA_PROPERTY
+        .captures_iter(&xmp)
+        .map(|captures| (captures[1].to_string(), unescape_xml(&captures[2])))
+        .collect::<HashMap<String, String>>();
+    // The XMP packet first; the Info mirror when another tool rewrote it.
+    let property = |name: &str| {
+        let key = SCHEMA_PROPERTIES
+            .iter()
+            .find(|(property, _, _)| *property == name)
+            .map(|(_, key, _)| key.as_bytes())?;
+        xmp_properties
+            .get(name)
+            .cloned()
+            .or_else(|| info_text(key))
+            .filter(|value| !value.is_empty())
+    };
+
+    Ok(DocumentMetadata {
+        document_id: property("documentId"),
+        title: info_text(b"Title"),
+        subject: info_text(b"Subject"),
+        keywords: info_text(b"Keywords")
+            .map(|keywords| {
+                keywords
+                    .split(';')
+                    .map(|keyword| keyword.trim().to_string())
+                    .filter(|keyword| !keyword.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        type_name: property("typeName"),
+        type_abbr: property("typeAbbr"),
+        primary_date: property("primaryDate"),
+        page_preprocess_stage_result: property("result")
+            .and_then(|json| serde_json::from_str(&json).ok()),
+    })
+}
+
+#[tauri::command]
+pub fn read_document_metadata(
+    handle: AppHandle,
+    document_path: String,
+) -> Result<DocumentMetadata, String> {
+    let document_path = PathScope::current(&handle)?.check(&document_path)?;
+    read_metadata(&document_path)
+}
+
+fn keywords(result: &PagePreprocessStageResult) -> Vec<String> {
+    let mut keywords = vec![result.type_name.clone(), result.type_abbr.clone()];
+    keywords.extend(result.dates.iter().map(|date| date.date.clone()));
+    keywords.extend(result.custom_fields.values().cloned());
+    keywords.retain(|keyword| !keyword.trim().is_empty());
+    keywords.dedup();
+    keywords
+}
+
+struct XmpFields<'a> {
+    title: &'a str,
+    description: &'a str,
+    keywords: &'a [String],
+    producer: Option<&'a str>,
+    author: Option<&'a str>,
+    trapped: Option<&'a str>,
+    created: &'a DateTime<FixedOffset>,
+    modified: &'a DateTime<FixedOffset>,
+    pdfa: Option<(String, String)>,
+    properties: [&'a str; 5],
+}
+
+fn xmp(fields: XmpFields) -> String {
+    let keywords = fields.keywords.join("; ");
+    let subjects = fields
+        .keywords
+        .iter()
+        .map(|keyword| format!("<rdf:li>{}</rdf:li>", escape_xml(keyword)))
+        .collect::<String>();
+    let pdfa = fields
+        .pdfa
+        .map(|(part, conformance)| {
+            format!(
+                r#"
+  <rdf:Description rdf:about="" xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/">
+   <pdfaid:part>{}</pdfaid:part>
+   <pdfaid:conformance>{}</pdfaid:conformance>
+  </rdf:Description>"#,
+                part, conformance
+            )
+        })
+        .unwrap_or_default();
+    let producer = fields
+        .producer
+        .map(|producer| format!("\n   <pdf:Producer>{}</pdf:Producer>", escape_xml(producer)))
+        .unwrap_or_default();
+    let trapped = fields
+        .trapped
+        .map(|trapped| format!("\n   <pdf:Trapped>{}</pdf:Trapped>", escape_xml(trapped)))
+        .unwrap_or_default();
+    let creator = fields
+        .author
+        .map(|author| {
+            format!(
+                "\n   <dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>",
+                escape_xml(author)
+            )
+        })
+        .unwrap_or_default();
+    let schema_properties = SCHEMA_PROPERTIES
+        .iter()
+        .map(|(name, _, description)| {
+            format!(
+                r#"
+         <rdf:li rdf:parseType="Resource">
+          <pdfaProperty:name>{}</pdfaProperty:name>
+          <pdfaProperty:valueType>Text</pdfaProperty:valueType>
+          <pdfaProperty:category>external</pdfaProperty:category>
+          <pdfaProperty:description>{}</pdfaProperty:description>
+         </rdf:li>"#,
+                name, description
+            )
+        })
+        .collect::<String>();
+    let properties = SCHEMA_PROPERTIES
+        .iter()
+        .zip(fields.properties)
+        .map(|((name, _, _), value)| {
+            format!(
+                "\n   <{0}:{1}>{2}</{0}:{1}>",
+                NAMESPACE_PREFIX,
+                name,
+                escape_xml(value)
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        r#"<?xpacket begin="{bom}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+   <dc:format>application/pdf</dc:format>
+   <dc:title><rdf:Alt><rdf:li xml:lang="x-default">{title}</rdf:li></rdf:Alt></dc:title>
+   <dc:description><rdf:Alt><rdf:li xml:lang="x-default">{description}</rdf:li></rdf:Alt></dc:description>
+   <dc:subject><rdf:Bag>{subjects}</rdf:Bag></dc:subject>{creator}
+  </rdf:Description>
+  <rdf:Description rdf:about="" xmlns:pdf="http://ns.adobe.com/pdf/1.3/">
+   <pdf:Keywords>{keywords}</pdf:Keywords>{producer}{trapped}
+  </rdf:Description>
+  <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+   <xmp:CreatorTool>{creator_tool}</xmp:CreatorTool>
+   <xmp:CreateDate>{created}</xmp:CreateDate>
+   <xmp:ModifyDate>{modified}</xmp:ModifyDate>
+   <xmp:MetadataDate>{modified}</xmp:MetadataDate>
+  </rdf:Description>{pdfa}
+  <rdf:Description rdf:about="" xmlns:pdfaExtension="http://www.aiim.org/pdfa/ns/extension/" xmlns:pdfaSchema="http://www.aiim.org/pdfa/ns/schema#" xmlns:pdfaProperty="http://www.aiim.org/pdfa/ns/property#">
+   <pdfaExtension:schemas>
+    <rdf:Bag>
+     <rdf:li rdf:parseType="Resource">
+      <pdfaSchema:schema>Conectbras document metadata</pdfaSchema:schema>
+      <pdfaSchema:namespaceURI>{namespace}</pdfaSchema:namespaceURI>
+      <pdfaSchema:prefix>{prefix}</pdfaSchema:prefix>
+      <pdfaSchema:property>
+       <rdf:Seq>{schema_properties}
+       </rdf:Seq>
+      </pdfaSchema:property>
+     </rdf:li>
+    </rdf:Bag>
+   </pdfaExtension:schemas>
+  </rdf:Description>
+  <rdf:Description rdf:about="" xmlns:{prefix}="{namespace}">{properties}
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        bom = '\u{feff}',
+        title = escape_xml(fields.title),
+        description = escape_xml(fields.description),
+        subjects = subjects,
+        keywords = escape_xml(&keywords),
+        producer = producer,
+        trapped = trapped,
+        creator = creator,
+        creator_tool = CREATOR_TOOL,
+        created = fields.created.format("%Y-%m-%dT%H:%M:%S%:z"),
+        modified = fields.modified.format("%Y-%m-%dT%H:%M:%S%:z"),
+        pdfa = pdfa,
+        namespace = NAMESPACE_URI,
+        prefix = NAMESPACE_PREFIX,
+        schema_properties = schema_properties,
+        properties = properties,
+    )
+}
+
+/// A PDF text string: plain bytes for ASCII, UTF-16BE with a byte order mark
+/// otherwise, so accented Portuguese text survives.
+pub fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        return Object::String(text.as_bytes().to_vec(), StringFormat::Literal);
+    }
+    let mut bytes = vec![0xfe, 0xff];
+    bytes.extend(text.encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+pub fn read_text(object: &Object) -> Option<String> {
+    let bytes = object.as_str().ok()?;
+    match bytes {
+        [0xfe, 0xff, rest @ ..] => {
+            let units = rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect::<Vec<u16>>();
+            Some(String::from_utf16_lossy(&units))
+        }
+        [0xef, 0xbb, 0xbf, rest @ ..] => Some(String::from_utf8_lossy(rest).into_owned()),
+        // PDFDocEncoding matches Latin-1 for the characters we care about.
+        _ => Some(bytes.iter().map(|&byte| byte as char).collect()),
+    }
+}
+
+pub fn pdf_date(date: &DateTime<FixedOffset>) -> String {
+    let offset = date.offset().local_minus_utc();
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    format!(
+        "D:{}{}{:02}'{:02}'",
+        date.format("%Y%m%d%H%M%S"),
+        sign,
+        offset / 3600,
+        offset % 3600 / 60
+    )
+}
+
+pub fn parse_pdf_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    let captures = PDF_DATE.captures(date.trim())?;
+    let number = |index: usize, default: u32| {
+        captures
+            .get(index)
+            .and_then(|value| value.as_str().parse().ok())
+            .unwrap_or(default)
+    };
+    let local = NaiveDateTime::parse_from_str(
+        &format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            number(1, 0),
+            number(2, 1),
+            number(3, 1),
+            number(4, 0),
+            number(5, 0),
+            number(6, 0)
+        ),
+        "%Y-%m-%d %H:%M:%S",
+    )
+    .ok()?;
+    let offset_seconds = (number(8, 0) * 3600 + number(9, 0) * 60) as i32;
+    let offset = match captures.get(7).map(|sign| sign.as_str()) {
+        Some("-") => FixedOffset::west_opt(offset_seconds)?,
+        Some("+") => FixedOffset::east_opt(offset_seconds)?,
+        _ => FixedOffset::east_opt(0)?,
+    };
+    offset.from_local_datetime(&local).single()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
@@ -1,2 +1,13 @@
 pub mod workflows;
+pub mod settings;
+pub mod history;
+pub mod trash;
+pub mod metadata;
+pub mod sidecar;
+pub mod search;
+pub mod catalogue;
+pub mod identifiers;
+pub mod redaction;
+pub mod signatures;
+pub mod watch;
 mod implementations;
\ No newline at end of file
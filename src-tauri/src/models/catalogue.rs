@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+use super::workflows::{PagePreprocessStageResult, PageReference};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DocumentStatus {
+    Preprocessed,
+    Processed,
+    Failed,
+    Deleted,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FileStatus {
+    Present,
+    Missing,
+    /// Moved to the trash from the app.
+    Deleted,
+}
+
+/// A page group and everything recorded about it, as stored in the catalogue.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueEntry {
+    pub document_id: String,
+    pub status: DocumentStatus,
+    pub selected_pages: Vec<u32>,
+    pub source_pages: Vec<PageReference>,
+    pub page_preprocess_stage_result: Option<PagePreprocessStageResult>,
+    pub error_message: Option<String>,
+    /// RFC 3339 timestamps.
+    pub created_at: String,
+    pub updated_at: String,
+    pub sources: Vec<CatalogueSource>,
+    pub outputs: Vec<CatalogueOutput>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueSource {
+    pub path: String,
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+    pub status: FileStatus,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueOutput {
+    pub part_number: u32,
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+    pub status: FileStatus,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueFilter {
+    #[serde(default)]
+    pub status: Option<DocumentStatus>,
+    #[serde(default)]
+    pub type_name: Option<String>,
+    /// Path of a source document the pages were taken from.
+    #[serde(default)]
+    pub source_document: Option<String>,
+    /// Matched against the summary, suggested file name and output paths.
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub offset: Option<u32>,
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CataloguePage {
+    /// Entries matching the filter, over all pages.
+    pub total: u64,
+    pub offset: u32,
+    pub entries: Vec<CatalogueEntry>,
+}
+
+/// What reconciling the catalogue with the file system found and changed.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileReport {
+    pub checked_outputs: u32,
+    /// Outputs found under another name in the documents directory.
+    pub moved: Vec<MovedOutput>,
+    pub missing: Vec<String>,
+    /// Outputs marked missing earlier that are back at their path.
+    pub reappeared: Vec<String>,
+    /// Outputs whose content no longer matches the recorded hash.
+    pub changed: Vec<String>,
+    /// PDFs in the documents directory the catalogue knows nothing about.
+    pub untracked: Vec<String>,
+    pub missing_sources: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MovedOutput {
+    pub document_id: String,
+    pub old_path: String,
+    pub new_path: String,
+}
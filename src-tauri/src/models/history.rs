@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameHistoryStore {
+    pub documents: Vec<DocumentRenameHistory>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentRenameHistory {
+    pub document_id: String,
+    pub current_path: String,
+    pub fingerprint: FileFingerprint,
+    /// The renames that can be undone and redone.
+    pub entries: Vec<RenameEntry>,
+    /// Number of entries currently applied; entries after it can be redone.
+    pub applied: usize,
+    /// Every move of the document, oldest first, whatever triggered it.
+    #[serde(default)]
+    pub moves: Vec<RenameEntry>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameEntry {
+    pub old_path: String,
+    /// `trash:<id>` of the trash entry for a document sent to the system
+    /// trash, where its path is not known.
+    pub new_path: String,
+    pub timestamp: u64,
+    pub trigger: RenameTrigger,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RenameTrigger {
+    User,
+    Undo,
+    Redo,
+    Trash,
+    Restore,
+    /// The watch folder moved the file to its archive, or back after a
+    /// failed extraction.
+    WatchArchive,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FileFingerprint {
+    pub size: u64,
+    pub modified: u64,
+}
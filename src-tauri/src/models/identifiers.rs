@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum IdentifierKind {
+    Cpf,
+    Cnpj,
+    /// Unified process number, `NNNNNNN-DD.AAAA.J.TR.OOOO`.
+    Cnj,
+    Oab,
+    Cep,
+    /// An amount in reais.
+    Amount,
+}
+
+/// An identifier found in the text of a document.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractedIdentifier {
+    pub kind: IdentifierKind,
+    /// Canonical form, e.g. `123.456.789-09` or `123456/SP`; amounts are
+    /// written `1234.56`.
+    pub value: String,
+    /// Whether the check digits match. Kinds without check digits are valid
+    /// when well formed, e.g. an OAB registration from an existing state.
+    pub valid: bool,
+    /// Pages of the document it occurs on, counted from 1.
+    pub pages: Vec<u32>,
+}
@@ -1,8 +1,13 @@
-use log::{debug, error};
-use std::{fs::create_dir_all, path::PathBuf, time::Instant};
+use log::{debug, error, warn};
+use std::fmt;
+use std::{collections::HashSet, fs::create_dir_all, path::PathBuf, time::Instant};
 use tauri::{AppHandle, Emitter};
 
+use super::catalogue::{DocumentStatus, FileStatus};
+use super::identifiers::IdentifierKind;
+use super::signatures::{SignatureReport, SigningOptions};
 use super::workflows::*;
+use crate::dates::PartialDate;
 
 impl PagePreprocessStage {
     pub fn get_pages_paths(&self) -> Vec<PathBuf> {
@@ -13,6 +18,15 @@ impl PagePreprocessStage {
             })
             .collect()
     }
+    pub fn to_error(&self, error_message: String) -> PagePreprocessStageError {
+        PagePreprocessStageError {
+            id: self.id.clone(),
+            selected_pages: self.selected_pages.clone(),
+            data_directory: self.data_directory.clone(),
+            images_directory: self.images_directory.clone(),
+            error_message,
+        }
+    }
     pub fn get_preprocessed_pages_directory(&self) -> PathBuf {
         let page_numbers = self
             .selected_pages
@@ -28,6 +42,78 @@ impl PagePreprocessStage {
     }
 }
 
+impl DocumentProcessStage {
+    pub fn to_error(&self, error_message: String) -> DocumentProcessStageError {
+        DocumentProcessStageError {
+            id: self.id.clone(),
+            selected_pages: self.selected_pages.clone(),
+            data_directory: self.data_directory.clone(),
+            images_directory: self.images_directory.clone(),
+            page_preprocess_stage_result: self.page_preprocess_stage_result.clone(),
+            document_path: self.document_path.clone(),
+            file_name: self.file_name.clone(),
+            error_message,
+            page_number_prefix: self.page_number_prefix.clone(),
+            source_pages: self.page_references(),
+        }
+    }
+    /// The pages that make up the output document, in order.
+    pub fn page_references(&self) -> Vec<PageReference> {
+        if !self.source_pages.is_empty() {
+            return self.source_pages.clone();
+        }
+        self.selected_pages
+            .iter()
+            .map(|&page_number| PageReference {
+                document_path: self.document_path.clone(),
+                page_number,
+                rotation: 0,
+            })
+            .collect()
+    }
+}
+
+impl PagePreprocessStageResult {
+    /// Rewrites every date in normalised ISO partial form, drops the ones that
+    /// are not valid dates and removes duplicates while keeping the relevance
+    /// order given by the model.
+    pub fn normalise_dates(&mut self) {
+        let mut seen = HashSet::new();
+        self.dates = self
+            .dates
+            .drain(..)
+            .filter_map(|date| match PartialDate::parse(&date.date) {
+                Ok(parsed) => seen.insert(parsed).then(|| Date {
+                    date: parsed.to_string(),
+                    description: date.description,
+                }),
+                Err(e) => {
+                    warn!("Discarding extracted date {:?}: {}", date.date, e);
+                    None
+                }
+            })
+            .collect();
+    }
+
+    /// The most relevant valid date, which the prompt places first.
+    pub fn primary_date(&self) -> Option<PartialDate> {
+        self.dates
+            .iter()
+            .find_map(|date| PartialDate::parse(&date.date).ok())
+    }
+
+    pub fn chronological_dates(&self) -> Vec<PartialDate> {
+        let mut dates = self
+            .dates
+            .iter()
+            .filter_map(|date| PartialDate::parse(&date.date).ok())
+            .collect::<Vec<PartialDate>>();
+        dates.sort();
+        dates.dedup();
+        dates
+    }
+}
+
 impl ProgressState {
     pub fn new(total_document_pages: usize) -> Self {
         Self {
@@ -76,3 +162,74 @@ impl ProgressState {
         Ok(())
     }
 }
+
+impl DocumentStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DocumentStatus::Preprocessed => "preprocessed",
+            DocumentStatus::Processed => "processed",
+            DocumentStatus::Failed => "failed",
+            DocumentStatus::Deleted => "deleted",
+        }
+    }
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "preprocessed" => Ok(DocumentStatus::Preprocessed),
+            "processed" => Ok(DocumentStatus::Processed),
+            "failed" => Ok(DocumentStatus::Failed),
+            "deleted" => Ok(DocumentStatus::Deleted),
+            _ => Err(format!("Unknown document status: {}", value)),
+        }
+    }
+}
+
+impl FileStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileStatus::Present => "present",
+            FileStatus::Missing => "missing",
+            FileStatus::Deleted => "deleted",
+        }
+    }
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "present" => Ok(FileStatus::Present),
+            "missing" => Ok(FileStatus::Missing),
+            "deleted" => Ok(FileStatus::Deleted),
+            _ => Err(format!("Unknown file status: {}", value)),
+        }
+    }
+}
+
+impl IdentifierKind {
+    /// Name of the naming template field holding the first valid value.
+    pub fn field_name(self) -> &'static str {
+        match self {
+            IdentifierKind::Cpf => "cpf",
+            IdentifierKind::Cnpj => "cnpj",
+            IdentifierKind::Cnj => "cnj",
+            IdentifierKind::Oab => "oab",
+            IdentifierKind::Cep => "cep",
+            IdentifierKind::Amount => "amount",
+        }
+    }
+}
+
+impl SignatureReport {
+    /// Whether the signed bytes and the signature value still check out,
+    /// whatever the trust in the signer; rewriting the file would break it.
+    pub fn is_intact(&self) -> bool {
+        self.digest_matches && self.signature_valid
+    }
+}
+
+// Written by hand so that the password never reaches the logs.
+impl fmt::Debug for SigningOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningOptions")
+            .field("password", &"<hidden>")
+            .field("stamp", &self.stamp)
+            .field("reason", &self.reason)
+            .finish()
+    }
+}
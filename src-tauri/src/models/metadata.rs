@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+use super::workflows::PagePreprocessStageResult;
+
+/// Metadata read back from a processed PDF. Fields are absent when the file
+/// was not produced by this app or was edited by another tool.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentMetadata {
+    pub document_id: Option<String>,
+    pub title: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Vec<String>,
+    pub type_name: Option<String>,
+    pub type_abbr: Option<String>,
+    pub primary_date: Option<String>,
+    pub page_preprocess_stage_result: Option<PagePreprocessStageResult>,
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+use super::identifiers::IdentifierKind;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RedactionStage {
+    /// The processed document to redact; it is left untouched and the
+    /// redacted copy is written next to it.
+    pub document_path: String,
+    /// Text to black out wherever it appears, e.g. bank account numbers or a
+    /// diagnosis. Case, accents, spaces and punctuation are ignored.
+    #[serde(default)]
+    pub terms: Vec<String>,
+    /// Kinds of identifiers to black out, as found in the document's text.
+    #[serde(default)]
+    pub identifier_kinds: Vec<IdentifierKind>,
+    /// Areas drawn by the user on the page images.
+    #[serde(default)]
+    pub regions: Vec<RedactionRegion>,
+}
+
+/// A rectangle on a page, in fractions of the page width and height measured
+/// from the top left corner, so it does not depend on the image resolution.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RedactionRegion {
+    pub page_number: u32,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RedactionReport {
+    pub document_path: String,
+    /// Pages replaced by a blacked-out image of themselves.
+    pub redacted_pages: Vec<u32>,
+    /// Number of boxes drawn over all pages.
+    pub boxes: u32,
+    /// Terms found on no page.
+    pub not_found: Vec<String>,
+    /// Pages whose text layer had a term that could not be located on the
+    /// page image, redacted with the regions the user marked on them. The
+    /// stage fails for such pages without a region.
+    pub unlocated_pages: Vec<u32>,
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use super::workflows::Date;
+
+/// What the index keeps of a processed document. The text is stored as is so
+/// snippets can be cut from it; terms are derived when the index is loaded.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedDocument {
+    pub document_id: String,
+    /// Output files in order; more than one when the document was split.
+    pub part_paths: Vec<String>,
+    pub source_documents: Vec<String>,
+    pub type_name: String,
+    pub type_abbr: String,
+    pub summary: String,
+    pub dates: Vec<Date>,
+    /// RFC 3339 timestamp.
+    pub indexed_at: String,
+    pub pages: Vec<IndexedPage>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedPage {
+    /// The output file holding the page and its number in that file.
+    pub document_path: String,
+    pub page_number: u32,
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchQuery {
+    /// Words to look for; every word must occur in the document. A word
+    /// ending in `*` matches as a prefix.
+    pub query: String,
+    #[serde(default)]
+    pub type_name: Option<String>,
+    /// Inclusive bounds on the extracted dates, as `YYYY`, `YYYY-MM` or
+    /// `YYYY-MM-DD`.
+    #[serde(default)]
+    pub date_from: Option<String>,
+    #[serde(default)]
+    pub date_to: Option<String>,
+    /// Path of a source document the pages were taken from.
+    #[serde(default)]
+    pub source_document: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub document_id: String,
+    pub document_path: String,
+    pub type_name: String,
+    pub summary: String,
+    pub dates: Vec<Date>,
+    pub score: u32,
+    pub pages: Vec<PageHit>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PageHit {
+    pub document_path: String,
+    pub page_number: u32,
+    pub snippet: String,
+}
+
+/// Values present in the index, for building the search filters.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchFacets {
+    pub type_names: Vec<String>,
+    pub source_documents: Vec<String>,
+    pub earliest_date: Option<String>,
+    pub latest_date: Option<String>,
+}
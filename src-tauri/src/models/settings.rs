@@ -0,0 +1,567 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub naming: NamingSettings,
+    pub file_names: FileNameSettings,
+    pub trash: TrashSettings,
+    pub paths: PathSettings,
+    pub pdf: PdfSettings,
+    pub ocr: OcrSettings,
+    pub sidecar: SidecarSettings,
+    pub optimisation: OptimisationSettings,
+    pub signatures: SignatureSettings,
+    pub stamping: StampSettings,
+    pub watch: WatchSettings,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NamingSettings {
+    pub active_preset: String,
+    pub presets: Vec<NamingPreset>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NamingPreset {
+    pub name: String,
+    pub template: String,
+    #[serde(default = "default_counter")]
+    pub next_counter: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FileNameSettings {
+    pub transliterate: bool,
+    /// In bytes of UTF-8, as file systems count them, not characters.
+    pub max_file_name_length: usize,
+    /// In bytes, like `max_file_name_length`.
+    pub max_path_length: usize,
+    pub collision_policy: CollisionPolicy,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CollisionPolicy {
+    Suffix,
+    Fail,
+    Overwrite,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TrashSettings {
+    pub use_system_trash: bool,
+    pub retention_days: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PathSettings {
+    pub allowed_roots: Vec<String>,
+    pub allow_network_paths: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PdfSettings {
+    pub qpdf_fallback: bool,
+    pub pdfa_policy: PdfaPolicy,
+}
+
+/// What to do when an output that should be PDF/A does not pass verification.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum PdfaPolicy {
+    Ignore,
+    #[default]
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OcrSettings {
+    pub default_profile: String,
+    pub profiles: Vec<OcrProfile>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrProfile {
+    pub name: String,
+    pub engine: OcrEngineKind,
+    /// Tesseract language codes, e.g. `por`, `eng`, `spa`.
+    pub languages: Vec<String>,
+    #[serde(default)]
+    pub mode: OcrMode,
+    #[serde(default)]
+    pub deskew: bool,
+    #[serde(default)]
+    pub rotate_pages: bool,
+    #[serde(default)]
+    pub clean: bool,
+    /// ocrmypdf `--optimize` level, 0 to 3.
+    #[serde(default = "default_optimisation_level")]
+    pub optimisation_level: u8,
+    #[serde(default)]
+    pub pdfa_level: PdfaLevel,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum OcrEngineKind {
+    OcrMyPdf,
+    /// Rebuilds the document from its pages rendered at 300 dpi.
+    Tesseract,
+    /// Keeps the document as extracted, for born-digital files.
+    None,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum OcrMode {
+    /// Rasterises every page and replaces any existing text.
+    #[default]
+    Force,
+    /// Leaves pages that already have text untouched.
+    SkipText,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum PdfaLevel {
+    None,
+    Pdfa1,
+    #[default]
+    Pdfa2,
+    Pdfa3,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SidecarSettings {
+    pub enabled: bool,
+    pub format: SidecarFormat,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum SidecarFormat {
+    #[default]
+    Json,
+    Xml,
+    Csv,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OptimisationSettings {
+    /// Profile applied when a document does not name one; none by default,
+    /// which leaves the OCR output as it is.
+    pub default_profile: Option<String>,
+    pub profiles: Vec<OptimisationProfile>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimisationProfile {
+    pub name: String,
+    /// Images above this resolution (dpi) are downsampled.
+    #[serde(default)]
+    pub image_resolution: Option<u32>,
+    /// JPEG quality, 1 to 100; lossy recompression is off when absent.
+    #[serde(default)]
+    pub jpeg_quality: Option<u8>,
+    #[serde(default)]
+    pub jbig2_lossy: bool,
+    #[serde(default)]
+    pub grayscale: bool,
+    #[serde(default)]
+    pub object_streams: bool,
+    /// Steps quality down until the output is at most this many bytes.
+    #[serde(default)]
+    pub target_max_bytes: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SignatureSettings {
+    /// Directory of trusted certificates (`.cer`, `.crt`, `.der` or `.pem`),
+    /// e.g. the ICP-Brasil root and intermediate authorities. Signatures are
+    /// reported as untrusted when absent.
+    pub trust_store_directory: Option<String>,
+    /// Keeps a copy of signed sources next to the processed document, since
+    /// processing invalidates their signatures.
+    pub keep_signed_original: bool,
+    /// PKCS#12 file (`.pfx` or `.p12`) with the key and certificate chain
+    /// used to sign outputs; its password is asked for on each use.
+    pub certificate_path: Option<String>,
+    /// RFC 3161 timestamp authority. Signatures carry no timestamp when
+    /// absent.
+    pub timestamp_url: Option<String>,
+    pub reason: Option<String>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StampSettings {
+    pub stamps: Vec<Stamp>,
+    pub bates: BatesSettings,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Stamp {
+    pub name: String,
+    pub kind: StampKind,
+    /// Template of text stamps, with the file naming fields plus `{page}`,
+    /// `{bates}`, `{today}` and `{file_name}`, e.g. `{bates}` or
+    /// `Protocolo {cnj} - {today:%d/%m/%Y}`.
+    #[serde(default)]
+    pub text: String,
+    /// Image of image stamps, e.g. a PNG logo with transparency.
+    #[serde(default)]
+    pub image_path: Option<String>,
+    /// Text height or image width, in points.
+    #[serde(default = "default_stamp_size")]
+    pub size: f64,
+    /// Colour of text stamps, `#RRGGBB`.
+    #[serde(default = "default_stamp_colour")]
+    pub colour: String,
+    #[serde(default)]
+    pub position: StampPosition,
+    /// Distance from the page edges, in points.
+    #[serde(default = "default_stamp_margin")]
+    pub margin: f64,
+    /// From 0, invisible, to 1, opaque.
+    #[serde(default = "default_stamp_opacity")]
+    pub opacity: f64,
+    /// Counter-clockwise, in degrees.
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default)]
+    pub pages: StampPages,
+    /// Pages stamped with `StampPages::Selected`, counted from 1 in the
+    /// processed document.
+    #[serde(default)]
+    pub page_numbers: Vec<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum StampKind {
+    Text,
+    Image,
+}
+
+/// Where a stamp goes on the page as displayed.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum StampPosition {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomCenter,
+    #[default]
+    BottomRight,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum StampPages {
+    #[default]
+    All,
+    First,
+    Last,
+    Odd,
+    Even,
+    Selected,
+}
+
+/// Bates numbers such as `ABC-000123`, running across every document of a
+/// case whatever source PDF it came from. A case is a prefix; its counter is
+/// kept app-wide.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BatesSettings {
+    pub prefix: String,
+    /// Digits the number is zero-padded to.
+    pub digits: usize,
+    /// First number of a case that has none yet.
+    pub start: u64,
+}
+
+/// Folders polled for new PDFs, e.g. the network share scanners drop onto.
+/// Polling rather than file system events, as shares seldom report them.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WatchSettings {
+    pub enabled: bool,
+    pub folders: Vec<WatchFolder>,
+    pub poll_interval_seconds: u64,
+    /// How long a file must keep its size and modification time before it is
+    /// taken as fully written.
+    pub stable_seconds: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchFolder {
+    pub path: String,
+    /// Globs matched against the path relative to the folder, ignoring case;
+    /// `*` also matches across subfolders.
+    #[serde(default = "default_watch_include")]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub recursive: bool,
+    #[serde(default)]
+    pub after_ingest: PostIngestAction,
+    /// Where `PostIngestAction::Archive` moves files to.
+    #[serde(default)]
+    pub archive_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum PostIngestAction {
+    #[default]
+    LeaveInPlace,
+    /// Moves the file to the archive folder before extraction, so its data
+    /// directory is created there. A file that fails extraction is moved
+    /// back, and tried again once it changes or the app restarts.
+    Archive,
+}
+
+pub const DEFAULT_NAMING_PRESET: &str = "default";
+pub const DEFAULT_NAMING_TEMPLATE: &str = "{page_number_prefix}-{suggested_file_name}";
+
+pub const DEFAULT_OCR_PROFILE: &str = "portuguese";
+
+fn default_counter() -> u64 {
+    1
+}
+
+fn default_optimisation_level() -> u8 {
+    1
+}
+
+fn default_watch_include() -> Vec<String> {
+    vec!["*.pdf".to_string()]
+}
+
+fn default_stamp_size() -> f64 {
+    12.0
+}
+
+fn default_stamp_colour() -> String {
+    "#000000".to_string()
+}
+
+fn default_stamp_margin() -> f64 {
+    24.0
+}
+
+fn default_stamp_opacity() -> f64 {
+    1.0
+}
+
+impl Default for NamingSettings {
+    fn default() -> Self {
+        Self {
+            active_preset: DEFAULT_NAMING_PRESET.to_string(),
+            presets: vec![NamingPreset {
+                name: DEFAULT_NAMING_PRESET.to_string(),
+                template: DEFAULT_NAMING_TEMPLATE.to_string(),
+                next_counter: default_counter(),
+            }],
+        }
+    }
+}
+
+impl Default for FileNameSettings {
+    fn default() -> Self {
+        Self {
+            transliterate: false,
+            max_file_name_length: 150,
+            max_path_length: 255,
+            collision_policy: CollisionPolicy::Suffix,
+        }
+    }
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        Self {
+            use_system_trash: true,
+            retention_days: 30,
+        }
+    }
+}
+
+impl Default for PdfSettings {
+    fn default() -> Self {
+        Self {
+            qpdf_fallback: true,
+            pdfa_policy: PdfaPolicy::default(),
+        }
+    }
+}
+
+impl Default for OcrSettings {
+    fn default() -> Self {
+        Self {
+            default_profile: DEFAULT_OCR_PROFILE.to_string(),
+            profiles: vec![
+                OcrProfile {
+                    name: DEFAULT_OCR_PROFILE.to_string(),
+                    engine: OcrEngineKind::OcrMyPdf,
+                    languages: vec!["por".to_string()],
+                    mode: OcrMode::Force,
+                    deskew: false,
+                    rotate_pages: false,
+                    clean: true,
+                    optimisation_level: default_optimisation_level(),
+                    pdfa_level: PdfaLevel::Pdfa2,
+                },
+                OcrProfile {
+                    name: "born-digital".to_string(),
+                    engine: OcrEngineKind::OcrMyPdf,
+                    languages: vec!["por".to_string(), "eng".to_string()],
+                    mode: OcrMode::SkipText,
+                    deskew: false,
+                    rotate_pages: false,
+                    clean: false,
+                    optimisation_level: default_optimisation_level(),
+                    pdfa_level: PdfaLevel::Pdfa2,
+                },
+            ],
+        }
+    }
+}
+
+impl Default for SidecarSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            format: SidecarFormat::default(),
+        }
+    }
+}
+
+impl Default for OptimisationSettings {
+    fn default() -> Self {
+        Self {
+            default_profile: None,
+            profiles: vec![
+                OptimisationProfile {
+                    name: "archive".to_string(),
+                    image_resolution: None,
+                    jpeg_quality: None,
+                    jbig2_lossy: false,
+                    grayscale: false,
+                    object_streams: true,
+                    target_max_bytes: None,
+                },
+                OptimisationProfile {
+                    name: "email".to_string(),
+                    image_resolution: Some(200),
+                    jpeg_quality: Some(80),
+                    jbig2_lossy: false,
+                    grayscale: false,
+                    object_streams: true,
+                    target_max_bytes: Some(10 * 1024 * 1024),
+                },
+                OptimisationProfile {
+                    name: "e-filing".to_string(),
+                    image_resolution: Some(150),
+                    jpeg_quality: Some(70),
+                    jbig2_lossy: true,
+                    grayscale: true,
+                    object_streams: true,
+                    target_max_bytes: Some(10 * 1024 * 1024),
+                },
+            ],
+        }
+    }
+}
+
+impl Default for SignatureSettings {
+    fn default() -> Self {
+        Self {
+            trust_store_directory: None,
+            keep_signed_original: true,
+            certificate_path: None,
+            timestamp_url: None,
+            reason: None,
+            location: None,
+        }
+    }
+}
+
+impl Default for StampSettings {
+    fn default() -> Self {
+        Self {
+            stamps: vec![
+                Stamp {
+                    name: "copia".to_string(),
+                    kind: StampKind::Text,
+                    text: "CÓPIA".to_string(),
+                    image_path: None,
+                    size: 96.0,
+                    colour: "#C00000".to_string(),
+                    position: StampPosition::Center,
+                    margin: default_stamp_margin(),
+                    opacity: 0.2,
+                    rotation: 45.0,
+                    pages: StampPages::All,
+                    page_numbers: Vec::new(),
+                },
+                Stamp {
+                    name: "bates".to_string(),
+                    kind: StampKind::Text,
+                    text: "{bates}".to_string(),
+                    image_path: None,
+                    size: default_stamp_size(),
+                    colour: default_stamp_colour(),
+                    position: StampPosition::BottomRight,
+                    margin: default_stamp_margin(),
+                    opacity: default_stamp_opacity(),
+                    rotation: 0.0,
+                    pages: StampPages::All,
+                    page_numbers: Vec::new(),
+                },
+            ],
+            bates: BatesSettings::default(),
+        }
+    }
+}
+
+impl Default for BatesSettings {
+    fn default() -> Self {
+        Self {
+            prefix: "DOC".to_string(),
+            digits: 6,
+            start: 1,
+        }
+    }
+}
+
+impl Default for WatchSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            folders: Vec::new(),
+            poll_interval_seconds: 5,
+            stable_seconds: 10,
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+use super::identifiers::ExtractedIdentifier;
+use super::workflows::{Date, PageReference};
+
+pub const SIDECAR_SCHEMA_VERSION: u32 = 1;
+
+/// The metadata file written next to a processed PDF for DMS import. Field
+/// names are stable; new fields are only ever added, with a new
+/// `schema_version`. `resources/document-sidecar.schema.json` describes
+/// the format for DMS mapping.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentSidecar {
+    pub schema_version: u32,
+    pub document_id: String,
+    pub file_name: String,
+    pub page_count: usize,
+    /// Pages per source document, e.g. `contrato.pdf:1-3,5`.
+    pub page_range: String,
+    pub pages: Vec<PageReference>,
+    pub source_documents: Vec<SourceDocument>,
+    /// Hash and size of `file_name`, the first part of a split document.
+    pub output_sha256: String,
+    pub output_size: u64,
+    pub ocr_profile: String,
+    /// RFC 3339 timestamps.
+    pub processing_started_at: String,
+    pub processing_finished_at: String,
+    pub type_name: String,
+    pub type_abbr: String,
+    pub summary: String,
+    pub suggested_file_name: String,
+    pub dates: Vec<Date>,
+    pub custom_fields: Vec<CustomField>,
+    /// Every file written, in order; a single entry unless the document was
+    /// split.
+    pub parts: Vec<SidecarPart>,
+    /// Identifiers found in the text.
+    pub identifiers: Vec<ExtractedIdentifier>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarPart {
+    pub file_name: String,
+    pub page_range: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceDocument {
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomField {
+    pub name: String,
+    pub value: String,
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SignatureStatus {
+    /// Intact, covering the whole file and chained to the trust store.
+    Valid,
+    /// Intact, but no certificate of its chain is in the trust store.
+    Untrusted,
+    /// Intact, but the file was changed after signing, e.g. by a later
+    /// incremental update.
+    Modified,
+    /// The signed bytes or the signature value do not match.
+    Invalid,
+    /// Uses a format or algorithm that cannot be checked here.
+    Unsupported,
+}
+
+/// Which time certificate validity was checked at.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ValidityBasis {
+    /// The signing time in the signed attributes or the signature
+    /// dictionary. The signer wrote it, so it is no evidence of when the
+    /// document was actually signed.
+    ClaimedSigningTime,
+    /// No signing time was given, so the time of the check.
+    Now,
+}
+
+/// A certificate of a signature's chain, from the signer up.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateSummary {
+    pub subject: String,
+    pub issuer: String,
+    /// Hexadecimal.
+    pub serial_number: String,
+    pub not_before: String,
+    pub not_after: String,
+    /// Whether the certificate is one of the trust store's.
+    pub trusted: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureReport {
+    /// Fully qualified name of the signature field.
+    pub field_name: String,
+    /// Common name of the signing certificate, e.g. `FULANO DE TAL:12345678909`
+    /// for ICP-Brasil, or the name written in the signature dictionary.
+    pub signer_name: Option<String>,
+    /// Signing time claimed by the signer, RFC 3339.
+    pub signing_time: Option<String>,
+    pub reason: Option<String>,
+    pub location: Option<String>,
+    /// e.g. `ETSI.CAdES.detached` or `adbe.pkcs7.detached`.
+    pub sub_filter: Option<String>,
+    pub certificate_chain: Vec<CertificateSummary>,
+    /// Whether the signed byte range spans the whole file except the
+    /// signature value itself.
+    pub covers_whole_document: bool,
+    /// Whether the signed bytes hash to the digest in the signature.
+    pub digest_matches: bool,
+    /// Whether the signature value verifies with the signer's key.
+    pub signature_valid: bool,
+    pub trusted: bool,
+    /// When the certificate chain was checked to be valid, RFC 3339.
+    #[serde(default)]
+    pub validity_checked_at: Option<String>,
+    #[serde(default)]
+    pub validity_basis: Option<ValidityBasis>,
+    pub status: SignatureStatus,
+    pub issues: Vec<String>,
+}
+
+/// How to sign the outputs of a process stage.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SigningOptions {
+    /// Password of the PKCS#12 file from settings; it is never stored or
+    /// sent back.
+    #[serde(default, skip_serializing)]
+    pub password: String,
+    /// Visible stamp; the signature is invisible when absent.
+    #[serde(default)]
+    pub stamp: Option<SignatureStamp>,
+    /// Overrides the reason from settings.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Where the visible signature goes, in fractions of the page width and
+/// height measured from the top left corner.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureStamp {
+    /// Page of the processed document, counted from 1. A split document
+    /// carries the stamp on the part holding the page.
+    pub page_number: u32,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SigningReport {
+    pub field_name: String,
+    pub signer_name: String,
+    /// RFC 3339.
+    pub signing_time: String,
+    /// Time certified by the timestamp authority, when one is configured.
+    pub timestamp_time: Option<String>,
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashIndex {
+    pub entries: Vec<TrashedDocument>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedDocument {
+    pub id: String,
+    pub original_path: String,
+    pub trashed_path: Option<String>,
+    pub location: TrashLocation,
+    pub deleted_at: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TrashLocation {
+    System,
+    App,
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestStore {
+    pub records: Vec<IngestRecord>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum IngestStatus {
+    Extracted,
+    /// Same content as a file extracted before; left alone.
+    Duplicate,
+    Failed,
+}
+
+/// A file picked up from a watch folder.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestRecord {
+    pub sha256: String,
+    /// Where the file was found.
+    pub source_path: String,
+    /// Where the file was extracted from, after any archive move; where it
+    /// was moved back to when extraction failed.
+    pub document_path: String,
+    pub data_directory: Option<String>,
+    pub status: IngestStatus,
+    pub error_message: Option<String>,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchStatus {
+    pub enabled: bool,
+    /// Files found but still being written, or waiting their turn.
+    pub pending: Vec<String>,
+    /// Most recent first.
+    pub ingested: Vec<IngestRecord>,
+}
@@ -1,4 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::identifiers::ExtractedIdentifier;
+use super::signatures::{SignatureReport, SigningOptions, SigningReport};
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -42,6 +46,8 @@ pub struct PagePreprocessStageResult {
     pub type_abbr: String,
     pub summary: String,
     pub suggested_file_name: String,
+    #[serde(default)]
+    pub custom_fields: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -59,6 +65,13 @@ pub struct PagePreprocessStageSuccess {
     pub images_directory: String,
     pub page_preprocess_stage_result: PagePreprocessStageResult,
     pub page_number_prefix: String,
+    /// Processed documents that look like this page group, most similar first.
+    #[serde(default)]
+    pub duplicates: Vec<DuplicateCandidate>,
+    /// Intact signatures of the source document, which processing the pages
+    /// will invalidate.
+    #[serde(default)]
+    pub signatures: Vec<SignatureReport>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -82,6 +95,57 @@ pub struct DocumentProcessStage {
     pub file_name: String,
     pub page_preprocess_stage_result: PagePreprocessStageResult,
     pub page_number_prefix: String,
+    /// Pages to compose, possibly from several documents. When empty,
+    /// `selected_pages` of `document_path` are used.
+    #[serde(default)]
+    pub source_pages: Vec<PageReference>,
+    /// Name of the OCR profile from settings; the default profile when absent.
+    #[serde(default)]
+    pub ocr_profile: Option<String>,
+    /// Name of the optimisation profile from settings; the default profile,
+    /// if any, when absent.
+    #[serde(default)]
+    pub optimisation_profile: Option<String>,
+    /// Overrides the size limit of the optimisation profile.
+    #[serde(default)]
+    pub target_max_bytes: Option<u64>,
+    /// Splits the output into numbered parts, e.g. for e-filing portals.
+    #[serde(default)]
+    pub split: Option<SplitOptions>,
+    /// Identifiers found in the OCR text. Filled in by the process stage;
+    /// passing them back lets file name previews use them.
+    #[serde(default)]
+    pub identifiers: Vec<ExtractedIdentifier>,
+    /// Keeps a copy of signed sources next to the output; the setting applies
+    /// when absent.
+    #[serde(default)]
+    pub keep_signed_original: Option<bool>,
+    /// Names of the stamps from settings to apply, in order.
+    #[serde(default)]
+    pub stamps: Vec<String>,
+    /// Overrides the Bates prefix from settings, e.g. per party.
+    #[serde(default)]
+    pub bates_prefix: Option<String>,
+    /// Signs the outputs with the certificate from settings as the last step.
+    #[serde(default)]
+    pub signing: Option<SigningOptions>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitOptions {
+    pub max_bytes: Option<u64>,
+    pub max_pages: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PageReference {
+    pub document_path: String,
+    pub page_number: u32,
+    /// Clockwise rotation in degrees added to the page's own rotation.
+    #[serde(default)]
+    pub rotation: i32,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -91,12 +155,97 @@ pub struct DocumentProcessStageSuccess {
     pub selected_pages: Vec<u32>,
     pub data_directory: String,
     pub images_directory: String,
-    pub document_path: String,
     pub file_name: String,
     pub page_preprocess_stage_result: PagePreprocessStageResult,
     pub page_number_prefix: String,
+    /// The composition the output was built from, so it can be edited and
+    /// the stage run again.
+    #[serde(default)]
+    pub source_pages: Vec<PageReference>,
+    /// Outcome of the PDF/A checks; absent when the OCR profile does not
+    /// produce PDF/A.
+    #[serde(default)]
+    pub pdfa_report: Option<PdfaReport>,
+    #[serde(default)]
+    pub optimisation_report: Option<OptimisationReport>,
+    /// Every file written, in order; a single entry unless the document was
+    /// split.
+    #[serde(default)]
+    pub part_paths: Vec<String>,
+    #[serde(default)]
+    pub identifiers: Vec<ExtractedIdentifier>,
+    /// Other processed documents that look like this one, now that its text
+    /// is known as well.
+    #[serde(default)]
+    pub duplicates: Vec<DuplicateCandidate>,
+    /// Intact signatures of the sources that the output no longer carries.
+    #[serde(default)]
+    pub invalidated_signatures: Vec<SignatureReport>,
+    /// Copies of the signed sources kept next to the output.
+    #[serde(default)]
+    pub signed_original_paths: Vec<String>,
+    /// The signature applied to each output, in the order of `part_paths`.
+    #[serde(default)]
+    pub signing_reports: Vec<SigningReport>,
+    /// Bates numbers given to the pages, when a stamp uses them.
+    #[serde(default)]
+    pub bates_range: Option<BatesRange>,
 }
 
+/// A processed document that is likely the same as the one being worked on.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateCandidate {
+    pub document_id: String,
+    /// First output of the document, when it is still in place.
+    pub document_path: Option<String>,
+    /// Data directory of the source it was processed from, which may be
+    /// another document's.
+    pub data_directory: String,
+    pub summary: String,
+    /// The higher of the page and text similarities, from 0 to 1.
+    pub similarity: f32,
+    /// Share of pages with near-identical images on both sides.
+    pub page_similarity: Option<f32>,
+    pub matched_pages: u32,
+    /// Estimated overlap of the OCR text; known only after processing.
+    pub text_similarity: Option<f32>,
+}
+
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfaReport {
+    /// Part and conformance level requested, e.g. `2b`.
+    pub expected_level: Option<String>,
+    /// Part and conformance level declared in the XMP metadata.
+    pub claimed_level: Option<String>,
+    pub conforms: bool,
+    pub issues: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimisationReport {
+    pub profile: String,
+    /// Size of the OCR output before optimisation.
+    pub original_bytes: u64,
+    /// Size of the document as saved.
+    pub optimised_bytes: u64,
+    pub target_max_bytes: Option<u64>,
+    /// Number of optimisation passes, more than one when quality was stepped
+    /// down to meet the target.
+    pub attempts: u32,
+    pub target_met: bool,
+}
+
+/// First and last Bates numbers of a document, as stamped.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BatesRange {
+    pub first: String,
+    pub last: String,
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -110,6 +259,8 @@ pub struct DocumentProcessStageError {
     pub page_preprocess_stage_result: PagePreprocessStageResult,
     pub page_number_prefix: String,
     pub error_message: String,
+    #[serde(default)]
+    pub source_pages: Vec<PageReference>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -0,0 +1,456 @@
+use lazy_static::lazy_static;
+use log::warn;
+use std::{
+    collections::{HashMap, HashSet},
+    iter::Peekable,
+    path::Path,
+    sync::Mutex,
+};
+use tauri::AppHandle;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use super::dates::{normalise_dates_in_text, PartialDate};
+use super::models::identifiers::IdentifierKind;
+use super::models::settings::{NamingPreset, NamingSettings, Settings};
+use super::models::workflows::DocumentProcessStage;
+use super::sanitizer::sanitise_file_name;
+use super::settings::{load_settings, save_settings};
+
+const COUNTER_FIELD: &str = "counter";
+
+lazy_static! {
+    static ref COUNTER_LOCK: Mutex<()> = Mutex::new(());
+}
+
+#[derive(Debug, Clone)]
+pub enum FieldValue {
+    Text(String),
+    Date(PartialDate),
+    Number(u64),
+}
+
+#[derive(Debug, Clone)]
+enum Formatter {
+    Slug(Option<usize>),
+    Truncate(usize),
+    Upper,
+    Lower,
+    Pad(usize),
+    DateFormat(String),
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Placeholder {
+        field: String,
+        formatters: Vec<Formatter>,
+    },
+}
+
+/// A parsed file-naming template such as
+/// `{date:%Y-%m-%d}_{type_abbr}_{client}_{summary:slug:40}`.
+///
+/// Placeholders name a field followed by `:`-separated formatters: `slug`
+/// (with an optional maximum length), `truncate:N`, `upper`, `lower`, `pad:N`
+/// and date patterns starting with `%`. Literal braces are written `{{` and
+/// `}}`.
+#[derive(Debug, Clone)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NamingContext {
+    fields: HashMap<String, FieldValue>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => {
+                                return Err(format!("Unclosed placeholder in template: {}", template))
+                            }
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(parse_placeholder(&placeholder)?);
+                }
+                '}' => return Err(format!("Unmatched '}}' in template: {}", template)),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        if segments.is_empty() {
+            return Err("Template is empty".to_string());
+        }
+
+        Ok(Self { segments })
+    }
+
+    pub fn uses_field(&self, name: &str) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Placeholder { field, .. } if field == name))
+    }
+
+    /// Renders the template. Fields missing from the context render as empty
+    /// strings so optional custom fields do not break a preset.
+    pub fn render(&self, context: &NamingContext) -> String {
+        let mut output = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => output.push_str(text),
+                Segment::Placeholder { field, formatters } => {
+                    if let Some(value) = context.fields.get(field) {
+                        output.push_str(&apply_formatters(value, formatters));
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+impl NamingContext {
+    pub fn from_stage(stage: &DocumentProcessStage, counter: u64) -> Self {
+        let result = &stage.page_preprocess_stage_result;
+        let references = stage.page_references();
+        let pages = references
+            .iter()
+            .map(|reference| reference.page_number.to_string())
+            .collect::<Vec<String>>();
+        let chronological_dates = result.chronological_dates();
+
+        let mut context = Self::default();
+        for (name, value) in &result.custom_fields {
+            context.insert(name, FieldValue::Text(value.clone()));
+        }
+        context.insert("id", FieldValue::Text(stage.id.clone()));
+        context.insert(
+            "page_number_prefix",
+            FieldValue::Text(stage.page_number_prefix.clone()),
+        );
+        context.insert("pages", FieldValue::Text(pages.join("-")));
+        context.insert("page_count", FieldValue::Number(pages.len() as u64));
+        if let Some(first) = references.first() {
+            context.insert("first_page", FieldValue::Number(first.page_number as u64));
+            // A composition is named after the document it starts with.
+            if let Some(source_name) = Path::new(&first.document_path).file_stem() {
+                context.insert(
+                    "source_name",
+                    FieldValue::Text(source_name.to_string_lossy().into_owned()),
+                );
+            }
+        }
+        if let Some(last) = references.last() {
+            context.insert("last_page", FieldValue::Number(last.page_number as u64));
+        }
+        context.insert("type_name", FieldValue::Text(result.type_name.clone()));
+        context.insert("type_abbr", FieldValue::Text(result.type_abbr.clone()));
+        context.insert("summary", FieldValue::Text(result.summary.clone()));
+        context.insert(
+            "suggested_file_name",
+            FieldValue::Text(normalise_dates_in_text(&result.suggested_file_name)),
+        );
+        if let Some(date) = result.primary_date() {
+            context.insert("date", FieldValue::Date(date));
+        }
+        if let Some(date) = chronological_dates.first() {
+            context.insert("date_first", FieldValue::Date(*date));
+        }
+        if let Some(date) = chronological_dates.last() {
+            context.insert("date_last", FieldValue::Date(*date));
+        }
+        // The first valid value of each kind, plus its bare digits for the
+        // punctuated ones, e.g. `{cnpj}` and `{cnpj_digits}`.
+        let mut kinds = HashSet::new();
+        for identifier in stage.identifiers.iter().filter(|identifier| identifier.valid) {
+            if !kinds.insert(identifier.kind) {
+                continue;
+            }
+            let name = identifier.kind.field_name();
+            context.insert(name, FieldValue::Text(identifier.value.clone()));
+            if !matches!(identifier.kind, IdentifierKind::Oab | IdentifierKind::Amount) {
+                let digits = identifier
+                    .value
+                    .chars()
+                    .filter(char::is_ascii_alphanumeric)
+                    .collect();
+                context.insert(&format!("{}_digits", name), FieldValue::Text(digits));
+            }
+        }
+        context.insert(COUNTER_FIELD, FieldValue::Number(counter));
+        context
+    }
+
+    pub fn insert(&mut self, name: &str, value: FieldValue) {
+        self.fields.insert(name.to_string(), value);
+    }
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Segment, String> {
+    let mut tokens = placeholder.split(':').map(str::trim).peekable();
+    let field = tokens
+        .next()
+        .filter(|field| !field.is_empty())
+        .ok_or_else(|| format!("Placeholder without a field name: {{{}}}", placeholder))?
+        .to_string();
+
+    let mut formatters = Vec::new();
+    while let Some(token) = tokens.next() {
+        let formatter = match token {
+            "slug" => Formatter::Slug(numeric_argument(&mut tokens)),
+            "truncate" => Formatter::Truncate(
+                numeric_argument(&mut tokens)
+                    .ok_or_else(|| format!("truncate needs a length in {{{}}}", placeholder))?,
+            ),
+            "pad" => Formatter::Pad(
+                numeric_argument(&mut tokens)
+                    .ok_or_else(|| format!("pad needs a width in {{{}}}", placeholder))?,
+            ),
+            "upper" => Formatter::Upper,
+            "lower" => Formatter::Lower,
+            token if token.contains('%') => Formatter::DateFormat(token.to_string()),
+            token => return Err(format!("Unknown formatter '{}' in {{{}}}", token, placeholder)),
+        };
+        formatters.push(formatter);
+    }
+
+    Ok(Segment::Placeholder { field, formatters })
+}
+
+fn numeric_argument<'a, I: Iterator<Item = &'a str>>(tokens: &mut Peekable<I>) -> Option<usize> {
+    let argument = tokens.peek().and_then(|next| next.parse::<usize>().ok());
+    if argument.is_some() {
+        tokens.next();
+    }
+    argument
+}
+
+fn apply_formatters(value: &FieldValue, formatters: &[Formatter]) -> String {
+    let mut output = match value {
+        FieldValue::Text(text) => text.clone(),
+        FieldValue::Date(date) => date.to_string(),
+        FieldValue::Number(number) => number.to_string(),
+    };
+
+    for formatter in formatters {
+        output = match formatter {
+            Formatter::Slug(max_length) => slugify(&output, *max_length),
+            Formatter::Truncate(length) => {
+                output.chars().take(*length).collect::<String>().trim_end().to_string()
+            }
+            Formatter::Upper => output.to_uppercase(),
+            Formatter::Lower => output.to_lowercase(),
+            Formatter::Pad(width) => format!("{:0>width$}", output, width = *width),
+            Formatter::DateFormat(pattern) => match value {
+                FieldValue::Date(date) => date.format(pattern),
+                _ => PartialDate::parse(&output)
+                    .map(|date| date.format(pattern))
+                    .unwrap_or(output),
+            },
+        };
+    }
+
+    output
+}
+
+/// Lowercases, strips accents and replaces every run of characters that are
+/// not ASCII letters or digits with a single `-`.
+pub fn slugify(text: &str, max_length: Option<usize>) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.nfd().filter(|c| !is_combining_mark(*c)) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    if let Some(max_length) = max_length {
+        slug.truncate(max_length);
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+fn active_preset(presets: &[NamingPreset], active: &str) -> Result<usize, String> {
+    presets
+        .iter()
+        .position(|preset| preset.name == active)
+        .ok_or_else(|| format!("Naming preset not found: {}", active))
+}
+
+/// A number taken from the counter of a naming preset for one document. It is
+/// given back when dropped, unless `keep` was called once the document is in
+/// place, so a failed run does not leave a gap.
+pub struct CounterReservation {
+    handle: AppHandle,
+    preset: String,
+    number: u64,
+    kept: bool,
+}
+
+impl CounterReservation {
+    fn take(handle: &AppHandle, preset: &str) -> Result<Self, String> {
+        let _guard = COUNTER_LOCK
+            .lock()
+            .map_err(|e| format!("Failed to lock the naming counter: {}", e))?;
+        let mut settings = load_settings(handle)?;
+        let index = active_preset(&settings.naming.presets, preset)?;
+        let number = settings.naming.presets[index].next_counter;
+        settings.naming.presets[index].next_counter += 1;
+        save_settings(handle, &settings)?;
+        Ok(Self {
+            handle: handle.clone(),
+            preset: preset.to_string(),
+            number,
+            kept: false,
+        })
+    }
+
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    pub fn keep(mut self) {
+        self.kept = true;
+    }
+
+    /// Moves the counter back unless a later number was taken meanwhile.
+    fn release(&self) -> Result<(), String> {
+        let _guard = COUNTER_LOCK
+            .lock()
+            .map_err(|e| format!("Failed to lock the naming counter: {}", e))?;
+        let mut settings = load_settings(&self.handle)?;
+        let index = active_preset(&settings.naming.presets, &self.preset)?;
+        let preset = &mut settings.naming.presets[index];
+        if preset.next_counter != self.number + 1 {
+            return Ok(());
+        }
+        preset.next_counter = self.number;
+        save_settings(&self.handle, &settings)
+    }
+}
+
+impl Drop for CounterReservation {
+    fn drop(&mut self) {
+        if self.kept {
+            return;
+        }
+        if let Err(e) = self.release() {
+            warn!(
+                "Failed to give back counter {} of preset '{}': {}",
+                self.number, self.preset, e
+            );
+        }
+    }
+}
+
+/// Saves `settings` with the counters of existing presets as stored, under the
+/// counter lock. Counters only move through reservations, so a settings form
+/// loaded before a document was numbered cannot move one back, and a
+/// reservation cannot overwrite the settings being saved.
+pub fn save_settings_keeping_counters(
+    handle: &AppHandle,
+    settings: &mut Settings,
+) -> Result<(), String> {
+    let _guard = COUNTER_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to lock the naming counter: {}", e))?;
+    let stored = load_settings(handle)?;
+    for preset in &mut settings.naming.presets {
+        if let Some(stored_preset) = stored
+            .naming
+            .presets
+            .iter()
+            .find(|stored_preset| stored_preset.name == preset.name)
+        {
+            preset.next_counter = stored_preset.next_counter;
+        }
+    }
+    save_settings(handle, settings)
+}
+
+/// Renders the sanitised file name for a document process stage with the
+/// active naming preset, or with `template` when one is given, numbering it
+/// with the next counter without taking it.
+pub fn render_file_name(
+    handle: &AppHandle,
+    stage: &DocumentProcessStage,
+    template: Option<&str>,
+) -> Result<String, String> {
+    let settings = load_settings(handle)?;
+    let index = active_preset(&settings.naming.presets, &settings.naming.active_preset)?;
+    let preset = &settings.naming.presets[index];
+    let template = Template::parse(template.unwrap_or(&preset.template))?;
+    let context = NamingContext::from_stage(stage, preset.next_counter);
+    sanitise_file_name(&template.render(&context), &settings.file_names)
+}
+
+/// Renders the file name of a document being processed with the active
+/// naming preset. When the template uses `{counter}`, a number is reserved
+/// from the preset counter; keep the reservation once the document is in
+/// place.
+pub fn render_output_file_name(
+    handle: &AppHandle,
+    stage: &DocumentProcessStage,
+) -> Result<(String, Option<CounterReservation>), String> {
+    let settings = load_settings(handle)?;
+    let index = active_preset(&settings.naming.presets, &settings.naming.active_preset)?;
+    let preset = &settings.naming.presets[index];
+    let template = Template::parse(&preset.template)?;
+    let reservation = if template.uses_field(COUNTER_FIELD) {
+        Some(CounterReservation::take(handle, &preset.name)?)
+    } else {
+        None
+    };
+    let counter = reservation
+        .as_ref()
+        .map_or(preset.next_counter, CounterReservation::number);
+    let context = NamingContext::from_stage(stage, counter);
+    let file_name = sanitise_file_name(&template.render(&context), &settings.file_names)?;
+    Ok((file_name, reservation))
+}
+
+/// Checks that every preset template parses and that the active preset exists,
+/// so invalid settings are rejected when saved rather than when a document is
+/// processed.
+pub fn validate_naming_settings(naming: &NamingSettings) -> Result<(), String> {
+    for preset in &naming.presets {
+        Template::parse(&preset.template)
+            .map_err(|e| format!("Invalid template in preset '{}': {}", preset.name, e))?;
+    }
+    active_preset(&naming.presets, &naming.active_preset).map(|_| ())
+}
+
+#[tauri::command]
+pub fn preview_file_name(
+    handle: AppHandle,
+    document_process_stage: DocumentProcessStage,
+    template: Option<String>,
+) -> Result<String, String> {
+    render_file_name(&handle, &document_process_stage, template.as_deref())
+}
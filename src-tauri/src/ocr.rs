@@ -0,0 +1,309 @@
+use log::{debug, error};
+use regex::Regex;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tauri::AppHandle;
+
+use super::models::settings::{OcrEngineKind, OcrMode, OcrProfile, OcrSettings, PdfaLevel};
+use super::utilities::call_utility;
+
+pub const OCRMYPDF_UTILITY: &str = if cfg!(windows) { "ocrmypdf.exe" } else { "ocrmypdf" };
+pub const TESSERACT_UTILITY: &str = if cfg!(windows) { "tesseract.exe" } else { "tesseract" };
+pub const MAGICK_UTILITY: &str = if cfg!(windows) { "magick.exe" } else { "magick" };
+/// Resolution the pages are rendered at for Tesseract; the previews made at
+/// extraction are too coarse to recognise reliably or to archive.
+const TESSERACT_DENSITY: u32 = 300;
+
+/// What an engine works on. `input` and `output` may be the same file. The
+/// recognised text goes to `text_output`, one page per form feed, when given.
+pub struct OcrRequest<'a> {
+    pub input: &'a Path,
+    pub output: &'a Path,
+    pub text_output: Option<&'a Path>,
+}
+
+pub trait OcrEngine {
+    fn utility(&self) -> &'static str;
+
+    /// Command line for `request`. May write helper files next to the output.
+    fn arguments(&self, request: &OcrRequest) -> Result<Vec<String>, String>;
+}
+
+pub struct OcrMyPdf<'a> {
+    profile: &'a OcrProfile,
+}
+
+impl OcrEngine for OcrMyPdf<'_> {
+    fn utility(&self) -> &'static str {
+        OCRMYPDF_UTILITY
+    }
+
+    fn arguments(&self, request: &OcrRequest) -> Result<Vec<String>, String> {
+        let profile = self.profile;
+        let mut arguments = vec![
+            match profile.mode {
+                OcrMode::Force => "--force-ocr".to_owned(),
+                OcrMode::SkipText => "--skip-text".to_owned(),
+            },
+            "--pdf-renderer".to_owned(),
+            "hocr".to_owned(),
+            "--color-conversion-strategy".to_owned(),
+            "UseDeviceIndependentColor".to_owned(),
+            "-l".to_owned(),
+            profile.languages.join("+"),
+        ];
+        if profile.deskew {
+            arguments.push("--deskew".to_owned());
+        }
+        if profile.rotate_pages {
+            arguments.push("--rotate-pages".to_owned());
+        }
+        if profile.clean {
+            arguments.push("--clean".to_owned());
+        }
+        arguments.push("--optimize".to_owned());
+        arguments.push(profile.optimisation_level.to_string());
+        if let Some(text_output) = request.text_output {
+            arguments.push("--sidecar".to_owned());
+            arguments.push(text_output.display().to_string());
+        }
+        arguments.push("--output-type".to_owned());
+        arguments.push(output_type(profile).to_owned());
+        arguments.push(request.input.display().to_string());
+        arguments.push(request.output.display().to_string());
+        Ok(arguments)
+    }
+}
+
+/// Runs Tesseract directly on the pages rendered from the input and writes a
+/// searchable PDF. The output is rebuilt from the images, so PDF/A and the
+/// other ocrmypdf options do not apply.
+pub struct Tesseract<'a> {
+    profile: &'a OcrProfile,
+    page_images: &'a [PathBuf],
+}
+
+impl OcrEngine for Tesseract<'_> {
+    fn utility(&self) -> &'static str {
+        TESSERACT_UTILITY
+    }
+
+    fn arguments(&self, request: &OcrRequest) -> Result<Vec<String>, String> {
+        if self.page_images.is_empty() {
+            return Err("Tesseract needs the page images of the document".to_string());
+        }
+        if let Some(missing) = self.page_images.iter().find(|image| !image.is_file()) {
+            return Err(format!("Page image not found: {}", missing.display()));
+        }
+
+        // Tesseract reads a multi-page input from a list of image paths and
+        // appends `.pdf` to the output base it is given.
+        let list_path = request.output.with_extension("pages.txt");
+        let list = self
+            .page_images
+            .iter()
+            .map(|image| image.display().to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        fs::write(&list_path, list).map_err(|e| {
+            error!("Failed to write Tesseract page list: {}", e);
+            format!("Failed to write Tesseract page list: {}", e)
+        })?;
+
+        let mut arguments = vec![
+            list_path.display().to_string(),
+            request.output.with_extension("").display().to_string(),
+            "-l".to_owned(),
+            self.profile.languages.join("+"),
+            "pdf".to_owned(),
+        ];
+        // The text goes next to the PDF; `run_ocr` moves it into place.
+        if request.text_output.is_some() {
+            arguments.push("txt".to_owned());
+        }
+        Ok(arguments)
+    }
+}
+
+/// The ocrmypdf `--output-type` for documents OCRed with `profile`. Only
+/// ocrmypdf produces PDF/A.
+pub fn output_type(profile: &OcrProfile) -> &'static str {
+    if profile.engine != OcrEngineKind::OcrMyPdf {
+        return "pdf";
+    }
+    match profile.pdfa_level {
+        PdfaLevel::None => "pdf",
+        PdfaLevel::Pdfa1 => "pdfa-1",
+        PdfaLevel::Pdfa2 => "pdfa-2",
+        PdfaLevel::Pdfa3 => "pdfa-3",
+    }
+}
+
+/// The engine configured by `profile`, or `None` when the profile skips OCR.
+/// `page_images` are the rendered pages, in order, for engines that cannot
+/// read PDFs.
+pub fn engine_for<'a>(
+    profile: &'a OcrProfile,
+    page_images: &'a [PathBuf],
+) -> Option<Box<dyn OcrEngine + 'a>> {
+    match profile.engine {
+        OcrEngineKind::OcrMyPdf => Some(Box::new(OcrMyPdf { profile })),
+        OcrEngineKind::Tesseract => Some(Box::new(Tesseract {
+            profile,
+            page_images,
+        })),
+        OcrEngineKind::None => None,
+    }
+}
+
+/// Looks up `name`, falling back to the default profile when no name is given.
+pub fn select_profile<'a>(
+    settings: &'a OcrSettings,
+    name: Option<&str>,
+) -> Result<&'a OcrProfile, String> {
+    let name = name.unwrap_or(&settings.default_profile);
+    settings
+        .profiles
+        .iter()
+        .find(|profile| profile.name == name)
+        .ok_or_else(|| format!("OCR profile not found: {}", name))
+}
+
+pub async fn run_ocr(
+    handle: &AppHandle,
+    profile: &OcrProfile,
+    request: OcrRequest<'_>,
+) -> Result<(), String> {
+    if profile.engine == OcrEngineKind::None {
+        debug!("OCR profile '{}' skips OCR", profile.name);
+        if request.input != request.output {
+            fs::copy(request.input, request.output)
+                .map_err(|e| format!("Failed to copy document: {}", e))?;
+        }
+        return Ok(());
+    }
+
+    let pages_directory = request.output.with_extension("pages");
+    let page_images = if profile.engine == OcrEngineKind::Tesseract {
+        let rendered = render_pages(handle, request.input, &pages_directory).await;
+        if rendered.is_err() {
+            let _ = fs::remove_dir_all(&pages_directory);
+        }
+        rendered?
+    } else {
+        Vec::new()
+    };
+    let Some(engine) = engine_for(profile, &page_images) else {
+        return Ok(());
+    };
+
+    let arguments = engine.arguments(&request)?;
+    let is_success = call_utility(
+        handle.clone(),
+        engine.utility().to_owned(),
+        arguments,
+        false,
+    )
+    .await;
+
+    if profile.engine == OcrEngineKind::Tesseract {
+        let _ = fs::remove_file(request.output.with_extension("pages.txt"));
+        let _ = fs::remove_dir_all(&pages_directory);
+        if let Some(text_output) = request.text_output.filter(|_| is_success) {
+            fs::rename(request.output.with_extension("txt"), text_output)
+                .map_err(|e| format!("Failed to move Tesseract text output: {}", e))?;
+        }
+    }
+    if is_success {
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to call {} with OCR profile '{}'",
+            engine.utility(),
+            profile.name
+        ))
+    }
+}
+
+/// Renders every page of `input` into `directory` at `TESSERACT_DENSITY` and
+/// returns the images in page order.
+async fn render_pages(
+    handle: &AppHandle,
+    input: &Path,
+    directory: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    fs::create_dir_all(directory).map_err(|e| {
+        error!("Failed to create page image directory: {}", e);
+        format!("Failed to create page image directory: {}", e)
+    })?;
+    let args = vec![
+        "-density".to_owned(),
+        TESSERACT_DENSITY.to_string(),
+        input.display().to_string(),
+        "-background".to_owned(),
+        "white".to_owned(),
+        "-alpha".to_owned(),
+        "remove".to_owned(),
+        "-alpha".to_owned(),
+        "off".to_owned(),
+        directory.join("page-%d.png").display().to_string(),
+    ];
+    if !call_utility(handle.clone(), MAGICK_UTILITY.to_owned(), args, false).await {
+        error!("Failed to render the pages of {}", input.display());
+        return Err(format!("Failed to render the pages of {}", input.display()));
+    }
+    let entries = fs::read_dir(directory)
+        .map_err(|e| format!("Failed to read {}: {}", directory.display(), e))?;
+    let mut pages = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let index = path
+                .file_stem()?
+                .to_str()?
+                .strip_prefix("page-")?
+                .parse::<usize>()
+                .ok()?;
+            Some((index, path))
+        })
+        .collect::<Vec<(usize, PathBuf)>>();
+    pages.sort();
+    Ok(pages.into_iter().map(|(_, path)| path).collect())
+}
+
+pub fn validate_ocr_settings(ocr: &OcrSettings) -> Result<(), String> {
+    let language = Regex::new(r"^[a-z]{3}(_[a-z]+)*$").map_err(|e| e.to_string())?;
+    for (index, profile) in ocr.profiles.iter().enumerate() {
+        if profile.name.trim().is_empty() {
+            return Err("OCR profile names cannot be empty".to_string());
+        }
+        if ocr.profiles[..index]
+            .iter()
+            .any(|other| other.name == profile.name)
+        {
+            return Err(format!("Duplicate OCR profile: {}", profile.name));
+        }
+        if profile.engine != OcrEngineKind::None && profile.languages.is_empty() {
+            return Err(format!("OCR profile '{}' has no languages", profile.name));
+        }
+        if let Some(invalid) = profile
+            .languages
+            .iter()
+            .find(|code| !language.is_match(code))
+        {
+            return Err(format!(
+                "Invalid language '{}' in OCR profile '{}'",
+                invalid, profile.name
+            ));
+        }
+        if profile.optimisation_level > 3 {
+            return Err(format!(
+                "Optimisation level of OCR profile '{}' must be between 0 and 3",
+                profile.name
+            ));
+        }
+    }
+    select_profile(ocr, None).map(|_| ())
+}
@@ -0,0 +1,278 @@
+use log::{debug, warn};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tauri::AppHandle;
+
+use super::models::settings::{OcrProfile, OptimisationProfile, OptimisationSettings};
+use super::ocr::{output_type, OCRMYPDF_UTILITY};
+use super::utilities::{call_utility, try_call_utility};
+use super::workspace::ProcessingWorkspace;
+
+const GHOSTSCRIPT_UTILITY: &str = if cfg!(windows) { "gswin64c.exe" } else { "gs" };
+const MAX_ATTEMPTS: u32 = 5;
+/// Resolutions tried, in order, when a size target is not met.
+const RESOLUTION_STEPS: [u32; 5] = [300, 200, 150, 120, 100];
+const JPEG_QUALITY_STEP: u8 = 15;
+const MIN_JPEG_QUALITY: u8 = 30;
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+pub struct OptimisationOutcome {
+    pub path: PathBuf,
+    pub original_bytes: u64,
+    pub optimised_bytes: u64,
+    pub attempts: u32,
+}
+
+/// Looks up `name`, falling back to the default profile. `None` means the
+/// document is not optimised.
+pub fn select_optimisation_profile<'a>(
+    settings: &'a OptimisationSettings,
+    name: Option<&str>,
+) -> Result<Option<&'a OptimisationProfile>, String> {
+    let Some(name) = name.or(settings.default_profile.as_deref()) else {
+        return Ok(None);
+    };
+    settings
+        .profiles
+        .iter()
+        .find(|profile| profile.name == name)
+        .map(Some)
+        .ok_or_else(|| format!("Optimisation profile not found: {}", name))
+}
+
+/// Shrinks `input` with `profile`, writing passes into the workspace. With a
+/// size target, every pass starts again from `input` with lower quality until
+/// the result fits or the steps run out; the smallest result is kept.
+pub async fn optimise(
+    handle: &AppHandle,
+    profile: &OptimisationProfile,
+    ocr_profile: &OcrProfile,
+    target_max_bytes: Option<u64>,
+    input: &Path,
+    workspace: &ProcessingWorkspace,
+) -> Result<OptimisationOutcome, String> {
+    let original_bytes = file_size(input)?;
+    let mut best: Option<(PathBuf, u64)> = None;
+    let mut attempts = 0;
+    let mut step = profile.clone();
+
+    while attempts < MAX_ATTEMPTS {
+        attempts += 1;
+        let output = workspace.path(&format!("optimised-{}.pdf", attempts));
+        run_pass(handle, &step, ocr_profile, input, &output).await?;
+        let size = file_size(&output)?;
+        debug!(
+            "Optimisation pass {} with profile '{}': {} -> {} bytes",
+            attempts, profile.name, original_bytes, size
+        );
+        if best.as_ref().map_or(true, |(_, best_size)| size < *best_size) {
+            best = Some((output, size));
+        }
+
+        let fits = target_max_bytes.map_or(true, |target| size <= target);
+        if fits {
+            break;
+        }
+        match step_down(&step) {
+            Some(next) => step = next,
+            None => break,
+        }
+    }
+
+    let (path, optimised_bytes) = best.ok_or("No optimisation pass ran")?;
+    if let Some(target) = target_max_bytes.filter(|&target| optimised_bytes > target) {
+        warn!(
+            "Could not bring the document under {} bytes; smallest result is {} bytes",
+            target, optimised_bytes
+        );
+    }
+    Ok(OptimisationOutcome {
+        path,
+        original_bytes,
+        optimised_bytes,
+        attempts,
+    })
+}
+
+/// Rewrites `path` with compressed object streams through qpdf. Not applied
+/// to PDF/A-1, which forbids them.
+pub async fn compress_object_streams(
+    handle: &AppHandle,
+    ocr_profile: &OcrProfile,
+    input: &Path,
+    output: &Path,
+) -> Result<(), String> {
+    if output_type(ocr_profile) == "pdfa-1" {
+        return Err("Object streams are not allowed in PDF/A-1".to_string());
+    }
+    try_call_utility(
+        handle.clone(),
+        "qpdf".to_owned(),
+        vec![
+            "--object-streams=generate".to_owned(),
+            "--compress-streams=y".to_owned(),
+            "--recompress-flate".to_owned(),
+            "--compression-level=9".to_owned(),
+            input.display().to_string(),
+            output.display().to_string(),
+        ],
+        false,
+    )
+    .await
+}
+
+/// Downsampling and grayscale conversion go through Ghostscript; image
+/// recompression and the PDF/A output type through ocrmypdf with OCR turned
+/// off, which also restores PDF/A after Ghostscript.
+async fn run_pass(
+    handle: &AppHandle,
+    profile: &OptimisationProfile,
+    ocr_profile: &OcrProfile,
+    input: &Path,
+    output: &Path,
+) -> Result<(), String> {
+    let mut source = input.to_path_buf();
+    if profile.image_resolution.is_some() || profile.grayscale {
+        let resampled = output.with_extension("gs.pdf");
+        let is_success = call_utility(
+            handle.clone(),
+            GHOSTSCRIPT_UTILITY.to_owned(),
+            ghostscript_arguments(profile, input, &resampled),
+            false,
+        )
+        .await;
+        if !is_success {
+            return Err(format!("Failed to call {}", GHOSTSCRIPT_UTILITY));
+        }
+        source = resampled;
+    }
+
+    let lossy = profile.jpeg_quality.is_some() || profile.jbig2_lossy;
+    let mut arguments = vec![
+        "--skip-text".to_owned(),
+        "--tesseract-timeout".to_owned(),
+        "0".to_owned(),
+        "--optimize".to_owned(),
+        if lossy { "3" } else { "1" }.to_owned(),
+    ];
+    if let Some(quality) = profile.jpeg_quality {
+        arguments.push("--jpeg-quality".to_owned());
+        arguments.push(quality.to_string());
+        arguments.push("--png-quality".to_owned());
+        arguments.push(quality.to_string());
+    }
+    if profile.jbig2_lossy {
+        arguments.push("--jbig2-lossy".to_owned());
+    }
+    arguments.push("--output-type".to_owned());
+    arguments.push(output_type(ocr_profile).to_owned());
+    arguments.push(source.display().to_string());
+    arguments.push(output.display().to_string());
+
+    let is_success = call_utility(handle.clone(), OCRMYPDF_UTILITY.to_owned(), arguments, false).await;
+    if source != input {
+        let _ = fs::remove_file(&source);
+    }
+    if !is_success {
+        return Err(format!("Failed to call {}", OCRMYPDF_UTILITY));
+    }
+    Ok(())
+}
+
+fn ghostscript_arguments(profile: &OptimisationProfile, input: &Path, output: &Path) -> Vec<String> {
+    let mut arguments = vec![
+        "-sDEVICE=pdfwrite".to_owned(),
+        "-dSAFER".to_owned(),
+        "-dQUIET".to_owned(),
+        "-dCompatibilityLevel=1.7".to_owned(),
+    ];
+    if let Some(resolution) = profile.image_resolution {
+        for kind in ["Color", "Gray"] {
+            arguments.push(format!("-dDownsample{}Images=true", kind));
+            arguments.push(format!("-d{}ImageDownsampleType=/Bicubic", kind));
+            arguments.push(format!("-d{}ImageResolution={}", kind, resolution));
+        }
+        // Bilevel scans lose legibility fast; keep them sharper.
+        arguments.push("-dDownsampleMonoImages=true".to_owned());
+        arguments.push("-dMonoImageDownsampleType=/Subsample".to_owned());
+        arguments.push(format!("-dMonoImageResolution={}", (resolution * 2).max(300)));
+    }
+    if profile.grayscale {
+        arguments.push("-sColorConversionStrategy=Gray".to_owned());
+        arguments.push("-dProcessColorModel=/DeviceGray".to_owned());
+    }
+    arguments.push("-o".to_owned());
+    arguments.push(output.display().to_string());
+    arguments.push(input.display().to_string());
+    arguments
+}
+
+/// The next, smaller, settings to try, or `None` when nothing is left to lower.
+fn step_down(profile: &OptimisationProfile) -> Option<OptimisationProfile> {
+    let mut next = profile.clone();
+    let resolution = RESOLUTION_STEPS
+        .iter()
+        .copied()
+        .find(|&step| profile.image_resolution.map_or(true, |current| step < current));
+    let quality = profile
+        .jpeg_quality
+        .unwrap_or(DEFAULT_JPEG_QUALITY + JPEG_QUALITY_STEP)
+        .saturating_sub(JPEG_QUALITY_STEP)
+        .max(MIN_JPEG_QUALITY);
+
+    next.image_resolution = resolution.or(profile.image_resolution);
+    next.jpeg_quality = Some(quality);
+    next.jbig2_lossy = true;
+
+    let changed = next.image_resolution != profile.image_resolution
+        || next.jpeg_quality != profile.jpeg_quality
+        || !profile.jbig2_lossy;
+    changed.then_some(next)
+}
+
+fn file_size(path: &Path) -> Result<u64, String> {
+    fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+pub fn validate_optimisation_settings(settings: &OptimisationSettings) -> Result<(), String> {
+    for (index, profile) in settings.profiles.iter().enumerate() {
+        if profile.name.trim().is_empty() {
+            return Err("Optimisation profile names cannot be empty".to_string());
+        }
+        if settings.profiles[..index]
+            .iter()
+            .any(|other| other.name == profile.name)
+        {
+            return Err(format!("Duplicate optimisation profile: {}", profile.name));
+        }
+        if profile
+            .jpeg_quality
+            .is_some_and(|quality| !(1..=100).contains(&quality))
+        {
+            return Err(format!(
+                "JPEG quality of optimisation profile '{}' must be between 1 and 100",
+                profile.name
+            ));
+        }
+        if profile
+            .image_resolution
+            .is_some_and(|resolution| !(36..=1200).contains(&resolution))
+        {
+            return Err(format!(
+                "Image resolution of optimisation profile '{}' must be between 36 and 1200 dpi",
+                profile.name
+            ));
+        }
+        if profile.target_max_bytes == Some(0) {
+            return Err(format!(
+                "Size target of optimisation profile '{}' must be above zero",
+                profile.name
+            ));
+        }
+    }
+    select_optimisation_profile(settings, None).map(|_| ())
+}
@@ -0,0 +1,535 @@
+use log::{debug, warn};
+use lopdf::{xref::XrefType, Dictionary, Document, Object, ObjectId};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use super::models::workflows::PageReference;
+
+/// Page attributes a page may inherit from its ancestors in the page tree.
+const INHERITABLE_PAGE_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Copies objects from one source document into the output, renumbering them
+/// on first sight so resources shared between pages are written only once.
+struct SourceImport<'a> {
+    name: &'a str,
+    document: &'a Document,
+    page_ids: HashSet<ObjectId>,
+    mapping: HashMap<ObjectId, ObjectId>,
+    copied_pages: HashSet<ObjectId>,
+    queue: Vec<ObjectId>,
+}
+
+impl<'a> SourceImport<'a> {
+    fn new(name: &'a str, document: &'a Document) -> Self {
+        Self {
+            name,
+            document,
+            page_ids: document.page_iter().collect(),
+            mapping: HashMap::new(),
+            copied_pages: HashSet::new(),
+            queue: Vec::new(),
+        }
+    }
+
+    /// Rewrites the references inside `object` to output ids. References to
+    /// pages that are not part of the output, and to page tree nodes, become
+    /// null so links and annotations never drag in unselected pages.
+    fn rewrite(&mut self, object: Object, output: &mut Document) -> Object {
+        match object {
+            Object::Reference(id) => {
+                if let Some(new_id) = self.mapping.get(&id) {
+                    return Object::Reference(*new_id);
+                }
+                if self.page_ids.contains(&id) || self.is_page_tree_node(id) {
+                    return Object::Null;
+                }
+                let new_id = output.new_object_id();
+                self.mapping.insert(id, new_id);
+                self.queue.push(id);
+                Object::Reference(new_id)
+            }
+            Object::Array(items) => Object::Array(
+                items
+                    .into_iter()
+                    .map(|item| self.rewrite(item, output))
+                    .collect(),
+            ),
+            Object::Dictionary(dictionary) => {
+                Object::Dictionary(self.rewrite_dictionary(dictionary, output))
+            }
+            Object::Stream(mut stream) => {
+                stream.dict = self.rewrite_dictionary(stream.dict, output);
+                Object::Stream(stream)
+            }
+            other => other,
+        }
+    }
+
+    fn rewrite_dictionary(&mut self, dictionary: Dictionary, output: &mut Document) -> Dictionary {
+        let mut rewritten = Dictionary::new();
+        for (key, value) in dictionary.into_iter() {
+            let value = self.rewrite(value, output);
+            rewritten.set(key, value);
+        }
+        rewritten
+    }
+
+    fn is_page_tree_node(&self, id: ObjectId) -> bool {
+        self.document
+            .get_dictionary(id)
+            .map(|dictionary| dictionary.type_is(b"Pages"))
+            .unwrap_or(false)
+    }
+
+    /// Copies every object queued by `rewrite` until no new references remain.
+    fn drain(&mut self, output: &mut Document) -> Result<(), String> {
+        while let Some(id) = self.queue.pop() {
+            let object = match self.document.get_object(id) {
+                Ok(object) => object.clone(),
+                Err(e) => {
+                    debug!("Replacing missing object {:?} with null: {}", id, e);
+                    Object::Null
+                }
+            };
+            let object = self.rewrite(object, output);
+            output.objects.insert(self.mapping[&id], object);
+        }
+        Ok(())
+    }
+
+    /// The page dictionary with inherited attributes made explicit and the
+    /// link to its old parent removed.
+    fn flattened_page(&self, page_id: ObjectId) -> Result<Dictionary, String> {
+        let mut page = self
+            .document
+            .get_dictionary(page_id)
+            .map_err(|e| format!("Failed to read page {:?}: {}", page_id, e))?
+            .clone();
+
+        let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+        while let Some(parent_id) = parent {
+            let Ok(node) = self.document.get_dictionary(parent_id) else {
+                break;
+            };
+            for key in INHERITABLE_PAGE_KEYS {
+                if !page.has(key) {
+                    if let Ok(value) = node.get(key) {
+                        page.set(key.to_vec(), value.clone());
+                    }
+                }
+            }
+            parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+        }
+        page.remove(b"Parent");
+        Ok(page)
+    }
+
+    /// The source's form fields that have at least one widget in the output,
+    /// plus the rest of its AcroForm dictionary.
+    fn acroform(&mut self, output: &mut Document) -> Result<Option<(Dictionary, Vec<Object>)>, String> {
+        let Ok(catalog) = self.document.catalog() else {
+            return Ok(None);
+        };
+        let acroform = match catalog.get(b"AcroForm") {
+            Ok(Object::Reference(id)) => self.document.get_dictionary(*id).ok().cloned(),
+            Ok(Object::Dictionary(dictionary)) => Some(dictionary.clone()),
+            _ => None,
+        };
+        let Some(mut acroform) = acroform else {
+            return Ok(None);
+        };
+
+        let fields = acroform
+            .remove(b"Fields")
+            .and_then(|fields| fields.as_array().ok().cloned())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|field| field.as_reference().ok())
+            .filter_map(|id| self.mapping.get(&id).map(|new_id| Object::Reference(*new_id)))
+            .collect::<Vec<Object>>();
+        if fields.is_empty() {
+            return Ok(None);
+        }
+
+        let acroform = self.rewrite_dictionary(acroform, output);
+        self.drain(output)?;
+        Ok(Some((acroform, fields)))
+    }
+}
+
+/// A page of one of the sources handed to `compose`.
+struct PageSelection {
+    source: usize,
+    page_number: u32,
+    rotation: i32,
+}
+
+/// One part of a split document: its page range in the input and the PDF.
+pub struct DocumentPart {
+    pub first_page: u32,
+    pub last_page: u32,
+    pub content: Vec<u8>,
+}
+
+/// Catalog entries that describe the document as a whole, carried over when a
+/// document is split so every part keeps its PDF/A identification.
+const DOCUMENT_CATALOG_KEYS: [&[u8]; 4] =
+    [b"Metadata", b"OutputIntents", b"Lang", b"ViewerPreferences"];
+
+fn load_document(path: &str) -> Result<Document, String> {
+    let document =
+        Document::load(path).map_err(|e| format!("Failed to load PDF {}: {}", path, e))?;
+    if document.is_encrypted() {
+        return Err(format!("PDF is encrypted: {}", path));
+    }
+    Ok(document)
+}
+
+/// Builds a new PDF at `output_path` from `references`, in order, copying each
+/// page with its rotation, annotations and resources and applying the extra
+/// rotation requested for it. Pages may come from any number of source
+/// documents, and a page may appear more than once.
+pub fn extract_pages(references: &[PageReference], output_path: &Path) -> Result<(), String> {
+    if references.is_empty() {
+        return Err("No pages selected".to_string());
+    }
+
+    let mut paths: Vec<&str> = Vec::new();
+    let mut selection = Vec::with_capacity(references.len());
+    for reference in references {
+        let source = match paths.iter().position(|path| *path == reference.document_path) {
+            Some(source) => source,
+            None => {
+                paths.push(&reference.document_path);
+                paths.len() - 1
+            }
+        };
+        selection.push(PageSelection {
+            source,
+            page_number: reference.page_number,
+            rotation: reference.rotation,
+        });
+    }
+    let documents = paths
+        .iter()
+        .map(|path| load_document(path))
+        .collect::<Result<Vec<Document>, String>>()?;
+    let mut sources = paths
+        .iter()
+        .zip(&documents)
+        .map(|(path, document)| SourceImport::new(path, document))
+        .collect::<Vec<SourceImport>>();
+
+    let mut output = compose(&mut sources, &selection, false)?;
+    output
+        .save(output_path)
+        .map_err(|e| format!("Failed to write {}: {}", output_path.display(), e))?;
+    Ok(())
+}
+
+/// Writes `input_path` to `output_path` with some pages swapped for the first
+/// page of another PDF, keyed by page number. The Info dictionary, XMP
+/// metadata and other document-level entries are left out, as they may repeat
+/// what the replaced pages said.
+pub fn replace_pages(
+    input_path: &Path,
+    replacements: &[(u32, PathBuf)],
+    output_path: &Path,
+) -> Result<(), String> {
+    let name = input_path.display().to_string();
+    let document = load_document(&name)?;
+    let names = replacements
+        .iter()
+        .map(|(_, path)| path.display().to_string())
+        .collect::<Vec<String>>();
+    let replacement_documents = names
+        .iter()
+        .map(|name| load_document(name))
+        .collect::<Result<Vec<Document>, String>>()?;
+
+    let mut sources = vec![SourceImport::new(&name, &document)];
+    sources.extend(
+        names
+            .iter()
+            .zip(&replacement_documents)
+            .map(|(name, document)| SourceImport::new(name, document)),
+    );
+    let page_count = document.get_pages().len() as u32;
+    let selection = (1..=page_count)
+        .map(|page_number| {
+            match replacements.iter().position(|(replaced, _)| *replaced == page_number) {
+                Some(index) => PageSelection {
+                    source: index + 1,
+                    page_number: 1,
+                    rotation: 0,
+                },
+                None => PageSelection {
+                    source: 0,
+                    page_number,
+                    rotation: 0,
+                },
+            }
+        })
+        .collect::<Vec<PageSelection>>();
+
+    let mut output = compose(&mut sources, &selection, false)?;
+    output
+        .save(output_path)
+        .map_err(|e| format!("Failed to write {}: {}", output_path.display(), e))?;
+    Ok(())
+}
+
+/// Splits `input_path` into consecutive parts of at most `max_pages` pages and
+/// `max_bytes` bytes. Parts keep the document's Info dictionary, XMP metadata
+/// and output intents. A single page larger than `max_bytes` becomes a part of
+/// its own.
+pub fn split_document(
+    input_path: &Path,
+    max_pages: Option<u32>,
+    max_bytes: Option<u64>,
+) -> Result<Vec<DocumentPart>, String> {
+    let name = input_path.display().to_string();
+    let document = load_document(&name)?;
+    let page_count = document.get_pages().len() as u32;
+    if page_count == 0 {
+        return Err(format!("PDF has no pages: {}", name));
+    }
+
+    let build = |first_page: u32, last_page: u32| -> Result<Vec<u8>, String> {
+        let mut sources = [SourceImport::new(&name, &document)];
+        let selection = (first_page..=last_page)
+            .map(|page_number| PageSelection {
+                source: 0,
+                page_number,
+                rotation: 0,
+            })
+            .collect::<Vec<PageSelection>>();
+        let mut output = compose(&mut sources, &selection, true)?;
+        let mut content = Vec::new();
+        output
+            .save_to(&mut content)
+            .map_err(|e| format!("Failed to write part of {}: {}", name, e))?;
+        Ok(content)
+    };
+    let fits = |content: &[u8]| max_bytes.map_or(true, |max_bytes| content.len() as u64 <= max_bytes);
+
+    let mut parts = Vec::new();
+    let mut first_page = 1;
+    while first_page <= page_count {
+        let last_allowed = match max_pages {
+            Some(max_pages) => (first_page + max_pages.max(1) - 1).min(page_count),
+            None => page_count,
+        };
+        let mut part = (first_page, build(first_page, first_page)?);
+        if !fits(&part.1) {
+            warn!(
+                "Page {} of {} alone is larger than {} bytes",
+                first_page,
+                name,
+                max_bytes.unwrap_or_default()
+            );
+        } else if max_bytes.is_none() {
+            part = (last_allowed, build(first_page, last_allowed)?);
+        } else {
+            // Grow the part by doubling, then narrow down the last page that
+            // still fits. Shared resources make the size grow with the pages.
+            let mut low = first_page;
+            let mut high = None;
+            let mut length = 1;
+            while high.is_none() && low < last_allowed {
+                length *= 2;
+                let candidate = (first_page + length - 1).min(last_allowed);
+                let content = build(first_page, candidate)?;
+                if fits(&content) {
+                    low = candidate;
+                    part = (candidate, content);
+                } else {
+                    high = Some(candidate);
+                }
+            }
+            if let Some(mut high) = high {
+                while high - low > 1 {
+                    let candidate = low + (high - low) / 2;
+                    let content = build(first_page, candidate)?;
+                    if fits(&content) {
+                        low = candidate;
+                        part = (candidate, content);
+                    } else {
+                        high = candidate;
+                    }
+                }
+            }
+        }
+
+        let (last_page, content) = part;
+        debug!(
+            "Part {} of {}: pages {}-{}, {} bytes",
+            parts.len() + 1,
+            name,
+            first_page,
+            last_page,
+            content.len()
+        );
+        parts.push(DocumentPart {
+            first_page,
+            last_page,
+            content,
+        });
+        first_page = last_page + 1;
+    }
+    Ok(parts)
+}
+
+/// Copies the selected pages into a new document. With `keep_document_entries`
+/// the first source's document-level catalog entries, Info dictionary and ID
+/// are copied too.
+fn compose(
+    sources: &mut [SourceImport],
+    selection: &[PageSelection],
+    keep_document_entries: bool,
+) -> Result<Document, String> {
+    let version = sources
+        .iter()
+        .map(|source| source.document.version.clone())
+        .max()
+        .unwrap_or_else(|| "1.7".to_string());
+    let mut output = Document::with_version(version);
+    output.reference_table.cross_reference_type = XrefType::CrossReferenceTable;
+    let pages_id = output.new_object_id();
+
+    // Allocate every output page first so references between selected pages
+    // (links, annotation owners) resolve to the copies.
+    let mut pages = Vec::with_capacity(selection.len());
+    for selected in selection {
+        let source = &mut sources[selected.source];
+        let page_id = *source
+            .document
+            .get_pages()
+            .get(&selected.page_number)
+            .ok_or_else(|| format!("Page {} not found in {}", selected.page_number, source.name))?;
+        let new_page_id = output.new_object_id();
+        source.mapping.entry(page_id).or_insert(new_page_id);
+        pages.push((selected.source, page_id, new_page_id));
+    }
+
+    for (selected, &(source_index, page_id, new_page_id)) in selection.iter().zip(&pages) {
+        let source = &mut sources[source_index];
+        let mut page = source.flattened_page(page_id)?;
+        if selected.rotation != 0 {
+            let rotation = page
+                .get(b"Rotate")
+                .and_then(Object::as_i64)
+                .unwrap_or(0)
+                + i64::from(selected.rotation);
+            page.set("Rotate", Object::Integer(rotation.rem_euclid(360)));
+        }
+        if !source.copied_pages.insert(page_id) {
+            // A repeated page gets its own dictionary; annotations belong to
+            // the first copy only.
+            page.remove(b"Annots");
+        }
+        let mut page = source.rewrite_dictionary(page, &mut output);
+        page.set("Parent", Object::Reference(pages_id));
+        output.objects.insert(new_page_id, Object::Dictionary(page));
+        source.drain(&mut output)?;
+    }
+
+    let mut catalog = Dictionary::new();
+    catalog.set("Type", Object::Name(b"Catalog".to_vec()));
+    catalog.set("Pages", Object::Reference(pages_id));
+
+    let mut merged_acroform: Option<Dictionary> = None;
+    let mut merged_fields = Vec::new();
+    for source in sources.iter_mut() {
+        if let Some((acroform, fields)) = source.acroform(&mut output)? {
+            merged_acroform.get_or_insert(acroform);
+            merged_fields.extend(fields);
+        }
+    }
+    if let Some(mut acroform) = merged_acroform {
+        acroform.set("Fields", Object::Array(merged_fields));
+        catalog.set("AcroForm", Object::Dictionary(acroform));
+    }
+
+    let mut metadata_id = None;
+    if let Some(source) = sources.first_mut().filter(|_| keep_document_entries) {
+        let document = source.document;
+        if let Ok(source_catalog) = document.catalog() {
+            for key in DOCUMENT_CATALOG_KEYS {
+                if let Ok(value) = source_catalog.get(key) {
+                    let value = source.rewrite(value.clone(), &mut output);
+                    catalog.set(key.to_vec(), value);
+                }
+            }
+        }
+        if let Ok(info) = document.trailer.get(b"Info") {
+            let info = source.rewrite(info.clone(), &mut output);
+            output.trailer.set("Info", info);
+        }
+        if let Ok(id) = document.trailer.get(b"ID") {
+            output.trailer.set("ID", id.clone());
+        }
+        source.drain(&mut output)?;
+        metadata_id = catalog.get(b"Metadata").and_then(Object::as_reference).ok();
+    }
+
+    let mut pages_dictionary = Dictionary::new();
+    pages_dictionary.set("Type", Object::Name(b"Pages".to_vec()));
+    pages_dictionary.set("Count", Object::Integer(pages.len() as i64));
+    pages_dictionary.set(
+        "Kids",
+        Object::Array(
+            pages
+                .iter()
+                .map(|&(_, _, new_page_id)| Object::Reference(new_page_id))
+                .collect(),
+        ),
+    );
+    output
+        .objects
+        .insert(pages_id, Object::Dictionary(pages_dictionary));
+
+    let catalog_id = output.add_object(catalog);
+    output.trailer.set("Root", Object::Reference(catalog_id));
+    // PDF/A requires the XMP packet to stay readable without decoding.
+    if let Some(Ok(Object::Stream(metadata))) =
+        metadata_id.map(|id| output.get_object_mut(id))
+    {
+        metadata.allows_compression = false;
+    }
+    output.compress();
+    Ok(output)
+}
+
+/// Arguments for `qpdf --empty --pages … -- output`, grouping consecutive pages
+/// of the same document into one `file pages` pair. Requested rotations are
+/// passed as `--rotate`, which qpdf applies to the output page numbers.
+pub fn qpdf_page_arguments(references: &[PageReference], output_path: &Path) -> Vec<String> {
+    let mut arguments = vec!["--empty".to_owned(), "--pages".to_owned()];
+    let mut groups: Vec<(&str, Vec<String>)> = Vec::new();
+    for reference in references {
+        match groups.last_mut() {
+            Some((path, pages)) if *path == reference.document_path => {
+                pages.push(reference.page_number.to_string())
+            }
+            _ => groups.push((
+                &reference.document_path,
+                vec![reference.page_number.to_string()],
+            )),
+        }
+    }
+    for (path, pages) in groups {
+        arguments.push(path.to_owned());
+        arguments.push(pages.join(","));
+    }
+    arguments.push("--".to_owned());
+    for (index, reference) in references.iter().enumerate() {
+        if reference.rotation != 0 {
+            arguments.push(format!("--rotate={:+}:{}", reference.rotation, index + 1));
+        }
+    }
+    arguments.push(output_path.display().to_string());
+    arguments
+}
@@ -0,0 +1,179 @@
+use lazy_static::lazy_static;
+use lopdf::{Dictionary, Document, Object, Stream};
+use regex::Regex;
+use std::path::Path;
+
+use super::models::settings::PdfaLevel;
+use super::models::workflows::PdfaReport;
+
+lazy_static! {
+    static ref PDFA_PART: Regex =
+        Regex::new(r#"pdfaid:part(?:\s*=\s*["'](\d)["']|>\s*(\d)\s*<)"#).unwrap();
+    static ref PDFA_CONFORMANCE: Regex =
+        Regex::new(r#"pdfaid:conformance(?:\s*=\s*["']([A-Za-z])["']|>\s*([A-Za-z])\s*<)"#)
+            .unwrap();
+}
+
+const FONT_FILE_KEYS: [&[u8]; 3] = [b"FontFile", b"FontFile2", b"FontFile3"];
+
+/// Checks the parts of PDF/A conformance that can be read from the file
+/// structure: no encryption, a document ID, XMP metadata declaring the
+/// expected part, a PDF/A output intent with an ICC profile, embedded fonts
+/// and no JavaScript. This is not a full validator; it catches the cases where
+/// ocrmypdf fell back to plain PDF output.
+pub fn verify_pdfa(path: &Path, expected: PdfaLevel) -> Result<PdfaReport, String> {
+    let document = Document::load(path)
+        .map_err(|e| format!("Failed to load PDF {}: {}", path.display(), e))?;
+    let expected_part = match expected {
+        PdfaLevel::None => None,
+        PdfaLevel::Pdfa1 => Some("1"),
+        PdfaLevel::Pdfa2 => Some("2"),
+        PdfaLevel::Pdfa3 => Some("3"),
+    };
+
+    let mut issues = Vec::new();
+    if document.is_encrypted() {
+        issues.push("Document is encrypted".to_string());
+    }
+    if document.trailer.get(b"ID").is_err() {
+        issues.push("Trailer has no document ID".to_string());
+    }
+
+    let catalog = document
+        .catalog()
+        .map_err(|e| format!("Failed to read document catalog: {}", e))?;
+
+    let claimed_level = match xmp_metadata(&document) {
+        Some(xmp) => {
+            let (part, conformance) = match pdfa_identification(&xmp) {
+                Some((part, conformance)) => (Some(part), conformance),
+                None => (None, None),
+            };
+            match (&part, expected_part) {
+                (None, _) => issues.push("XMP metadata has no PDF/A identification".to_string()),
+                (Some(part), Some(expected_part)) if part != expected_part => issues.push(format!(
+                    "XMP metadata declares PDF/A-{} instead of PDF/A-{}",
+                    part, expected_part
+                )),
+                _ => {}
+            }
+            part.map(|part| format!("{}{}", part, conformance.unwrap_or_default().to_lowercase()))
+        }
+        None => {
+            issues.push("Catalog has no XMP metadata stream".to_string());
+            None
+        }
+    };
+
+    if !has_pdfa_output_intent(&document, catalog) {
+        issues.push("No PDF/A output intent with an ICC profile".to_string());
+    }
+
+    issues.extend(unembedded_fonts(&document).into_iter().map(|font| {
+        format!("Font is not embedded: {}", font)
+    }));
+
+    let has_javascript = catalog
+        .get_deref(b"Names", &document)
+        .and_then(Object::as_dict)
+        .is_ok_and(|names| names.has(b"JavaScript"));
+    if has_javascript {
+        issues.push("Document contains JavaScript".to_string());
+    }
+
+    Ok(PdfaReport {
+        expected_level: expected_part.map(|part| format!("{}b", part)),
+        claimed_level,
+        conforms: issues.is_empty(),
+        issues,
+    })
+}
+
+/// The PDF/A part and, when declared, conformance level in an XMP packet.
+pub fn pdfa_identification(xmp: &str) -> Option<(String, Option<String>)> {
+    let part = capture(&PDFA_PART, xmp)?;
+    Some((part, capture(&PDFA_CONFORMANCE, xmp)))
+}
+
+fn capture(pattern: &Regex, text: &str) -> Option<String> {
+    pattern.captures(text).and_then(|captures| {
+        captures
+            .get(1)
+            .or_else(|| captures.get(2))
+            .map(|value| value.as_str().to_string())
+    })
+}
+
+fn stream_content(stream: &Stream) -> Option<Vec<u8>> {
+    if stream.dict.has(b"Filter") {
+        stream.decompressed_content().ok()
+    } else {
+        Some(stream.content.clone())
+    }
+}
+
+/// The XMP packet referenced by the catalog, decoded as text.
+pub fn xmp_metadata(document: &Document) -> Option<String> {
+    let stream = document
+        .catalog()
+        .ok()?
+        .get_deref(b"Metadata", document)
+        .and_then(Object::as_stream)
+        .ok()?;
+    stream_content(stream).map(|content| String::from_utf8_lossy(&content).into_owned())
+}
+
+fn has_pdfa_output_intent(document: &Document, catalog: &Dictionary) -> bool {
+    let Ok(intents) = catalog
+        .get_deref(b"OutputIntents", document)
+        .and_then(Object::as_array)
+    else {
+        return false;
+    };
+    intents.iter().any(|intent| {
+        let Ok((_, intent)) = document.dereference(intent) else {
+            return false;
+        };
+        let Ok(intent) = intent.as_dict() else {
+            return false;
+        };
+        let is_pdfa = intent
+            .get(b"S")
+            .and_then(Object::as_name)
+            .is_ok_and(|name| name == b"GTS_PDFA1");
+        is_pdfa && intent.has(b"DestOutputProfile")
+    })
+}
+
+/// Base names of the fonts whose programs are not in the file. Type 3 fonts
+/// are drawn with PDF operators and Type 0 fonts are checked through their
+/// descendant fonts, which are visited as objects of their own.
+fn unembedded_fonts(document: &Document) -> Vec<String> {
+    let mut fonts = Vec::new();
+    for object in document.objects.values() {
+        let Ok(font) = object.as_dict() else {
+            continue;
+        };
+        if !font.type_is(b"Font") {
+            continue;
+        }
+        let subtype = font.get(b"Subtype").and_then(Object::as_name).unwrap_or_default();
+        if subtype == b"Type3" || subtype == b"Type0" {
+            continue;
+        }
+        let is_embedded = font
+            .get_deref(b"FontDescriptor", document)
+            .and_then(Object::as_dict)
+            .is_ok_and(|descriptor| FONT_FILE_KEYS.iter().any(|key| descriptor.has(key)));
+        if !is_embedded {
+            let name = font
+                .get(b"BaseFont")
+                .and_then(Object::as_name_str)
+                .unwrap_or("unnamed");
+            fonts.push(name.to_string());
+        }
+    }
+    fonts.sort();
+    fonts.dedup();
+    fonts
+}
@@ -1,19 +1,137 @@
+use chrono::Local;
+use log::warn;
 use regex::Regex;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tauri::AppHandle;
 
+use super::catalogue::{
+    record_failure, record_fingerprint, record_preprocessed, record_processed, set_output_status,
+    DOCUMENTS_DIRECTORY_NAME,
+};
+use super::dates::PartialDate;
+use super::fingerprint::{composition_hashes, find_duplicates, page_hashes, text_signature};
+use super::history::{follow_rename, part_document_id, record_rename, start_history};
+use super::identifiers::extract_identifiers;
+use super::models::catalogue::FileStatus;
+use super::models::history::RenameTrigger;
+use super::models::settings::{
+    CollisionPolicy, FileNameSettings, OcrEngineKind, PdfaLevel, PdfaPolicy, SignatureSettings,
+};
+use super::models::signatures::{SignatureReport, SignatureStamp};
+use super::models::search::{IndexedDocument, IndexedPage};
+use super::models::sidecar::{
+    CustomField, DocumentSidecar, SidecarPart, SourceDocument, SIDECAR_SCHEMA_VERSION,
+};
+use super::models::trash::TrashedDocument;
 use super::models::workflows::{
     DocumentProcessStage, DocumentProcessStageError, DocumentProcessStageSuccess,
-    PagePreprocessStage, PagePreprocessStageError, PagePreprocessStageResult,
-    PagePreprocessStageSuccess,
+    DuplicateCandidate, OptimisationReport, PageReference, PagePreprocessStage, PagePreprocessStageError,
+    PagePreprocessStageResult, PagePreprocessStageSuccess, PdfaReport,
+};
+use super::metadata::write_metadata;
+use super::naming::{render_output_file_name, CounterReservation, FieldValue, NamingContext};
+use super::optimise::{compress_object_streams, optimise, select_optimisation_profile};
+use super::ocr::{run_ocr, select_profile, OcrRequest};
+use super::pages::{extract_pages, qpdf_page_arguments, split_document};
+use super::pdfa::verify_pdfa;
+use super::recycle::move_to_trash;
+use super::reveal::reveal_in_file_manager;
+use super::sanitizer::{
+    part_file_name, resolve_destination, sanitise_file_name, signed_original_file_name,
+};
+use super::scope::PathScope;
+use super::search::{index_document, read_page_texts};
+use super::settings::load_settings;
+use super::signatures::{inspect_signatures, load_trust_store, read_signature_reports};
+use super::signing::Signer;
+use super::stamping::{
+    select_stamps, stamp_document, uses_bates, BatesReservation, StampRequest,
 };
-use super::{call_utility, call_utility2};
+use super::sidecar::{move_sidecars, page_range, sidecar_path, write_sidecar};
+use super::utilities::sha256_file;
+use super::workspace::{ProcessStep, ProcessingWorkspace};
+use super::call_utility2;
+use super::utilities::try_call_utility;
 
 #[tauri::command]
 pub async fn run_page_preprocess_stage(
     handle: AppHandle,
     page_preprocess_stage: PagePreprocessStage,
+) -> Result<PagePreprocessStageSuccess, PagePreprocessStageError> {
+    let mut outcome = preprocess_pages(handle.clone(), page_preprocess_stage).await;
+    let recorded = match &mut outcome {
+        Ok(success) => match catalogue_directory(&handle, &success.data_directory) {
+            Ok(directory) => {
+                success.duplicates = find_page_group_duplicates(
+                    &handle,
+                    &directory,
+                    &success.id,
+                    &success.images_directory,
+                    &success.selected_pages,
+                )
+                .await;
+                success.signatures = intact_signatures(&directory);
+                record_preprocessed(
+                    &directory,
+                    &success.id,
+                    &success.selected_pages,
+                    &success.page_preprocess_stage_result,
+                )
+            }
+            Err(e) => Err(e),
+        },
+        Err(error) => catalogue_directory(&handle, &error.data_directory).and_then(|directory| {
+            record_failure(
+                &directory,
+                &error.id,
+                &error.selected_pages,
+                &[],
+                &error.error_message,
+            )
+        }),
+    };
+    if let Err(e) = recorded {
+        warn!("Failed to record the page preprocess stage in the catalogue: {}", e);
+    }
+    outcome
+}
+
+/// Processed documents whose page images look like those of the page group.
+/// Hashes missing from the catalogue are computed first.
+async fn find_page_group_duplicates(
+    handle: &AppHandle,
+    data_directory: &Path,
+    document_id: &str,
+    images_directory: &str,
+    selected_pages: &[u32],
+) -> Vec<DuplicateCandidate> {
+    let hashes =
+        page_hashes(handle, data_directory, Path::new(images_directory), selected_pages).await;
+    match hashes.and_then(|hashes| find_duplicates(handle, document_id, &hashes, &[])) {
+        Ok(duplicates) => duplicates,
+        Err(e) => {
+            warn!("Failed to look for duplicates of {}: {}", document_id, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Signatures of the document in `data_directory` that still check out, as
+/// found by the extraction stage.
+fn intact_signatures(data_directory: &Path) -> Vec<SignatureReport> {
+    match read_signature_reports(data_directory) {
+        Ok(reports) => reports.into_iter().filter(SignatureReport::is_intact).collect(),
+        Err(e) => {
+            warn!("Failed to read the signatures of {}: {}", data_directory.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+async fn preprocess_pages(
+    handle: AppHandle,
+    page_preprocess_stage: PagePreprocessStage,
 ) -> Result<PagePreprocessStageSuccess, PagePreprocessStageError> {
     // Introduce a test error condition
     if page_preprocess_stage.id == "test_error" {
@@ -26,6 +144,16 @@ pub async fn run_page_preprocess_stage(
         });
     }
 
+    let scope = PathScope::current(&handle).map_err(|e| page_preprocess_stage.to_error(e))?;
+    for path in [
+        &page_preprocess_stage.data_directory,
+        &page_preprocess_stage.images_directory,
+    ] {
+        scope
+            .check(path)
+            .map_err(|e| page_preprocess_stage.to_error(e.to_string()))?;
+    }
+
     let page_number_prefix = format!("p-{}", page_preprocess_stage.selected_pages.iter().map(|&x| x.to_string()).collect::<Vec<String>>().join("-"));
     let pages_paths = page_preprocess_stage.get_pages_paths();
     let preprocessed_pages_directory = page_preprocess_stage.get_preprocessed_pages_directory();
@@ -83,7 +211,7 @@ pub async fn run_page_preprocess_stage(
                 })?
                 .as_str();
 
-            let preprocess_result: PagePreprocessStageResult = serde_json::from_str(json_str)
+            let mut preprocess_result: PagePreprocessStageResult = serde_json::from_str(json_str)
                 .map_err(|e| PagePreprocessStageError {
                     id: page_preprocess_stage.id.clone(),
                     data_directory: page_preprocess_stage.data_directory.clone(),
@@ -91,9 +219,20 @@ pub async fn run_page_preprocess_stage(
                     images_directory: page_preprocess_stage.images_directory.clone(),
                     error_message: e.to_string(),
                 })?;
+            preprocess_result.normalise_dates();
 
+            let result_json =
+                serde_json::to_string_pretty(&preprocess_result).map_err(|e| {
+                    PagePreprocessStageError {
+                        id: page_preprocess_stage.id.clone(),
+                        data_directory: page_preprocess_stage.data_directory.clone(),
+                        selected_pages: page_preprocess_stage.selected_pages.clone(),
+                        images_directory: page_preprocess_stage.images_directory.clone(),
+                        error_message: e.to_string(),
+                    }
+                })?;
             let result_file_path = preprocessed_pages_directory.join("result.json");
-            fs::write(&result_file_path, json_str).map_err(|e| PagePreprocessStageError {
+            fs::write(&result_file_path, result_json).map_err(|e| PagePreprocessStageError {
                 id: page_preprocess_stage.id.clone(),
                 data_directory: page_preprocess_stage.data_directory.clone(),
                 selected_pages: page_preprocess_stage.selected_pages.clone(),
@@ -108,6 +247,8 @@ pub async fn run_page_preprocess_stage(
                 images_directory: page_preprocess_stage.images_directory,
                 page_preprocess_stage_result: preprocess_result,
                 page_number_prefix,
+                duplicates: Vec::new(),
+                signatures: Vec::new(),
             })
         }
 
@@ -121,10 +262,39 @@ pub async fn run_page_preprocess_stage(
     }
 }
 
+/// The data directory of a stage, if the app may write to it.
+fn catalogue_directory(handle: &AppHandle, data_directory: &str) -> Result<PathBuf, String> {
+    Ok(PathScope::current(handle)?.check(data_directory)?)
+}
+
+/// Successful runs are recorded in the catalogue by `process_document`, which
+/// has the hashes at hand; failures are recorded here.
 #[tauri::command]
 pub async fn run_document_process_stage(
     handle: AppHandle,
     document_process_stage: DocumentProcessStage,
+) -> Result<DocumentProcessStageSuccess, DocumentProcessStageError> {
+    let outcome = process_document(handle.clone(), document_process_stage).await;
+    if let Err(error) = &outcome {
+        let recorded = catalogue_directory(&handle, &error.data_directory).and_then(|directory| {
+            record_failure(
+                &directory,
+                &error.id,
+                &error.selected_pages,
+                &error.source_pages,
+                &error.error_message,
+            )
+        });
+        if let Err(e) = recorded {
+            warn!("Failed to record the document process stage in the catalogue: {}", e);
+        }
+    }
+    outcome
+}
+
+async fn process_document(
+    handle: AppHandle,
+    document_process_stage: DocumentProcessStage,
 ) -> Result<DocumentProcessStageSuccess, DocumentProcessStageError> {
     if document_process_stage.id == "test_error" {
         return Err(DocumentProcessStageError {
@@ -137,26 +307,48 @@ pub async fn run_document_process_stage(
             file_name: document_process_stage.file_name,
             error_message: "Forced error for testing".to_string(),
             page_number_prefix: document_process_stage.page_number_prefix,
+            source_pages: document_process_stage.source_pages,
         });
     }
 
+    let processing_started_at = Local::now().to_rfc3339();
+    let scope = PathScope::current(&handle).map_err(|e| document_process_stage.to_error(e))?;
+    for path in [
+        &document_process_stage.data_directory,
+        &document_process_stage.images_directory,
+        &document_process_stage.document_path,
+    ] {
+        scope
+            .check(path)
+            .map_err(|e| document_process_stage.to_error(e.to_string()))?;
+    }
 
-    let file_name = document_process_stage
+    let mut document_process_stage = document_process_stage;
+    document_process_stage
         .page_preprocess_stage_result
-        .suggested_file_name
-        .clone();
+        .normalise_dates();
 
-    let file_name = format!("{}-{}.pdf", document_process_stage.page_number_prefix, file_name);
-    let input_path = document_process_stage.document_path.clone();
-    let data_directory = document_process_stage.data_directory.clone();
-    let pages_to_process = document_process_stage
-        .selected_pages
+    let page_references = document_process_stage.page_references();
+    if let Some(reference) = page_references
         .iter()
-        .map(|page| page.to_string())
-        .collect::<Vec<String>>()
-        .join(",");
+        .find(|reference| reference.rotation % 90 != 0)
+    {
+        return Err(document_process_stage.to_error(format!(
+            "Rotation must be a multiple of 90 degrees, got {} for page {} of {}",
+            reference.rotation, reference.page_number, reference.document_path
+        )));
+    }
+    for reference in &page_references {
+        if reference.document_path != document_process_stage.document_path {
+            scope
+                .check(&reference.document_path)
+                .map_err(|e| document_process_stage.to_error(e.to_string()))?;
+        }
+    }
+
+    let data_directory = document_process_stage.data_directory.clone();
 
-    let output_dir = Path::new(&data_directory).join("documents");
+    let output_dir = Path::new(&data_directory).join(DOCUMENTS_DIRECTORY_NAME);
     if !output_dir.exists() {
         fs::create_dir_all(&output_dir).map_err(|e| DocumentProcessStageError {
             id: document_process_stage.id.clone(),
@@ -168,119 +360,799 @@ pub async fn run_document_process_stage(
                 .clone(),
             document_path: document_process_stage.document_path.clone(),
             file_name: document_process_stage.file_name.clone(),
-            error_message: format!("Failed to create output directory: {}", e),
+            error_message: ProcessStep::Prepare
+                .fail(format!("Failed to create output directory: {}", e)),
             page_number_prefix: document_process_stage.page_number_prefix.clone(),
+            source_pages: page_references.clone(),
         })?;
     }
 
-    let output_path = output_dir
-        .join(&file_name)
-        .with_extension("pdf")
-        .display()
-        .to_string();
-
-    // QPDF utility call
-    let is_success = call_utility(
-        handle.clone(),
-        "qpdf".to_owned(),
-        vec![
-            "--empty".to_owned(),
-            "--pages".to_owned(),
-            input_path,
-            pages_to_process,
-            "--".to_owned(),
-            output_path.clone(),
-        ],
-        false,
+    let settings = load_settings(&handle)
+        .map_err(|e| document_process_stage.to_error(ProcessStep::Prepare.fail(e)))?;
+    let profile = select_profile(
+        &settings.ocr,
+        document_process_stage.ocr_profile.as_deref(),
+    )
+    .map_err(|e| document_process_stage.to_error(ProcessStep::Prepare.fail(e)))?;
+    let optimisation_profile = select_optimisation_profile(
+        &settings.optimisation,
+        document_process_stage.optimisation_profile.as_deref(),
     )
-    .await;
+    .map_err(|e| document_process_stage.to_error(ProcessStep::Prepare.fail(e)))?;
+    if optimisation_profile.is_none() && document_process_stage.target_max_bytes.is_some() {
+        return Err(document_process_stage.to_error(
+            ProcessStep::Prepare.fail("A size target needs an optimisation profile"),
+        ));
+    }
+    // Every step rewrites the file, so no source signature survives.
+    let signed_sources = signed_sources(&settings.signatures, &page_references);
+    let keep_signed_original = document_process_stage
+        .keep_signed_original
+        .unwrap_or(settings.signatures.keep_signed_original);
+    // Opened before any work, so a wrong password fails the stage at once.
+    let signer = match &document_process_stage.signing {
+        Some(options) => Some(
+            Signer::load(&settings.signatures, options)
+                .map_err(|e| document_process_stage.to_error(ProcessStep::Sign.fail(e)))?,
+        ),
+        None => None,
+    };
+    let stamps = select_stamps(&settings.stamping, &document_process_stage.stamps)
+        .map_err(|e| document_process_stage.to_error(ProcessStep::Prepare.fail(e)))?;
+    // Every intermediate file lives in the workspace until all steps have
+    // succeeded; dropping it on any early return removes them.
+    let workspace = ProcessingWorkspace::new(Path::new(&data_directory))
+        .map_err(|e| document_process_stage.to_error(ProcessStep::Prepare.fail(e)))?;
+    let extracted_path = workspace.path("extracted.pdf");
+    let ocr_path = workspace.path("ocr.pdf");
+    let ocr_text_path = workspace.path("ocr.txt");
 
-    if !is_success {
-        return Err(DocumentProcessStageError {
-            id: document_process_stage.id,
-            selected_pages: document_process_stage.selected_pages,
-            data_directory: document_process_stage.data_directory,
-            images_directory: document_process_stage.images_directory,
-            page_preprocess_stage_result: document_process_stage.page_preprocess_stage_result,
-            document_path: document_process_stage.document_path,
-            file_name: document_process_stage.file_name,
-            error_message: "Failed to call QPDF utility".to_string(),
-            page_number_prefix: document_process_stage.page_number_prefix,
-        });
+    if let Err(e) = extract_pages(&page_references, &extracted_path) {
+        if !settings.pdf.qpdf_fallback {
+            return Err(document_process_stage.to_error(ProcessStep::ExtractPages.fail(e)));
+        }
+        warn!("Native page extraction failed, falling back to QPDF: {}", e);
+
+        // QPDF utility call
+        if let Err(qpdf_error) = try_call_utility(
+            handle.clone(),
+            "qpdf".to_owned(),
+            qpdf_page_arguments(&page_references, &extracted_path),
+            false,
+        )
+        .await
+        {
+            return Err(document_process_stage.to_error(ProcessStep::ExtractPages.fail(
+                format!("{}; QPDF fallback failed as well: {}", e, qpdf_error),
+            )));
+        }
+    }
+
+    let request = OcrRequest {
+        input: &extracted_path,
+        output: &ocr_path,
+        text_output: Some(&ocr_text_path),
+    };
+    if let Err(e) = run_ocr(&handle, profile, request).await {
+        return Err(document_process_stage.to_error(ProcessStep::Ocr.fail(e)));
     }
+    let ocr_text = fs::read_to_string(&ocr_text_path).ok();
+    let page_texts = read_page_texts(&ocr_path, ocr_text.as_deref());
+    let text_signature = text_signature(&page_texts);
+    document_process_stage.identifiers = extract_identifiers(&page_texts);
 
+    // Named once the text is known, so templates can use the identifiers.
+    let (file_name, counter) =
+        render_output_file_name(&handle, &document_process_stage).map_err(|e| {
+            document_process_stage.to_error(
+                ProcessStep::Prepare.fail(format!("Failed to render file name: {}", e)),
+            )
+        })?;
+    let output_path = resolve_destination(&output_dir, &file_name, None, &settings.file_names)
+        .map_err(|e| {
+            document_process_stage.to_error(
+                ProcessStep::Prepare.fail(format!("Failed to resolve output path: {}", e)),
+            )
+        })?;
+    let file_name = output_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or(file_name);
 
-    // OCRmyPDF utility call
-    let is_success = call_utility(
-        handle.clone(),
-        "ocrmypdf.exe".to_owned(),
-        vec![
-            "--force-ocr".to_owned(),
-            "--pdf-renderer".to_owned(),
-            "hocr".to_owned(),
-            "--color-conversion-strategy".to_owned(),
-            "UseDeviceIndependentColor".to_owned(),
-            "-l".to_owned(),
-            "por".to_owned(),
-            "--clean".to_owned(),
-            "--output-type".to_owned(),
-            "pdfa-2".to_owned(),
-            output_path.clone(),
-            output_path.clone(),
-        ],
-        false,
+    let mut current_path = ocr_path.clone();
+    let mut optimisation = None;
+    if let Some(optimisation_profile) = optimisation_profile {
+        let target_max_bytes = document_process_stage
+            .target_max_bytes
+            .or(optimisation_profile.target_max_bytes);
+        let outcome = optimise(
+            &handle,
+            optimisation_profile,
+            profile,
+            target_max_bytes,
+            &ocr_path,
+            &workspace,
+        )
+        .await
+        .map_err(|e| document_process_stage.to_error(ProcessStep::Optimise.fail(e)))?;
+        current_path = outcome.path.clone();
+        optimisation = Some((optimisation_profile, target_max_bytes, outcome));
+    }
+
+    // Stamped before splitting, so Bates numbers run across the parts. The
+    // numbers go back to the counter if the stage fails later on.
+    let mut bates = None;
+    if !stamps.is_empty() {
+        let numbered = uses_bates(&stamps)
+            .map_err(|e| document_process_stage.to_error(ProcessStep::Stamp.fail(e)))?;
+        if numbered {
+            let reservation = BatesReservation::take(
+                &handle,
+                &settings.stamping.bates,
+                document_process_stage.bates_prefix.as_deref(),
+                page_references.len() as u64,
+            )
+            .map_err(|e| document_process_stage.to_error(ProcessStep::Stamp.fail(e)))?;
+            bates = Some(reservation);
+        }
+        // The counter the file name was rendered with.
+        let number = counter.as_ref().map_or_else(
+            || {
+                settings
+                    .naming
+                    .presets
+                    .iter()
+                    .find(|preset| preset.name == settings.naming.active_preset)
+                    .map_or(0, |preset| preset.next_counter)
+            },
+            CounterReservation::number,
+        );
+        let mut context = NamingContext::from_stage(&document_process_stage, number);
+        context.insert("file_name", FieldValue::Text(file_name.clone()));
+        let stamped_path = workspace.path("stamped.pdf");
+        let request = StampRequest {
+            input: &current_path,
+            output: &stamped_path,
+            scratch: &workspace.path("stamp.pam"),
+        };
+        stamp_document(
+            &handle,
+            &stamps,
+            &context,
+            bates.as_ref(),
+            profile.engine == OcrEngineKind::OcrMyPdf && profile.pdfa_level == PdfaLevel::Pdfa1,
+            request,
+        )
+        .await
+        .map_err(|e| document_process_stage.to_error(ProcessStep::Stamp.fail(e)))?;
+        current_path = stamped_path;
+    }
+
+    write_metadata(
+        &current_path,
+        &document_process_stage.id,
+        &document_process_stage.page_preprocess_stage_result,
     )
-    .await;
+    .map_err(|e| document_process_stage.to_error(ProcessStep::WriteMetadata.fail(e)))?;
 
-    if !is_success {
-        return Err(DocumentProcessStageError {
-            id: document_process_stage.id,
-            selected_pages: document_process_stage.selected_pages,
-            data_directory: document_process_stage.data_directory,
-            images_directory: document_process_stage.images_directory,
-            page_preprocess_stage_result: document_process_stage.page_preprocess_stage_result,
-            document_path: document_process_stage.document_path,
-            file_name: document_process_stage.file_name,
-            error_message: "Failed to call OCRmyPDF utility".to_string(),
-            page_number_prefix: document_process_stage.page_number_prefix,
-        });
+    // Each output is a workspace file with its page range in the document.
+    let page_count = page_references.len() as u32;
+    let mut outputs = vec![(current_path.clone(), 1, page_count)];
+    if let Some(split) = document_process_stage
+        .split
+        .as_ref()
+        .filter(|split| split.max_bytes.is_some() || split.max_pages.is_some())
+    {
+        let parts = split_document(&current_path, split.max_pages, split.max_bytes)
+            .map_err(|e| document_process_stage.to_error(ProcessStep::Split.fail(e)))?;
+        if parts.len() > 1 {
+            outputs.clear();
+            for (index, part) in parts.into_iter().enumerate() {
+                let part_path = workspace.path(&format!("part-{}.pdf", index + 1));
+                fs::write(&part_path, &part.content).map_err(|e| {
+                    document_process_stage.to_error(
+                        ProcessStep::Split.fail(format!("Failed to write part {}: {}", index + 1, e)),
+                    )
+                })?;
+                outputs.push((part_path, part.first_page, part.last_page));
+            }
+        }
+    }
+
+    // Object streams are written last because saving with lopdf drops them.
+    if optimisation_profile.is_some_and(|optimisation_profile| optimisation_profile.object_streams) {
+        for (index, (output, _, _)) in outputs.iter_mut().enumerate() {
+            let compact_path = workspace.path(&format!("compact-{}.pdf", index + 1));
+            match compress_object_streams(&handle, profile, output, &compact_path).await {
+                Ok(()) => *output = compact_path,
+                Err(e) => {
+                    warn!("Skipping object stream compression: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    let pdfa_report = if profile.engine == OcrEngineKind::OcrMyPdf
+        && profile.pdfa_level != PdfaLevel::None
+        && settings.pdf.pdfa_policy != PdfaPolicy::Ignore
+    {
+        let report = verify_outputs(&outputs, profile.pdfa_level)
+            .map_err(|e| document_process_stage.to_error(ProcessStep::VerifyPdfa.fail(e)))?;
+        if !report.conforms {
+            if settings.pdf.pdfa_policy == PdfaPolicy::Fail {
+                return Err(document_process_stage.to_error(
+                    ProcessStep::VerifyPdfa.fail(report.issues.join("; ")),
+                ));
+            }
+            warn!(
+                "{} does not conform to PDF/A: {}",
+                file_name,
+                report.issues.join("; ")
+            );
+        }
+        Some(report)
+    } else {
+        None
+    };
+
+    // Signed last, as any later rewrite would break the signature; the
+    // incremental update keeps the PDF/A conformance checked above.
+    let mut signing_reports = Vec::new();
+    if let (Some(signer), Some(options)) = (&signer, &document_process_stage.signing) {
+        for (index, (output, first_page, last_page)) in outputs.iter_mut().enumerate() {
+            let stamp = options
+                .stamp
+                .as_ref()
+                .filter(|stamp| (*first_page..=*last_page).contains(&stamp.page_number))
+                .map(|stamp| SignatureStamp {
+                    page_number: stamp.page_number - *first_page + 1,
+                    ..stamp.clone()
+                });
+            let signed_path = workspace.path(&format!("signed-{}.pdf", index + 1));
+            let report = signer
+                .sign(
+                    &handle,
+                    output,
+                    &signed_path,
+                    stamp.as_ref(),
+                    &workspace.path("stamp.gray"),
+                )
+                .await
+                .map_err(|e| document_process_stage.to_error(ProcessStep::Sign.fail(e)))?;
+            *output = signed_path;
+            signing_reports.push(report);
+        }
     }
 
+    let optimisation_report = match optimisation {
+        Some((optimisation_profile, target_max_bytes, outcome)) => {
+            let optimised_bytes = outputs
+                .iter()
+                .map(|(output, _, _)| fs::metadata(output).map(|metadata| metadata.len()))
+                .sum::<Result<u64, _>>()
+                .unwrap_or(outcome.optimised_bytes);
+            Some(OptimisationReport {
+                profile: optimisation_profile.name.clone(),
+                original_bytes: outcome.original_bytes,
+                optimised_bytes,
+                target_max_bytes,
+                attempts: outcome.attempts,
+                target_met: target_max_bytes.map_or(true, |target| optimised_bytes <= target),
+            })
+        }
+        None => None,
+    };
+
+    // Resolved again now that the slow steps are done, in case a file took
+    // the name meanwhile.
+    let destinations = if outputs.len() == 1 {
+        vec![resolve_destination(&output_dir, &file_name, None, &settings.file_names)
+            .map_err(|e| {
+                document_process_stage.to_error(
+                    ProcessStep::Finalise.fail(format!("Failed to resolve output path: {}", e)),
+                )
+            })?]
+    } else {
+        (1..=outputs.len())
+            .map(|part| {
+                resolve_destination(
+                    &output_dir,
+                    &part_file_name(&file_name, part),
+                    None,
+                    &settings.file_names,
+                )
+            })
+            .collect::<Result<Vec<PathBuf>, String>>()
+            .map_err(|e| {
+                document_process_stage.to_error(
+                    ProcessStep::Split.fail(format!("Failed to resolve part path: {}", e)),
+                )
+            })?
+    };
+    // Copied byte for byte, so their signatures stay valid.
+    let mut signed_originals = Vec::new();
+    if keep_signed_original {
+        for (index, (source, _)) in signed_sources.iter().enumerate() {
+            let copy = workspace.path(&format!("original-{}.pdf", index + 1));
+            fs::copy(source, &copy).map_err(|e| {
+                document_process_stage.to_error(
+                    ProcessStep::Finalise.fail(format!("Failed to copy {}: {}", source, e)),
+                )
+            })?;
+            let destination = resolve_destination(
+                &output_dir,
+                &signed_original_file_name(&file_name, index + 1),
+                None,
+                &settings.file_names,
+            )
+            .map_err(|e| {
+                document_process_stage.to_error(ProcessStep::Finalise.fail(format!(
+                    "Failed to resolve the path of the signed original: {}",
+                    e
+                )))
+            })?;
+            signed_originals.push((copy, destination));
+        }
+    }
+    let file_name = destinations[0]
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or(file_name);
 
+    let source_documents = describe_sources(&page_references)
+        .map_err(|e| document_process_stage.to_error(ProcessStep::Finalise.fail(e)))?;
+    let parts = describe_outputs(&outputs, &destinations, &page_references)
+        .map_err(|e| document_process_stage.to_error(ProcessStep::Finalise.fail(e)))?;
 
+    let sidecar = if settings.sidecar.enabled {
+        let sidecar = build_sidecar(
+            &document_process_stage,
+            &page_references,
+            &source_documents,
+            &parts,
+            &profile.name,
+            &processing_started_at,
+        );
+        let sidecar_file = workspace.path("sidecar");
+        write_sidecar(&sidecar_file, &sidecar, settings.sidecar.format)
+            .map_err(|e| document_process_stage.to_error(ProcessStep::ExportSidecar.fail(e)))?;
+        Some((sidecar_file, sidecar_path(&destinations[0], settings.sidecar.format)))
+    } else {
+        None
+    };
 
-    Ok(DocumentProcessStageSuccess {
+    if let Some((sidecar_file, destination)) = &sidecar {
+        workspace
+            .commit(sidecar_file, destination, true)
+            .map_err(|e| document_process_stage.to_error(ProcessStep::Finalise.fail(e)))?;
+    }
+    let overwrite = settings.file_names.collision_policy == CollisionPolicy::Overwrite;
+    let files: Vec<(&PathBuf, &PathBuf)> = outputs
+        .iter()
+        .map(|(output, _, _)| output)
+        .zip(&destinations)
+        .chain(signed_originals.iter().map(|(copy, destination)| (copy, destination)))
+        .collect();
+    for (index, (file, destination)) in files.iter().enumerate() {
+        if let Err(e) = workspace.commit(file, destination, overwrite) {
+            // Leave nothing of a half-committed document behind.
+            for (_, committed) in &files[..index] {
+                let _ = fs::remove_file(committed);
+            }
+            if let Some((_, destination)) = &sidecar {
+                let _ = fs::remove_file(destination);
+            }
+            return Err(document_process_stage.to_error(ProcessStep::Finalise.fail(e)));
+        }
+    }
+    drop(workspace);
+    if let Some(counter) = counter {
+        counter.keep();
+    }
+    let bates_range = bates.map(|bates| {
+        let range = bates.range();
+        bates.keep();
+        range
+    });
+    let signed_original_paths = signed_originals
+        .iter()
+        .map(|(_, destination)| destination.display().to_string())
+        .collect::<Vec<String>>();
+    let part_paths = destinations
+        .iter()
+        .map(|destination| destination.display().to_string())
+        .collect::<Vec<String>>();
+    for (index, part_path) in part_paths.iter().enumerate() {
+        let document_id = part_document_id(&document_process_stage.id, index + 1);
+        if let Err(e) = start_history(&handle, &document_id, part_path) {
+            warn!("Failed to start rename history of {}: {}", part_path, e);
+        }
+    }
+    let indexed_document = build_indexed_document(
+        &document_process_stage,
+        &page_references,
+        &outputs,
+        &part_paths,
+        page_texts,
+    );
+    if let Err(e) = index_document(&handle, indexed_document) {
+        warn!("Failed to add {} to the search index: {}", file_name, e);
+    }
+
+    let data_directory = PathBuf::from(&document_process_stage.data_directory);
+    let page_hashes = composition_hashes(
+        &handle,
+        &data_directory,
+        Path::new(&document_process_stage.images_directory),
+        &document_process_stage.document_path,
+        &page_references,
+    )
+    .await
+    .unwrap_or_else(|e| {
+        warn!("Failed to hash the pages of {}: {}", file_name, e);
+        Vec::new()
+    });
+    let duplicates = find_duplicates(
+        &handle,
+        &document_process_stage.id,
+        &page_hashes,
+        &text_signature,
+    )
+    .unwrap_or_else(|e| {
+        warn!("Failed to look for duplicates of {}: {}", file_name, e);
+        Vec::new()
+    });
+
+    let success = DocumentProcessStageSuccess {
         id: document_process_stage.id,
         selected_pages: document_process_stage.selected_pages,
         data_directory: document_process_stage.data_directory,
         images_directory: document_process_stage.images_directory,
         page_preprocess_stage_result: document_process_stage.page_preprocess_stage_result,
-        document_path: output_path,
         file_name,
         page_number_prefix: document_process_stage.page_number_prefix,
-    })
+        source_pages: page_references,
+        pdfa_report,
+        optimisation_report,
+        part_paths,
+        duplicates,
+        identifiers: document_process_stage.identifiers,
+        invalidated_signatures: signed_sources
+            .into_iter()
+            .flat_map(|(_, reports)| reports)
+            .collect(),
+        signed_original_paths,
+        signing_reports,
+        bates_range,
+    };
+    let recorded = record_processed(&data_directory, &success, &source_documents, &parts)
+        .and_then(|_| {
+            record_fingerprint(
+                &handle,
+                &data_directory,
+                &success.id,
+                &page_hashes,
+                &text_signature,
+            )
+        });
+    if let Err(e) = recorded {
+        warn!("Failed to record {} in the catalogue: {}", success.file_name, e);
+    }
+    Ok(success)
+}
+
+/// Sources of the composition with intact signatures, each with them. Trust
+/// does not matter here: any signature that checks out is lost on output.
+fn signed_sources(
+    settings: &SignatureSettings,
+    page_references: &[PageReference],
+) -> Vec<(String, Vec<SignatureReport>)> {
+    let trust_store = load_trust_store(settings).unwrap_or_else(|e| {
+        warn!("Checking signatures without a trust store: {}", e);
+        Vec::new()
+    });
+    let mut signed_sources: Vec<(String, Vec<SignatureReport>)> = Vec::new();
+    let mut checked: Vec<&str> = Vec::new();
+    for reference in page_references {
+        if checked.contains(&reference.document_path.as_str()) {
+            continue;
+        }
+        checked.push(&reference.document_path);
+        match inspect_signatures(Path::new(&reference.document_path), &trust_store) {
+            Ok(reports) => {
+                let intact: Vec<SignatureReport> =
+                    reports.into_iter().filter(SignatureReport::is_intact).collect();
+                if !intact.is_empty() {
+                    warn!(
+                        "Processing invalidates {} signature(s) of {}",
+                        intact.len(),
+                        reference.document_path
+                    );
+                    signed_sources.push((reference.document_path.clone(), intact));
+                }
+            }
+            Err(e) => warn!(
+                "Failed to check the signatures of {}: {}",
+                reference.document_path, e
+            ),
+        }
+    }
+    signed_sources
+}
+
+/// The search index entry for the committed outputs. Page numbers are counted
+/// within the part holding the page.
+fn build_indexed_document(
+    document_process_stage: &DocumentProcessStage,
+    page_references: &[PageReference],
+    outputs: &[(PathBuf, u32, u32)],
+    part_paths: &[String],
+    page_texts: Vec<String>,
+) -> IndexedDocument {
+    let mut source_documents: Vec<String> = Vec::new();
+    for reference in page_references {
+        if !source_documents.contains(&reference.document_path) {
+            source_documents.push(reference.document_path.clone());
+        }
+    }
+    let pages = page_texts
+        .into_iter()
+        .zip(1..)
+        .filter_map(|(text, page_number)| {
+            outputs
+                .iter()
+                .zip(part_paths)
+                .find(|((_, first_page, last_page), _)| {
+                    (*first_page..=*last_page).contains(&page_number)
+                })
+                .map(|((_, first_page, _), part_path)| IndexedPage {
+                    document_path: part_path.clone(),
+                    page_number: page_number - first_page + 1,
+                    text,
+                })
+        })
+        .collect();
+    let result = &document_process_stage.page_preprocess_stage_result;
+
+    IndexedDocument {
+        document_id: document_process_stage.id.clone(),
+        part_paths: part_paths.to_vec(),
+        source_documents,
+        type_name: result.type_name.clone(),
+        type_abbr: result.type_abbr.clone(),
+        summary: result.summary.clone(),
+        dates: result.dates.clone(),
+        indexed_at: Local::now().to_rfc3339(),
+        pages,
+    }
+}
+
+/// Checks every output file; issues of a split document name their part.
+fn verify_outputs(outputs: &[(PathBuf, u32, u32)], level: PdfaLevel) -> Result<PdfaReport, String> {
+    let mut combined: Option<PdfaReport> = None;
+    for (index, (output, _, _)) in outputs.iter().enumerate() {
+        let mut report = verify_pdfa(output, level)?;
+        if outputs.len() > 1 {
+            report.issues = report
+                .issues
+                .into_iter()
+                .map(|issue| format!("Part {}: {}", index + 1, issue))
+                .collect();
+        }
+        match combined.as_mut() {
+            Some(combined) => {
+                combined.conforms &= report.conforms;
+                combined.issues.extend(report.issues);
+            }
+            None => combined = Some(report),
+        }
+    }
+    combined.ok_or_else(|| "No output to verify".to_string())
+}
+
+/// The distinct source documents of `page_references` with their hashes.
+fn describe_sources(page_references: &[PageReference]) -> Result<Vec<SourceDocument>, String> {
+    let mut source_documents: Vec<SourceDocument> = Vec::new();
+    for reference in page_references {
+        if source_documents
+            .iter()
+            .all(|source| source.path != reference.document_path)
+        {
+            source_documents.push(SourceDocument {
+                path: reference.document_path.clone(),
+                sha256: sha256_file(Path::new(&reference.document_path))?,
+            });
+        }
+    }
+    Ok(source_documents)
+}
+
+/// Names, page ranges, hashes and sizes of the output files about to be
+/// committed to `destinations`.
+fn describe_outputs(
+    outputs: &[(PathBuf, u32, u32)],
+    destinations: &[PathBuf],
+    page_references: &[PageReference],
+) -> Result<Vec<SidecarPart>, String> {
+    let mut parts = Vec::with_capacity(outputs.len());
+    for ((output, first_page, last_page), destination) in outputs.iter().zip(destinations) {
+        parts.push(SidecarPart {
+            file_name: destination
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            page_range: page_range(&page_references[*first_page as usize - 1..*last_page as usize]),
+            sha256: sha256_file(output)?,
+            size: fs::metadata(output)
+                .map_err(|e| format!("Failed to read {}: {}", output.display(), e))?
+                .len(),
+        });
+    }
+    Ok(parts)
 }
 
+/// Describes the processed document for the DMS. `parts` is never empty.
+fn build_sidecar(
+    document_process_stage: &DocumentProcessStage,
+    page_references: &[PageReference],
+    source_documents: &[SourceDocument],
+    parts: &[SidecarPart],
+    ocr_profile: &str,
+    processing_started_at: &str,
+) -> DocumentSidecar {
+    let result = &document_process_stage.page_preprocess_stage_result;
 
+    DocumentSidecar {
+        schema_version: SIDECAR_SCHEMA_VERSION,
+        document_id: document_process_stage.id.clone(),
+        file_name: parts[0].file_name.clone(),
+        page_count: page_references.len(),
+        page_range: page_range(page_references),
+        pages: page_references.to_vec(),
+        source_documents: source_documents.to_vec(),
+        output_sha256: parts[0].sha256.clone(),
+        output_size: parts[0].size,
+        ocr_profile: ocr_profile.to_string(),
+        processing_started_at: processing_started_at.to_string(),
+        processing_finished_at: Local::now().to_rfc3339(),
+        type_name: result.type_name.clone(),
+        type_abbr: result.type_abbr.clone(),
+        summary: result.summary.clone(),
+        suggested_file_name: result.suggested_file_name.clone(),
+        dates: result.dates.clone(),
+        custom_fields: result
+            .custom_fields
+            .iter()
+            .map(|(name, value)| CustomField {
+                name: name.clone(),
+                value: value.clone(),
+            })
+            .collect(),
+        parts: parts.to_vec(),
+        identifiers: document_process_stage.identifiers.clone(),
+    }
+}
+
+/// Renames the output at `path` to `file_name`, or to the next free variant
+/// of it, along with its sidecars. Returns the new path.
+fn rename_output(
+    path: &Path,
+    file_name: &str,
+    settings: &FileNameSettings,
+) -> Result<PathBuf, String> {
+    let directory = path
+        .parent()
+        .ok_or_else(|| format!("Invalid document path: {}", path.display()))?;
+    let new_path = resolve_destination(directory, file_name, Some(path), settings)?;
+    if new_path != path {
+        fs::rename(path, &new_path).map_err(|e| e.to_string())?;
+        move_sidecars(path, &new_path);
+    }
+    Ok(new_path)
+}
+
+/// Renames every part of a processed document: a single part to
+/// `new_file_name`, the parts of a split document to its numbered variants.
+/// When a part cannot be renamed, the parts already renamed are put back.
 #[tauri::command]
-pub fn run_update_file_name(new_file_name: String, document_path: String) -> Result<String, String> {
-    let document_path = Path::new(&document_path);
-    let new_file_name = document_path
-        .with_file_name(new_file_name)
-        .with_extension("pdf");
-    fs::rename(&document_path, &new_file_name).map_err(|e| e.to_string())?;
-    Ok(new_file_name.display().to_string())
+pub fn run_update_file_name(
+    handle: AppHandle,
+    new_file_name: String,
+    part_paths: Vec<String>,
+    document_id: Option<String>,
+) -> Result<Vec<String>, String> {
+    let settings = load_settings(&handle)?;
+    let scope = PathScope::current(&handle)?;
+    let part_paths = part_paths
+        .iter()
+        .map(|path| scope.check(path))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    let new_file_name = sanitise_file_name(&new_file_name, &settings.file_names)?;
+    let mut renamed: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for (index, part_path) in part_paths.iter().enumerate() {
+        let file_name = if part_paths.len() == 1 {
+            new_file_name.clone()
+        } else {
+            part_file_name(&new_file_name, index + 1)
+        };
+        match rename_output(part_path, &file_name, &settings.file_names) {
+            Ok(new_path) => renamed.push((part_path.clone(), new_path)),
+            Err(e) => {
+                for (old_path, new_path) in renamed.iter().rev() {
+                    if new_path == old_path {
+                        continue;
+                    }
+                    match fs::rename(new_path, old_path) {
+                        Ok(()) => move_sidecars(new_path, old_path),
+                        Err(e) => warn!("Failed to rename {} back: {}", new_path.display(), e),
+                    }
+                }
+                return Err(format!("Failed to rename {}: {}", part_path.display(), e));
+            }
+        }
+    }
+    for (index, (old_path, new_path)) in renamed.iter().enumerate() {
+        if new_path == old_path {
+            continue;
+        }
+        let part_id = document_id
+            .as_deref()
+            .map(|document_id| part_document_id(document_id, index + 1));
+        if let Err(e) = record_rename(
+            &handle,
+            part_id.as_deref(),
+            &old_path.display().to_string(),
+            &new_path.display().to_string(),
+            RenameTrigger::User,
+        ) {
+            warn!("Failed to record rename history: {}", e);
+        }
+        follow_rename(
+            &handle,
+            &old_path.display().to_string(),
+            new_path.display().to_string(),
+        );
+    }
+    Ok(renamed
+        .into_iter()
+        .map(|(_, new_path)| new_path.display().to_string())
+        .collect())
+}
+
+#[tauri::command]
+pub fn normalise_date(date: String) -> Result<String, String> {
+    PartialDate::parse(&date).map(|date| date.to_string())
 }
 
 #[tauri::command]
-pub fn open_in_explorer(path: &str) -> Result<(), String> {
-    let mut command = std::process::Command::new("explorer");
-    command.args(&["/select,", path]);
-    command.spawn().map_err(|_| "Failed to open in explorer")?;
-    Ok(())
+pub fn open_in_explorer(handle: AppHandle, path: &str) -> Result<(), String> {
+    let path = PathScope::current(&handle)?.check(path)?;
+    reveal_in_file_manager(&path)
 }
 
+/// Moves every part of a processed document to the trash.
 #[tauri::command]
-pub fn delete_processed_document(file_path: String) -> Result<(), String> {
-    fs::remove_file(file_path).map_err(|e| format!("Failed to delete file: {}", e))
+pub fn delete_processed_document(
+    handle: AppHandle,
+    part_paths: Vec<String>,
+    data_directory: String,
+) -> Result<Vec<TrashedDocument>, String> {
+    let scope = PathScope::current(&handle)?;
+    let part_paths = part_paths
+        .iter()
+        .map(|path| scope.check(path))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    let data_directory = scope.check(&data_directory)?;
+    let mut trashed_parts = Vec::new();
+    for part_path in &part_paths {
+        let trashed = move_to_trash(&handle, part_path, &data_directory)?;
+        if let Err(e) =
+            set_output_status(&data_directory, &trashed.original_path, FileStatus::Deleted)
+        {
+            warn!("Failed to record the deletion in the catalogue: {}", e);
+        }
+        trashed_parts.push(trashed);
+    }
+    Ok(trashed_parts)
 }
\ No newline at end of file
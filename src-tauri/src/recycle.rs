@@ -0,0 +1,332 @@
+use lazy_static::lazy_static;
+use log::{debug, error, warn};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tauri::AppHandle;
+use uuid::Uuid;
+
+use super::catalogue::{set_output_status, DOCUMENTS_DIRECTORY_NAME};
+use super::history::record_move;
+use super::models::catalogue::FileStatus;
+use super::models::history::RenameTrigger;
+use super::models::settings::TrashSettings;
+use super::models::trash::{TrashIndex, TrashLocation, TrashedDocument};
+use super::scope::{canonicalize, PathScope};
+use super::search::{forget_indexed_documents, restore_indexed_document, trash_indexed_document};
+use super::settings::load_settings;
+use super::sidecar::{move_sidecars, sidecar_paths};
+use super::utilities::unix_timestamp;
+
+const TRASH_DIRECTORY_NAME: &str = ".trash";
+const TRASH_INDEX_FILE_NAME: &str = "index.json";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+lazy_static! {
+    static ref TRASH_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn trash_directory(data_directory: &Path) -> PathBuf {
+    data_directory.join(TRASH_DIRECTORY_NAME)
+}
+
+fn load_index(trash_directory: &Path) -> Result<TrashIndex, String> {
+    let path = trash_directory.join(TRASH_INDEX_FILE_NAME);
+    if !path.exists() {
+        return Ok(TrashIndex::default());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| {
+        error!("Failed to read trash index: {}", e);
+        format!("Failed to read trash index: {}", e)
+    })?;
+    serde_json::from_str(&content).map_err(|e| {
+        error!("Failed to parse trash index: {}", e);
+        format!("Failed to parse trash index: {}", e)
+    })
+}
+
+fn save_index(trash_directory: &Path, index: &TrashIndex) -> Result<(), String> {
+    fs::create_dir_all(trash_directory).map_err(|e| {
+        error!("Failed to create trash directory: {}", e);
+        format!("Failed to create trash directory: {}", e)
+    })?;
+    let content = serde_json::to_string_pretty(index).map_err(|e| {
+        error!("Failed to serialise trash index: {}", e);
+        format!("Failed to serialise trash index: {}", e)
+    })?;
+    fs::write(trash_directory.join(TRASH_INDEX_FILE_NAME), content).map_err(|e| {
+        error!("Failed to write trash index: {}", e);
+        format!("Failed to write trash index: {}", e)
+    })
+}
+
+/// Runs `operation` on the trash index of `data_directory` while holding the
+/// trash lock, purging expired entries first and saving the index afterwards.
+fn with_index<T>(
+    handle: &AppHandle,
+    data_directory: &Path,
+    settings: &TrashSettings,
+    operation: impl FnOnce(&Path, &mut TrashIndex) -> Result<T, String>,
+) -> Result<T, String> {
+    let _guard = TRASH_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to lock trash index: {}", e))?;
+    let trash_directory = trash_directory(data_directory);
+    let mut index = load_index(&trash_directory)?;
+    let purged = purge_expired(&mut index, settings.retention_days);
+    let result = operation(&trash_directory, &mut index)?;
+    save_index(&trash_directory, &index)?;
+    if let Err(e) = forget_indexed_documents(handle, &purged) {
+        warn!("Failed to drop purged documents from the search index: {}", e);
+    }
+    Ok(result)
+}
+
+/// Drops entries older than the retention period and returns their original
+/// paths. Files in the app-managed trash are deleted for good with their
+/// sidecars; the system trash manages its own files.
+fn purge_expired(index: &mut TrashIndex, retention_days: u64) -> Vec<String> {
+    let cutoff = unix_timestamp().saturating_sub(retention_days * SECONDS_PER_DAY);
+    let mut purged = Vec::new();
+    index.entries.retain(|entry| {
+        if entry.deleted_at >= cutoff {
+            return true;
+        }
+        purged.push(entry.original_path.clone());
+        if let Some(trashed_path) = &entry.trashed_path {
+            match fs::remove_file(trashed_path) {
+                Ok(()) => debug!("Purged expired trash entry {}", trashed_path),
+                Err(e) => warn!("Failed to purge expired trash entry {}: {}", trashed_path, e),
+            }
+            for sidecar in sidecar_paths(Path::new(trashed_path)) {
+                if sidecar.exists() {
+                    if let Err(e) = fs::remove_file(&sidecar) {
+                        warn!("Failed to purge sidecar {}: {}", sidecar.display(), e);
+                    }
+                }
+            }
+        }
+        false
+    });
+    purged
+}
+
+/// Refuses any path that does not resolve to a file inside the `documents`
+/// directory of `data_directory`.
+fn ensure_processed_document(file_path: &Path, data_directory: &Path) -> Result<PathBuf, String> {
+    let documents_directory = canonicalize(&data_directory.join(DOCUMENTS_DIRECTORY_NAME))
+        .map_err(|e| format!("Failed to resolve documents directory: {}", e))?;
+    let file_path = canonicalize(file_path)
+        .map_err(|e| format!("Failed to resolve {}: {}", file_path.display(), e))?;
+    if !file_path.starts_with(&documents_directory) || !file_path.is_file() {
+        return Err(format!(
+            "Refusing to delete a file outside the documents directory: {}",
+            file_path.display()
+        ));
+    }
+    Ok(file_path)
+}
+
+/// Moves a processed document and its sidecars to the system trash when
+/// enabled and available, falling back to the app-managed `.trash` folder
+/// inside `data_directory`.
+pub fn move_to_trash(
+    handle: &AppHandle,
+    file_path: &Path,
+    data_directory: &Path,
+) -> Result<TrashedDocument, String> {
+    let settings = load_settings(handle)?.trash;
+    let file_path = ensure_processed_document(file_path, data_directory)?;
+    let original_path = file_path.display().to_string();
+
+    with_index(handle, data_directory, &settings, |trash_directory, index| {
+        let id = Uuid::new_v4().to_string();
+        let deleted_at = unix_timestamp();
+
+        if settings.use_system_trash {
+            match trash::delete(&file_path) {
+                Ok(()) => {
+                    for sidecar in sidecar_paths(&file_path) {
+                        if sidecar.exists() {
+                            if let Err(e) = trash::delete(&sidecar) {
+                                warn!("Failed to trash sidecar {}: {}", sidecar.display(), e);
+                            }
+                        }
+                    }
+                    let entry = TrashedDocument {
+                        id,
+                        original_path,
+                        trashed_path: None,
+                        location: TrashLocation::System,
+                        deleted_at,
+                    };
+                    index.entries.push(entry.clone());
+                    return Ok(entry);
+                }
+                Err(e) => warn!("System trash unavailable, using app trash: {}", e),
+            }
+        }
+
+        let file_name = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        fs::create_dir_all(trash_directory).map_err(|e| {
+            error!("Failed to create trash directory: {}", e);
+            format!("Failed to create trash directory: {}", e)
+        })?;
+        let trashed_path = trash_directory.join(format!("{}-{}", id, file_name));
+        fs::rename(&file_path, &trashed_path).map_err(|e| {
+            error!("Failed to move document to trash: {}", e);
+            format!("Failed to move document to trash: {}", e)
+        })?;
+        move_sidecars(&file_path, &trashed_path);
+        let entry = TrashedDocument {
+            id,
+            original_path,
+            trashed_path: Some(trashed_path.display().to_string()),
+            location: TrashLocation::App,
+            deleted_at,
+        };
+        index.entries.push(entry.clone());
+        Ok(entry)
+    })
+    .map(|entry| {
+        if let Err(e) = record_move(
+            handle,
+            None,
+            &entry.original_path,
+            &trash_location(&entry),
+            RenameTrigger::Trash,
+        ) {
+            warn!("Failed to record the deletion in the rename history: {}", e);
+        }
+        if let Err(e) = trash_indexed_document(handle, &entry.original_path) {
+            warn!("Failed to remove the document from the search index: {}", e);
+        }
+        entry
+    })
+}
+
+/// Where the rename history finds a trashed document: its path in the app
+/// trash, or `trash:<id>` when the system trash took it.
+fn trash_location(entry: &TrashedDocument) -> String {
+    entry
+        .trashed_path
+        .clone()
+        .unwrap_or_else(|| format!("trash:{}", entry.id))
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+))]
+fn restore_from_system_trash(entry: &TrashedDocument) -> Result<(), String> {
+    let original_path = PathBuf::from(&entry.original_path);
+    let items =
+        trash::os_limited::list().map_err(|e| format!("Failed to list system trash: {}", e))?;
+    let latest = |path: &Path| {
+        items
+            .iter()
+            .filter(|item| item.original_path() == path)
+            .max_by_key(|item| item.time_deleted)
+            .cloned()
+    };
+    let document = latest(&original_path).ok_or_else(|| {
+        format!(
+            "Document is no longer in the system trash: {}",
+            entry.original_path
+        )
+    })?;
+    // A sidecar written again in the meantime is kept.
+    let sidecars = sidecar_paths(&original_path)
+        .into_iter()
+        .filter(|path| !path.exists())
+        .filter_map(|path| latest(&path));
+    trash::os_limited::restore_all(std::iter::once(document).chain(sidecars))
+        .map_err(|e| format!("Failed to restore from system trash: {}", e))
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+)))]
+fn restore_from_system_trash(entry: &TrashedDocument) -> Result<(), String> {
+    Err(format!(
+        "Restoring from the system trash is not supported on this platform; restore {} from the Trash instead",
+        entry.original_path
+    ))
+}
+
+#[tauri::command]
+pub fn list_deleted_documents(
+    handle: AppHandle,
+    data_directory: String,
+) -> Result<Vec<TrashedDocument>, String> {
+    let settings = load_settings(&handle)?.trash;
+    let data_directory = PathScope::current(&handle)?.check(&data_directory)?;
+    with_index(&handle, &data_directory, &settings, |_, index| {
+        Ok(index.entries.clone())
+    })
+}
+
+#[tauri::command]
+pub fn restore_deleted_document(
+    handle: AppHandle,
+    data_directory: String,
+    trash_id: String,
+) -> Result<String, String> {
+    let settings = load_settings(&handle)?.trash;
+    let data_directory = PathScope::current(&handle)?.check(&data_directory)?;
+    with_index(&handle, &data_directory, &settings, |_, index| {
+        let position = index
+            .entries
+            .iter()
+            .position(|entry| entry.id == trash_id)
+            .ok_or_else(|| format!("Deleted document not found: {}", trash_id))?;
+        let entry = &index.entries[position];
+        if Path::new(&entry.original_path).exists() {
+            return Err(format!(
+                "A file already exists at the original location: {}",
+                entry.original_path
+            ));
+        }
+
+        match (&entry.location, &entry.trashed_path) {
+            (TrashLocation::App, Some(trashed_path)) => {
+                fs::rename(trashed_path, &entry.original_path).map_err(|e| {
+                    error!("Failed to restore document: {}", e);
+                    format!("Failed to restore document: {}", e)
+                })?;
+                move_sidecars(Path::new(trashed_path), Path::new(&entry.original_path));
+            }
+            (TrashLocation::App, None) => {
+                return Err(format!("Trash entry has no file: {}", trash_id));
+            }
+            (TrashLocation::System, _) => restore_from_system_trash(entry)?,
+        }
+
+        Ok(index.entries.remove(position))
+    })
+    .map(|entry| {
+        let original_path = entry.original_path.clone();
+        if let Err(e) = set_output_status(&data_directory, &original_path, FileStatus::Present) {
+            warn!("Failed to record the restore in the catalogue: {}", e);
+        }
+        if let Err(e) = record_move(
+            &handle,
+            None,
+            &trash_location(&entry),
+            &original_path,
+            RenameTrigger::Restore,
+        ) {
+            warn!("Failed to record the restore in the rename history: {}", e);
+        }
+        if let Err(e) = restore_indexed_document(&handle, &original_path) {
+            warn!("Failed to restore the document to the search index: {}", e);
+        }
+        original_path
+    })
+}
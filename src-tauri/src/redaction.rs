@@ -0,0 +1,435 @@
+use log::{debug, error, warn};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+use tauri::AppHandle;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use super::catalogue::data_directory_of;
+use super::identifiers::extract_identifiers;
+use super::models::redaction::{RedactionRegion, RedactionReport, RedactionStage};
+use super::models::settings::{CollisionPolicy, OcrEngineKind, OcrMode};
+use super::ocr::{run_ocr, select_profile, OcrRequest, MAGICK_UTILITY, TESSERACT_UTILITY};
+use super::pages::replace_pages;
+use super::sanitizer::{redacted_file_name, resolve_destination};
+use super::scope::PathScope;
+use super::search::{read_page_texts, read_page_texts_strictly};
+use super::settings::load_settings;
+use super::utilities::call_utility;
+use super::workspace::ProcessingWorkspace;
+
+/// Resolution pages are rendered at for locating and blacking out text.
+const RENDER_DENSITY: u32 = 300;
+/// Pixels added around every located word, so no stroke of a glyph remains.
+const BOX_PADDING: u32 = 6;
+/// Longest run of OCR words a term is matched across.
+const MAX_WORDS_PER_TERM: usize = 12;
+/// Shorter terms would black out unrelated text and fail verification.
+const MIN_TERM_LENGTH: usize = 3;
+
+/// A rectangle on a rendered page, in pixels.
+#[derive(Debug, Clone, Copy)]
+struct PixelBox {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+/// A word recognised on a rendered page, normalised for matching.
+struct OcrWord {
+    text: String,
+    area: PixelBox,
+}
+
+/// Lowercase letters and digits of `text`, without accents, so terms match
+/// however they are spaced and punctuated.
+fn normalise(text: &str) -> String {
+    text.nfkd()
+        .filter(|character| !is_combining_mark(*character))
+        .filter(|character| character.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Indices of the words `term` occurs in, on its own or spread over
+/// consecutive words, e.g. a name split over two words.
+fn find_term<'a, I>(words: I, term: &str) -> Vec<usize>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let words: Vec<&str> = words.into_iter().collect();
+    let mut matches = BTreeSet::new();
+    for start in 0..words.len() {
+        let mut joined = String::new();
+        for end in start..words.len().min(start + MAX_WORDS_PER_TERM) {
+            joined.push_str(words[end]);
+            // Matches starting in a later word are found from that word.
+            if let Some(position) = joined.find(term) {
+                if position < words[start].len() {
+                    matches.extend(start..=end);
+                }
+                break;
+            }
+            if joined.len() >= term.len() + words[start].len() {
+                break;
+            }
+        }
+    }
+    matches.into_iter().collect()
+}
+
+/// Width and height of a PNG file, from its header.
+fn png_size(path: &Path) -> Result<(u32, u32), String> {
+    let header = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if header.len() < 24 || &header[1..4] != b"PNG" {
+        return Err(format!("Not a PNG image: {}", path.display()));
+    }
+    let width = u32::from_be_bytes([header[16], header[17], header[18], header[19]]);
+    let height = u32::from_be_bytes([header[20], header[21], header[22], header[23]]);
+    Ok((width, height))
+}
+
+/// Words of a Tesseract TSV file, in reading order.
+fn parse_tsv(tsv: &str) -> Vec<OcrWord> {
+    tsv.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('\t').collect();
+            if columns.len() < 12 || columns[0] != "5" {
+                return None;
+            }
+            let number = |index: usize| columns[index].trim().parse::<u32>().ok();
+            let (left, top, width, height) = (number(6)?, number(7)?, number(8)?, number(9)?);
+            let text = normalise(columns[11]);
+            (!text.is_empty()).then_some(OcrWord {
+                text,
+                area: PixelBox {
+                    left,
+                    top,
+                    right: left + width,
+                    bottom: top + height,
+                },
+            })
+        })
+        .collect()
+}
+
+fn region_box(region: &RedactionRegion, (width, height): (u32, u32)) -> PixelBox {
+    let scale = |fraction: f64, size: u32| (fraction.clamp(0.0, 1.0) * f64::from(size)).round() as u32;
+    PixelBox {
+        left: scale(region.x, width),
+        top: scale(region.y, height),
+        right: scale(region.x + region.width, width),
+        bottom: scale(region.y + region.height, height),
+    }
+}
+
+async fn render_page(
+    handle: &AppHandle,
+    input: &Path,
+    page_number: u32,
+    output: &Path,
+) -> Result<(), String> {
+    let args = vec![
+        "-density".to_owned(),
+        RENDER_DENSITY.to_string(),
+        format!("{}[{}]", input.display(), page_number - 1),
+        "-background".to_owned(),
+        "white".to_owned(),
+        "-alpha".to_owned(),
+        "remove".to_owned(),
+        "-alpha".to_owned(),
+        "off".to_owned(),
+        output.display().to_string(),
+    ];
+    if call_utility(handle.clone(), MAGICK_UTILITY.to_owned(), args, false).await {
+        Ok(())
+    } else {
+        error!("Failed to render page {} of {}", page_number, input.display());
+        Err(format!("Failed to render page {}", page_number))
+    }
+}
+
+async fn recognise_words(
+    handle: &AppHandle,
+    image: &Path,
+    languages: &str,
+) -> Result<Vec<OcrWord>, String> {
+    let base = image.with_extension("");
+    let args = vec![
+        image.display().to_string(),
+        base.display().to_string(),
+        "-l".to_owned(),
+        languages.to_owned(),
+        "tsv".to_owned(),
+    ];
+    if !call_utility(handle.clone(), TESSERACT_UTILITY.to_owned(), args, false).await {
+        return Err(format!("Failed to recognise the text of {}", image.display()));
+    }
+    let tsv_path = base.with_extension("tsv");
+    let tsv = fs::read_to_string(&tsv_path)
+        .map_err(|e| format!("Failed to read {}: {}", tsv_path.display(), e))?;
+    Ok(parse_tsv(&tsv))
+}
+
+/// Paints `boxes` black on `image` and writes the result as a one-page PDF
+/// of the same physical size as the rendered page.
+async fn paint_boxes(
+    handle: &AppHandle,
+    image: &Path,
+    boxes: &[PixelBox],
+    output: &Path,
+) -> Result<(), String> {
+    let mut args = vec![
+        image.display().to_string(),
+        "-fill".to_owned(),
+        "black".to_owned(),
+    ];
+    for area in boxes {
+        args.push("-draw".to_owned());
+        args.push(format!(
+            "rectangle {},{} {},{}",
+            area.left, area.top, area.right, area.bottom
+        ));
+    }
+    args.extend([
+        "-units".to_owned(),
+        "PixelsPerInch".to_owned(),
+        "-density".to_owned(),
+        RENDER_DENSITY.to_string(),
+        "-compress".to_owned(),
+        "Zip".to_owned(),
+        output.display().to_string(),
+    ]);
+    if call_utility(handle.clone(), MAGICK_UTILITY.to_owned(), args, false).await {
+        Ok(())
+    } else {
+        error!("Failed to paint redaction boxes on {}", image.display());
+        Err(format!("Failed to paint redaction boxes on {}", image.display()))
+    }
+}
+
+/// Pages of `texts` on which a term survives, with the term.
+fn surviving_terms<'a>(texts: &[String], terms: &'a [String]) -> Vec<(u32, &'a str)> {
+    let mut survivors = Vec::new();
+    for (index, text) in texts.iter().enumerate() {
+        let words: Vec<String> = text.split_whitespace().map(normalise).collect();
+        for term in terms {
+            if !find_term(words.iter().map(String::as_str), term).is_empty() {
+                survivors.push((index as u32 + 1, term.as_str()));
+            }
+        }
+    }
+    survivors
+}
+
+/// Writes a redacted copy of a processed document. Every page with something
+/// to hide is rendered, blacked out and put back as an image only, so no text,
+/// vector or embedded image content of the page remains; other pages are
+/// copied as they are. A page whose text has a term OCR cannot locate needs a
+/// region from the user. Rasterised pages are OCRed again when the default OCR
+/// profile uses ocrmypdf, and the result is rejected if any term can still be
+/// read from its text layer, or if the text layer cannot be read at all.
+#[tauri::command]
+pub async fn run_redaction_stage(
+    handle: AppHandle,
+    redaction_stage: RedactionStage,
+) -> Result<RedactionReport, String> {
+    let document_path = PathScope::current(&handle)?.check(&redaction_stage.document_path)?;
+    let settings = load_settings(&handle)?;
+    let profile = select_profile(&settings.ocr, None)?;
+    let directory = document_path
+        .parent()
+        .ok_or_else(|| format!("Invalid document path: {}", document_path.display()))?;
+    let file_name = document_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("Invalid document path: {}", document_path.display()))?;
+
+    let page_texts = read_page_texts(&document_path, None);
+    let mut terms: Vec<String> = Vec::new();
+    for term in &redaction_stage.terms {
+        let term = normalise(term);
+        if term.chars().count() < MIN_TERM_LENGTH {
+            return Err(format!(
+                "Terms to redact need at least {} letters or digits",
+                MIN_TERM_LENGTH
+            ));
+        }
+        terms.push(term);
+    }
+    for identifier in extract_identifiers(&page_texts)
+        .into_iter()
+        .filter(|identifier| redaction_stage.identifier_kinds.contains(&identifier.kind))
+    {
+        terms.push(normalise(&identifier.value));
+    }
+    terms.sort();
+    terms.dedup();
+    if terms.is_empty() && redaction_stage.regions.is_empty() {
+        return Err("Nothing to redact".to_string());
+    }
+
+    let data_directory = data_directory_of(&document_path).unwrap_or_else(|| directory.to_path_buf());
+    let workspace = ProcessingWorkspace::new(&data_directory)?;
+    let languages = profile.languages.join("+");
+    let page_count = page_texts.len() as u32;
+    let mut found_terms = BTreeSet::new();
+    let mut replacements: Vec<(u32, PathBuf)> = Vec::new();
+    let mut unconfirmed_pages: Vec<u32> = Vec::new();
+    let mut report = RedactionReport {
+        document_path: String::new(),
+        redacted_pages: Vec::new(),
+        boxes: 0,
+        not_found: Vec::new(),
+        unlocated_pages: Vec::new(),
+    };
+
+    for page_number in 1..=page_count {
+        let regions: Vec<&RedactionRegion> = redaction_stage
+            .regions
+            .iter()
+            .filter(|region| region.page_number == page_number)
+            .collect();
+        if terms.is_empty() && regions.is_empty() {
+            continue;
+        }
+        let image = workspace.path(&format!("page-{}.png", page_number));
+        render_page(&handle, &document_path, page_number, &image).await?;
+
+        let mut boxes: Vec<PixelBox> = Vec::new();
+        let mut is_unlocated = false;
+        if !terms.is_empty() {
+            let words = recognise_words(&handle, &image, &languages).await?;
+            let text_words: Vec<String> = page_texts[page_number as usize - 1]
+                .split_whitespace()
+                .map(normalise)
+                .collect();
+            for term in &terms {
+                let located = find_term(words.iter().map(|word| word.text.as_str()), term);
+                if !located.is_empty() {
+                    found_terms.insert(term.clone());
+                    boxes.extend(located.into_iter().map(|index| {
+                        let area = words[index].area;
+                        PixelBox {
+                            left: area.left.saturating_sub(BOX_PADDING),
+                            top: area.top.saturating_sub(BOX_PADDING),
+                            right: area.right + BOX_PADDING,
+                            bottom: area.bottom + BOX_PADDING,
+                        }
+                    }));
+                } else if !find_term(text_words.iter().map(String::as_str), term).is_empty() {
+                    found_terms.insert(term.clone());
+                    is_unlocated = true;
+                }
+            }
+        }
+        if !regions.is_empty() {
+            let size = png_size(&image)?;
+            boxes.extend(regions.iter().map(|region| region_box(region, size)));
+        }
+
+        // OCR could not say where the term is, so nothing would cover it
+        // on the rendered image unless the user marked the spot.
+        if is_unlocated && regions.is_empty() {
+            unconfirmed_pages.push(page_number);
+            continue;
+        }
+        if boxes.is_empty() && !is_unlocated {
+            continue;
+        }
+        let redacted_page = workspace.path(&format!("redacted-{}.pdf", page_number));
+        paint_boxes(&handle, &image, &boxes, &redacted_page).await?;
+        report.boxes += boxes.len() as u32;
+        report.redacted_pages.push(page_number);
+        if is_unlocated {
+            report.unlocated_pages.push(page_number);
+        }
+        replacements.push((page_number, redacted_page));
+    }
+    if !unconfirmed_pages.is_empty() {
+        let pages: Vec<String> = unconfirmed_pages.iter().map(u32::to_string).collect();
+        warn!("Redaction terms could not be located on pages {:?}", pages);
+        return Err(format!(
+            "Terms to redact are in the text of page(s) {} but could not be located on the \
+             page image; mark them with a region and run again. Nothing was written",
+            pages.join(", ")
+        ));
+    }
+    report.not_found = redaction_stage
+        .terms
+        .iter()
+        .filter(|term| !found_terms.contains(&normalise(term)))
+        .cloned()
+        .collect();
+    if replacements.is_empty() {
+        return Err("None of the terms or regions were found in the document".to_string());
+    }
+
+    let composed_path = workspace.path("redacted.pdf");
+    replace_pages(&document_path, &replacements, &composed_path)?;
+    let output_path = workspace.path("redacted-ocr.pdf");
+    let text_path = workspace.path("redacted-ocr.txt");
+    let result_path = if profile.engine == OcrEngineKind::OcrMyPdf {
+        // Only the rasterised pages lack text; the others keep theirs.
+        let mut profile = profile.clone();
+        profile.mode = OcrMode::SkipText;
+        let request = OcrRequest {
+            input: &composed_path,
+            output: &output_path,
+            text_output: Some(&text_path),
+        };
+        run_ocr(&handle, &profile, request).await?;
+        output_path
+    } else {
+        warn!("Redacted pages are left without text: the OCR profile does not use ocrmypdf");
+        composed_path
+    };
+
+    let result_texts = read_page_texts_strictly(&result_path).map_err(|e| {
+        error!("Failed to verify the redaction: {}", e);
+        format!("Failed to verify the redaction, nothing was written: {}", e)
+    })?;
+    if result_texts.len() != page_texts.len() {
+        return Err(format!(
+            "The redacted document has {} page(s) instead of {}; nothing was written",
+            result_texts.len(),
+            page_texts.len()
+        ));
+    }
+    let survivors = surviving_terms(&result_texts, &terms);
+    if !survivors.is_empty() {
+        let pages = survivors
+            .iter()
+            .map(|(page, _)| page.to_string())
+            .collect::<BTreeSet<String>>();
+        error!("Redaction left readable text on pages {:?}", pages);
+        return Err(format!(
+            "Redacted text can still be read on page(s) {}; nothing was written",
+            pages.into_iter().collect::<Vec<String>>().join(", ")
+        ));
+    }
+
+    let destination = resolve_destination(
+        directory,
+        &redacted_file_name(&file_name),
+        None,
+        &settings.file_names,
+    )?;
+    workspace.commit(
+        &result_path,
+        &destination,
+        settings.file_names.collision_policy == CollisionPolicy::Overwrite,
+    )?;
+    debug!(
+        "Redacted {} page(s) of {} into {}",
+        report.redacted_pages.len(),
+        document_path.display(),
+        destination.display()
+    );
+    report.document_path = destination.display().to_string();
+    Ok(report)
+}
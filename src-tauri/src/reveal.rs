@@ -0,0 +1,80 @@
+use log::debug;
+use std::{path::Path, process::Command};
+
+use super::scope::canonicalize;
+
+/// Opens the platform file manager with `path` selected.
+pub fn reveal_in_file_manager(path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Err(format!("Path does not exist: {}", path.display()));
+    }
+    let path = canonicalize(path)
+        .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))?;
+    reveal(&path)
+}
+
+#[cfg(target_os = "windows")]
+fn reveal(path: &Path) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+
+    // Explorer wants `/select,"<path>"` as a single argument and exits with 1
+    // even when it succeeds, so only a failure to launch it is an error.
+    let path = path.display().to_string();
+    debug!("Revealing {} in Explorer", path);
+    Command::new("explorer")
+        .raw_arg(format!("/select,\"{}\"", path))
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to open Explorer: {}", e))
+}
+
+#[cfg(target_os = "macos")]
+fn reveal(path: &Path) -> Result<(), String> {
+    run(Command::new("open").arg("-R").arg(path))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn reveal(path: &Path) -> Result<(), String> {
+    use log::warn;
+
+    let uri = url::Url::from_file_path(path)
+        .map_err(|_| format!("Failed to build file URI for {}", path.display()))?;
+    let show_items = run(Command::new("dbus-send").args([
+        "--session",
+        "--print-reply",
+        "--dest=org.freedesktop.FileManager1",
+        "--type=method_call",
+        "/org/freedesktop/FileManager1",
+        "org.freedesktop.FileManager1.ShowItems",
+        &format!("array:string:{}", uri),
+        "string:",
+    ]));
+    match show_items {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            warn!("FileManager1.ShowItems failed, opening parent directory: {}", e);
+            let parent = path
+                .parent()
+                .ok_or_else(|| format!("Path has no parent directory: {}", path.display()))?;
+            run(Command::new("xdg-open").arg(parent))
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn run(command: &mut Command) -> Result<(), String> {
+    debug!("Running {:?}", command);
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to run {:?}: {}", command.get_program(), e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{:?} exited with {}: {}",
+            command.get_program(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
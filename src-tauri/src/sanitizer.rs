@@ -0,0 +1,177 @@
+use std::path::{Path, PathBuf};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use super::models::settings::{CollisionPolicy, FileNameSettings};
+
+const FORBIDDEN_CHARACTERS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+const PDF_EXTENSION: &str = ".pdf";
+
+/// Turns a model- or user-provided name into a PDF file name that is valid on
+/// Windows, macOS and Linux: forbidden and control characters become `-`,
+/// trailing dots and spaces are removed, reserved device names are prefixed
+/// with `_` and the stem is cut to the configured length in bytes.
+pub fn sanitise_file_name(name: &str, settings: &FileNameSettings) -> Result<String, String> {
+    let name = name.trim();
+    let stem = strip_pdf_extension(name);
+
+    let stem: String = if settings.transliterate {
+        stem.nfd().filter(|c| !is_combining_mark(*c)).collect()
+    } else {
+        stem.nfc().collect()
+    };
+
+    let mut sanitised = String::with_capacity(stem.len());
+    for c in stem.chars() {
+        let c = if c.is_control() || FORBIDDEN_CHARACTERS.contains(&c) {
+            '-'
+        } else if c.is_whitespace() {
+            ' '
+        } else {
+            c
+        };
+        if (c == '-' && sanitised.ends_with('-')) || (c == ' ' && sanitised.ends_with(' ')) {
+            continue;
+        }
+        sanitised.push(c);
+    }
+
+    let mut stem = trim_stem(&sanitised);
+    if stem.is_empty() {
+        return Err(format!("File name has no usable characters: {:?}", name));
+    }
+    if is_reserved_name(&stem) {
+        stem.insert(0, '_');
+    }
+    stem = truncate_stem(&stem, settings.max_file_name_length);
+
+    Ok(format!("{}{}", stem, PDF_EXTENSION))
+}
+
+/// Picks the final path for `file_name` inside `directory`, enforcing the
+/// configured maximum path length and applying the collision policy when a
+/// different file already exists there. `current_path` is the file being
+/// renamed, if any, so renaming a file to its own name is not a collision.
+pub fn resolve_destination(
+    directory: &Path,
+    file_name: &str,
+    current_path: Option<&Path>,
+    settings: &FileNameSettings,
+) -> Result<PathBuf, String> {
+    let stem = strip_pdf_extension(file_name);
+    let directory_length = directory.to_string_lossy().len() + 1;
+    let available = settings
+        .max_path_length
+        .saturating_sub(directory_length + PDF_EXTENSION.len());
+    if available == 0 {
+        return Err(format!(
+            "Output directory path is too long for the {} byte limit: {}",
+            settings.max_path_length,
+            directory.display()
+        ));
+    }
+    let stem = truncate_stem(stem, available);
+
+    let candidate = directory.join(format!("{}{}", stem, PDF_EXTENSION));
+    if !is_collision(&candidate, current_path) {
+        return Ok(candidate);
+    }
+
+    match settings.collision_policy {
+        CollisionPolicy::Overwrite => Ok(candidate),
+        CollisionPolicy::Fail => Err(format!("File already exists: {}", candidate.display())),
+        CollisionPolicy::Suffix => {
+            let mut counter = 2;
+            loop {
+                let suffix = format!("-{}", counter);
+                let stem = truncate_stem(&stem, available.saturating_sub(suffix.len()));
+                let candidate = directory.join(format!("{}{}{}", stem, suffix, PDF_EXTENSION));
+                if !is_collision(&candidate, current_path) {
+                    return Ok(candidate);
+                }
+                counter += 1;
+            }
+        }
+    }
+}
+
+/// The file name of part `part` (1-based) of a split document, e.g.
+/// `peticao-parte-2.pdf` for `peticao.pdf`.
+pub fn part_file_name(file_name: &str, part: usize) -> String {
+    format!("{}-parte-{}{}", strip_pdf_extension(file_name), part, PDF_EXTENSION)
+}
+
+/// The file name of the redacted copy of a document, e.g.
+/// `peticao-tarjado.pdf` for `peticao.pdf`.
+pub fn redacted_file_name(file_name: &str) -> String {
+    format!("{}-tarjado{}", strip_pdf_extension(file_name), PDF_EXTENSION)
+}
+
+/// The file name of a signed source kept next to a processed document, e.g.
+/// `peticao-original-assinado.pdf` for `peticao.pdf`; `source` (1-based)
+/// tells apart the originals of a document composed from several.
+pub fn signed_original_file_name(file_name: &str, source: usize) -> String {
+    match source {
+        1 => format!("{}-original-assinado{}", strip_pdf_extension(file_name), PDF_EXTENSION),
+        _ => format!(
+            "{}-original-assinado-{}{}",
+            strip_pdf_extension(file_name),
+            source,
+            PDF_EXTENSION
+        ),
+    }
+}
+
+fn strip_pdf_extension(name: &str) -> &str {
+    let split = name.len().saturating_sub(PDF_EXTENSION.len());
+    match name.get(split..) {
+        Some(extension) if extension.eq_ignore_ascii_case(PDF_EXTENSION) => &name[..split],
+        _ => name,
+    }
+}
+
+fn is_collision(candidate: &Path, current_path: Option<&Path>) -> bool {
+    if !candidate.exists() {
+        return false;
+    }
+    match current_path {
+        Some(current_path) => !same_file(candidate, current_path),
+        None => true,
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn is_reserved_name(stem: &str) -> bool {
+    let base = stem.split('.').next().unwrap_or(stem).trim_end();
+    RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(base))
+}
+
+fn trim_stem(stem: &str) -> String {
+    stem.trim_start_matches([' ', '-', '.'])
+        .trim_end_matches([' ', '-', '.'])
+        .to_string()
+}
+
+/// Cuts `stem` to at most `max_length` bytes, as file systems count them,
+/// without splitting a character.
+fn truncate_stem(stem: &str, max_length: usize) -> String {
+    if stem.len() <= max_length {
+        return stem.to_string();
+    }
+    let mut end = max_length;
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    trim_stem(&stem[..end])
+}
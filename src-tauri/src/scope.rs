@@ -0,0 +1,590 @@
+use lazy_static::lazy_static;
+use log::{debug, error, warn};
+use std::{
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+    sync::RwLock,
+};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::DialogExt;
+use tauri_plugin_fs::FsExt;
+
+use super::models::settings::PathSettings;
+use super::settings::{load_settings, save_settings};
+
+const ROOTS_FILE_NAME: &str = "scope-roots.json";
+/// Suffix of the data directory the frontend puts next to a document.
+const DATA_DIRECTORY_SUFFIX: &str = "-data";
+
+lazy_static! {
+    /// Data directories registered so far, loaded from the app data directory
+    /// on first use so a restart keeps them.
+    static ref REGISTERED_ROOTS: RwLock<Option<Vec<PathBuf>>> = RwLock::new(None);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopeError {
+    Empty,
+    Relative(String),
+    Traversal(String),
+    DevicePath(String),
+    NetworkPath(String),
+    InvalidCharacters(String),
+    Unresolvable(String, String),
+    OutsideScope(String),
+}
+
+impl fmt::Display for ScopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScopeError::Empty => write!(f, "Path is empty"),
+            ScopeError::Relative(path) => write!(f, "Path is not absolute: {}", path),
+            ScopeError::Traversal(path) => write!(f, "Path contains '..': {}", path),
+            ScopeError::DevicePath(path) => write!(f, "Device paths are not allowed: {}", path),
+            ScopeError::NetworkPath(path) => write!(f, "Network paths are not allowed: {}", path),
+            ScopeError::InvalidCharacters(path) => {
+                write!(f, "Path contains invalid characters: {}", path)
+            }
+            ScopeError::Unresolvable(path, reason) => {
+                write!(f, "Failed to resolve {}: {}", path, reason)
+            }
+            ScopeError::OutsideScope(path) => {
+                write!(f, "Path is outside the app's data directories: {}", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScopeError {}
+
+impl From<ScopeError> for String {
+    fn from(error: ScopeError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Resolves symlinks and `.` components. On Windows the result keeps the
+/// `C:\…` form rather than the verbatim `\\?\C:\…` one, so it passes
+/// `check_syntax` again and matches the paths stored in the catalogue, the
+/// search index and the history.
+pub fn canonicalize(path: &Path) -> std::io::Result<PathBuf> {
+    dunce::canonicalize(path)
+}
+
+/// The directories that commands coming from the webview may touch: data
+/// directories registered when a document is opened plus the roots configured
+/// in settings. Every path is canonicalised, so symlinks are followed before
+/// the containment check.
+#[derive(Debug, Clone)]
+pub struct PathScope {
+    roots: Vec<PathBuf>,
+    allow_network_paths: bool,
+}
+
+impl PathScope {
+    pub fn new(roots: impl IntoIterator<Item = PathBuf>, allow_network_paths: bool) -> Self {
+        let roots = roots
+            .into_iter()
+            .filter_map(|root| match canonicalize(&root) {
+                Ok(root) => Some(root),
+                Err(e) => {
+                    debug!("Skipping unavailable scope root {}: {}", root.display(), e);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            roots,
+            allow_network_paths,
+        }
+    }
+
+    pub fn current(handle: &AppHandle) -> Result<Self, String> {
+        let settings = load_settings(handle)?.paths;
+        let mut roots = registered_roots(handle)?;
+        roots.extend(settings.allowed_roots.iter().map(PathBuf::from));
+        Ok(Self::new(roots, settings.allow_network_paths))
+    }
+
+    /// Validates an existing path and returns its canonical form.
+    pub fn check(&self, path: &str) -> Result<PathBuf, ScopeError> {
+        check_syntax(path, self.allow_network_paths)?;
+        let canonical = canonicalize(Path::new(path))
+            .map_err(|e| ScopeError::Unresolvable(path.to_string(), e.to_string()))?;
+        self.contains(&canonical, path)?;
+        Ok(canonical)
+    }
+
+    /// Validates a path that may not exist yet: its parent must exist and be in
+    /// scope, and its last component must be a plain name.
+    pub fn check_new(&self, path: &str) -> Result<PathBuf, ScopeError> {
+        check_syntax(path, self.allow_network_paths)?;
+        let raw = Path::new(path);
+        if raw.exists() {
+            return self.check(path);
+        }
+        let (parent, name) = match (raw.parent(), raw.components().next_back()) {
+            (Some(parent), Some(Component::Normal(name))) => (parent, name),
+            _ => {
+                return Err(ScopeError::Unresolvable(
+                    path.to_string(),
+                    "no parent directory".to_string(),
+                ))
+            }
+        };
+        let parent = canonicalize(parent)
+            .map_err(|e| ScopeError::Unresolvable(path.to_string(), e.to_string()))?;
+        let canonical = parent.join(name);
+        self.contains(&canonical, path)?;
+        Ok(canonical)
+    }
+
+    fn contains(&self, canonical: &Path, raw: &str) -> Result<(), ScopeError> {
+        if self.roots.iter().any(|root| canonical.starts_with(root)) {
+            Ok(())
+        } else {
+            warn!("Rejected path outside scope: {}", raw);
+            Err(ScopeError::OutsideScope(raw.to_string()))
+        }
+    }
+}
+
+fn roots_path(handle: &AppHandle) -> Result<PathBuf, String> {
+    let data_directory = handle.path().app_data_dir().map_err(|e| {
+        error!("Failed to resolve app data directory: {}", e);
+        format!("Failed to resolve app data directory: {}", e)
+    })?;
+    Ok(data_directory.join(ROOTS_FILE_NAME))
+}
+
+fn load_roots(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path).map_err(|e| {
+        error!("Failed to read registered data directories: {}", e);
+        format!("Failed to read registered data directories: {}", e)
+    })?;
+    serde_json::from_str(&content).map_err(|e| {
+        error!("Failed to parse registered data directories: {}", e);
+        format!("Failed to parse registered data directories: {}", e)
+    })
+}
+
+fn save_roots(path: &Path, roots: &[PathBuf]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            error!("Failed to create app data directory: {}", e);
+            format!("Failed to create app data directory: {}", e)
+        })?;
+    }
+    let content = serde_json::to_string_pretty(roots).map_err(|e| {
+        error!("Failed to serialise registered data directories: {}", e);
+        format!("Failed to serialise registered data directories: {}", e)
+    })?;
+    let temporary_path = path.with_extension("json.tmp");
+    fs::write(&temporary_path, content)
+        .and_then(|_| fs::rename(&temporary_path, path))
+        .map_err(|e| {
+            error!("Failed to write registered data directories: {}", e);
+            format!("Failed to write registered data directories: {}", e)
+        })
+}
+
+fn registered_roots(handle: &AppHandle) -> Result<Vec<PathBuf>, String> {
+    if let Some(roots) = REGISTERED_ROOTS
+        .read()
+        .map_err(|e| format!("Failed to read path scope: {}", e))?
+        .as_ref()
+    {
+        return Ok(roots.clone());
+    }
+    let mut registered = REGISTERED_ROOTS
+        .write()
+        .map_err(|e| format!("Failed to update path scope: {}", e))?;
+    let roots = match registered.as_ref() {
+        Some(roots) => roots.clone(),
+        None => load_roots(&roots_path(handle)?)?,
+    };
+    *registered = Some(roots.clone());
+    Ok(roots)
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    if cfg!(windows) {
+        a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+    } else {
+        a == b
+    }
+}
+
+/// Lets the webview's file system plugin read the registered data
+/// directories and configured roots again after a restart.
+pub fn restore_scope(handle: &AppHandle) {
+    let roots = match (registered_roots(handle), load_settings(handle)) {
+        (Ok(mut roots), Ok(settings)) => {
+            roots.extend(settings.paths.allowed_roots.iter().map(PathBuf::from));
+            roots
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            warn!("Failed to restore the path scope: {}", e);
+            return;
+        }
+    };
+    for root in roots.iter().filter(|root| root.is_dir()) {
+        if let Err(e) = handle.fs_scope().allow_directory(root, true) {
+            warn!("Failed to allow {}: {}", root.display(), e);
+        }
+    }
+}
+
+/// Registers the data directory of a document the user opened. The document
+/// must have been picked in the file dialog or sit inside the scope, and the
+/// data directory must be the `<stem>-data` directory next to it. Creates the
+/// directory when needed.
+pub fn register_data_directory(
+    handle: &AppHandle,
+    document_path: &str,
+    data_directory: &str,
+) -> Result<PathBuf, String> {
+    let settings: PathSettings = load_settings(handle)?.paths;
+    check_syntax(document_path, settings.allow_network_paths)?;
+    check_syntax(data_directory, settings.allow_network_paths)?;
+
+    let document = canonicalize(Path::new(document_path))
+        .map_err(|e| ScopeError::Unresolvable(document_path.to_string(), e.to_string()))?;
+    let is_pdf = document
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"));
+    if !document.is_file() || !is_pdf {
+        return Err(format!("Document is not a PDF file: {}", document_path));
+    }
+    let picked = handle.fs_scope().is_allowed(&document);
+    if !picked {
+        PathScope::current(handle)?.contains(&document, document_path)?;
+    }
+
+    let expected = expected_data_directory(&document)
+        .ok_or_else(|| format!("Invalid document path: {}", document_path))?;
+    let raw = Path::new(data_directory);
+    let parent = raw
+        .parent()
+        .map(canonicalize)
+        .transpose()
+        .map_err(|e| ScopeError::Unresolvable(data_directory.to_string(), e.to_string()))?;
+    let next_to_document = match (parent, raw.file_name()) {
+        (Some(parent), Some(name)) => same_path(&parent.join(name), &expected),
+        _ => false,
+    };
+    if !next_to_document {
+        warn!(
+            "Rejected data directory {} for {}",
+            data_directory, document_path
+        );
+        return Err(format!(
+            "The data directory of {} must be {}",
+            document_path,
+            expected.display()
+        ));
+    }
+
+    fs::create_dir_all(&expected).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    let data = canonicalize(&expected)
+        .map_err(|e| ScopeError::Unresolvable(data_directory.to_string(), e.to_string()))?;
+    // A `<stem>-data` symlink must not lead somewhere else.
+    if !same_path(&data, &expected) {
+        warn!(
+            "Rejected data directory {} resolving to {}",
+            data_directory,
+            data.display()
+        );
+        return Err(ScopeError::OutsideScope(data_directory.to_string()).into());
+    }
+
+    let mut roots = registered_roots(handle)?;
+    if !roots.contains(&data) {
+        let mut registered = REGISTERED_ROOTS
+            .write()
+            .map_err(|e| format!("Failed to update path scope: {}", e))?;
+        roots = registered.take().unwrap_or(roots);
+        if !roots.contains(&data) {
+            debug!("Registered data directory {}", data.display());
+            roots.push(data.clone());
+        }
+        let saved = save_roots(&roots_path(handle)?, &roots);
+        *registered = Some(roots);
+        saved?;
+    }
+    if let Err(e) = handle.fs_scope().allow_directory(&data, true) {
+        warn!("Failed to allow {}: {}", data.display(), e);
+    }
+    Ok(data)
+}
+
+/// `<stem>-data` next to `document`, which must be canonical.
+fn expected_data_directory(document: &Path) -> Option<PathBuf> {
+    let stem = document.file_stem()?.to_string_lossy();
+    Some(document.with_file_name(format!("{}{}", stem, DATA_DIRECTORY_SUFFIX)))
+}
+
+/// Adds a root the user picks in the native folder dialog. Roots cannot be
+/// added through `update_settings`, so the webview cannot widen its own scope.
+#[tauri::command]
+pub async fn add_allowed_root(handle: AppHandle) -> Result<Option<PathSettings>, String> {
+    let Some(picked) = handle
+        .dialog()
+        .file()
+        .set_title("Selecione uma pasta de trabalho")
+        .blocking_pick_folder()
+    else {
+        return Ok(None);
+    };
+    let picked = picked
+        .into_path()
+        .map_err(|e| format!("Invalid folder: {}", e))?;
+    let root = canonicalize(&picked)
+        .map_err(|e| format!("Failed to resolve {}: {}", picked.display(), e))?;
+    let root = root.display().to_string();
+
+    let mut settings = load_settings(&handle)?;
+    // Picking a share in the dialog is the consent network paths need.
+    if root.starts_with(r"\\") {
+        settings.paths.allow_network_paths = true;
+    }
+    if !settings.paths.allowed_roots.contains(&root) {
+        settings.paths.allowed_roots.push(root.clone());
+    }
+    save_settings(&handle, &settings)?;
+    if let Err(e) = handle.fs_scope().allow_directory(&root, true) {
+        warn!("Failed to allow {}: {}", root, e);
+    }
+    Ok(Some(settings.paths))
+}
+
+/// Removes a configured root. Narrowing the scope needs no confirmation; the
+/// webview's file system plugin keeps the root until the app restarts.
+#[tauri::command]
+pub fn remove_allowed_root(handle: AppHandle, root: String) -> Result<PathSettings, String> {
+    let mut settings = load_settings(&handle)?;
+    settings
+        .paths
+        .allowed_roots
+        .retain(|allowed| allowed != &root);
+    save_settings(&handle, &settings)?;
+    Ok(settings.paths)
+}
+
+/// Rejects empty and relative paths, `..` components, Windows device namespaces
+/// (`\\?\`, `\\.\`), UNC shares unless allowed and, on Windows, colons outside
+/// the drive prefix (alternate data streams).
+fn check_syntax(path: &str, allow_network_paths: bool) -> Result<(), ScopeError> {
+    if path.trim().is_empty() {
+        return Err(ScopeError::Empty);
+    }
+    if path.contains('\0') {
+        return Err(ScopeError::InvalidCharacters(path.to_string()));
+    }
+
+    let normalised = path.replace('/', "\\");
+    if [r"\\?\", r"\\.\", r"\??\"]
+        .iter()
+        .any(|prefix| normalised.starts_with(prefix))
+    {
+        return Err(ScopeError::DevicePath(path.to_string()));
+    }
+    if normalised.starts_with(r"\\") && !allow_network_paths {
+        return Err(ScopeError::NetworkPath(path.to_string()));
+    }
+    if normalised.split('\\').any(|component| component == "..") {
+        return Err(ScopeError::Traversal(path.to_string()));
+    }
+    if !Path::new(path).is_absolute() {
+        return Err(ScopeError::Relative(path.to_string()));
+    }
+    if cfg!(windows) && path.char_indices().any(|(index, c)| c == ':' && index != 1) {
+        return Err(ScopeError::InvalidCharacters(path.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn scope_of(roots: &[&Path]) -> PathScope {
+        PathScope::new(roots.iter().map(|root| root.to_path_buf()), false)
+    }
+
+    fn text(path: &Path) -> String {
+        path.display().to_string()
+    }
+
+    #[test]
+    fn rejects_empty_and_relative_paths() {
+        assert_eq!(check_syntax("", false), Err(ScopeError::Empty));
+        assert_eq!(check_syntax("  ", false), Err(ScopeError::Empty));
+        assert!(matches!(
+            check_syntax("documents/a.pdf", false),
+            Err(ScopeError::Relative(_))
+        ));
+        assert!(matches!(
+            check_syntax("a\0.pdf", false),
+            Err(ScopeError::InvalidCharacters(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_traversal() {
+        for path in ["/data/../etc/passwd", r"C:\data\..\Windows", "/data/a/.."] {
+            assert!(
+                matches!(check_syntax(path, false), Err(ScopeError::Traversal(_))),
+                "{}",
+                path
+            );
+        }
+        // A name that merely starts with dots is not a parent reference.
+        assert!(!matches!(
+            check_syntax("/data/..hidden", false),
+            Err(ScopeError::Traversal(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_device_paths() {
+        for path in [
+            r"\\?\C:\data\a.pdf",
+            r"\\?\UNC\server\share\a.pdf",
+            r"\\.\PhysicalDrive0",
+            r"\??\C:\data",
+            "//?/C:/data/a.pdf",
+        ] {
+            // Device namespaces are refused even when network paths are allowed.
+            assert!(
+                matches!(check_syntax(path, true), Err(ScopeError::DevicePath(_))),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_network_paths_unless_allowed() {
+        for path in [r"\\server\share\a.pdf", "//server/share/a.pdf"] {
+            assert!(
+                matches!(check_syntax(path, false), Err(ScopeError::NetworkPath(_))),
+                "{}",
+                path
+            );
+            assert!(!matches!(
+                check_syntax(path, true),
+                Err(ScopeError::NetworkPath(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn contains_paths_below_a_root() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path().join("data");
+        fs::create_dir_all(root.join("documents")).unwrap();
+        fs::write(root.join("documents").join("a.pdf"), b"%PDF-").unwrap();
+        let scope = scope_of(&[&root]);
+
+        let checked = scope.check(&text(&root.join("documents").join("a.pdf")));
+        assert_eq!(
+            checked,
+            Ok(canonicalize(&root.join("documents").join("a.pdf")).unwrap())
+        );
+        assert!(scope.check(&text(&root)).is_ok());
+    }
+
+    #[test]
+    fn rejects_sibling_prefix_roots() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path().join("data");
+        let sibling = directory.path().join("data2");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&sibling).unwrap();
+        fs::write(sibling.join("a.pdf"), b"%PDF-").unwrap();
+        let scope = scope_of(&[&root]);
+
+        assert!(matches!(
+            scope.check(&text(&sibling.join("a.pdf"))),
+            Err(ScopeError::OutsideScope(_))
+        ));
+        assert!(matches!(
+            scope.check_new(&text(&sibling.join("b.pdf"))),
+            Err(ScopeError::OutsideScope(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_escaping_a_root() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path().join("data");
+        let outside = directory.path().join("outside");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("secret.pdf"), b"%PDF-").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.pdf"), root.join("secret.pdf")).unwrap();
+        let scope = scope_of(&[&root]);
+
+        for path in [
+            root.join("link").join("secret.pdf"),
+            root.join("secret.pdf"),
+        ] {
+            assert!(
+                matches!(scope.check(&text(&path)), Err(ScopeError::OutsideScope(_))),
+                "{}",
+                path.display()
+            );
+        }
+        assert!(matches!(
+            scope.check_new(&text(&root.join("link").join("new.pdf"))),
+            Err(ScopeError::OutsideScope(_))
+        ));
+    }
+
+    #[test]
+    fn checks_new_paths_against_their_parent() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path().join("data");
+        fs::create_dir_all(&root).unwrap();
+        let scope = scope_of(&[&root]);
+
+        assert_eq!(
+            scope.check_new(&text(&root.join("new.pdf"))),
+            Ok(canonicalize(&root).unwrap().join("new.pdf"))
+        );
+        assert!(matches!(
+            scope.check_new(&text(&root.join("missing").join("new.pdf"))),
+            Err(ScopeError::Unresolvable(_, _))
+        ));
+        assert!(matches!(
+            scope.check_new(&text(&root.join("..").join("new.pdf"))),
+            Err(ScopeError::Traversal(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_everything_without_roots() {
+        let directory = tempfile::tempdir().unwrap();
+        let scope = PathScope::new(vec![directory.path().join("missing")], false);
+
+        assert!(matches!(
+            scope.check(&text(directory.path())),
+            Err(ScopeError::OutsideScope(_))
+        ));
+    }
+
+    #[test]
+    fn expects_the_data_directory_next_to_the_document() {
+        assert_eq!(
+            expected_data_directory(Path::new("/cases/a.b.PDF")),
+            Some(PathBuf::from("/cases/a.b-data"))
+        );
+    }
+}
@@ -0,0 +1,593 @@
+use lazy_static::lazy_static;
+use log::{debug, error};
+use lopdf::Document;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tauri::{AppHandle, Manager};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use super::catalogue::{delete_search_documents, load_search_documents, save_search_document};
+use super::dates::PartialDate;
+use super::models::search::{IndexedDocument, PageHit, SearchFacets, SearchHit, SearchQuery};
+
+const DEFAULT_RESULT_LIMIT: usize = 50;
+const MAX_PAGE_HITS: usize = 10;
+/// Characters of context kept on each side of the first match in a snippet.
+const SNIPPET_CONTEXT: usize = 80;
+/// Summary, type and date descriptions weigh more than a match in the text.
+const METADATA_WEIGHT: u32 = 3;
+/// What ocrmypdf writes to its text output for pages that already had text.
+const OCR_SKIPPED_MARKER: &str = "[OCR skipped on page";
+
+/// Words too common in Portuguese to be worth indexing, already folded.
+const STOPWORDS: [&str; 40] = [
+    "a", "ao", "aos", "as", "com", "como", "da", "das", "de", "do", "dos", "e", "em", "entre",
+    "ja", "mais", "mas", "na", "nas", "nao", "no", "nos", "o", "os", "ou", "para", "pela",
+    "pelas", "pelo", "pelos", "por", "que", "se", "sem", "seu", "sua", "um", "uma", "uns",
+    "umas",
+];
+
+lazy_static! {
+    static ref SEARCH_INDEX: Mutex<Option<LoadedIndex>> = Mutex::new(None);
+}
+
+/// The index as stored in the library plus the terms of every document in
+/// search, derived on load.
+struct LoadedIndex {
+    app_data_directory: PathBuf,
+    documents: Vec<IndexedDocument>,
+    /// Documents whose outputs are all in the trash. They are left out of
+    /// search and put back when an output is restored.
+    trashed: Vec<IndexedDocument>,
+    terms: Vec<DocumentTerms>,
+}
+
+struct DocumentTerms {
+    /// Terms of the type, summary and date descriptions.
+    metadata: HashMap<String, u32>,
+    pages: Vec<HashMap<String, u32>>,
+}
+
+impl DocumentTerms {
+    fn new(document: &IndexedDocument) -> Self {
+        let mut metadata = HashMap::new();
+        let descriptions = document.dates.iter().map(|date| date.description.as_str());
+        for text in [
+            document.type_name.as_str(),
+            document.type_abbr.as_str(),
+            document.summary.as_str(),
+        ]
+        .into_iter()
+        .chain(descriptions)
+        {
+            count_terms(text, &mut metadata);
+        }
+        let pages = document
+            .pages
+            .iter()
+            .map(|page| {
+                let mut terms = HashMap::new();
+                count_terms(&page.text, &mut terms);
+                terms
+            })
+            .collect();
+        Self { metadata, pages }
+    }
+}
+
+/// A word of a text, folded, with its byte range in the text.
+struct Token {
+    term: String,
+    start: usize,
+    end: usize,
+}
+
+/// Lowercases `word` and strips its accents, so `Petição` and `peticao` are
+/// the same term. Compatibility forms (`º`, ligatures from OCR) are expanded.
+fn fold(word: &str) -> String {
+    word.nfkd()
+        .filter(|character| !is_combining_mark(*character))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Splits `text` into indexable words: runs of letters and digits, without
+/// stopwords and single letters.
+fn tokenise(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (index, character) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, character.is_alphanumeric()) {
+            (None, true) => start = Some(index),
+            (Some(word_start), false) => {
+                let term = fold(&text[word_start..index]);
+                let is_single_letter =
+                    term.chars().count() == 1 && !term.chars().all(|c| c.is_ascii_digit());
+                if !is_single_letter && !STOPWORDS.contains(&term.as_str()) {
+                    tokens.push(Token {
+                        term,
+                        start: word_start,
+                        end: index,
+                    });
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+/// The indexable words of `text`, folded, in order.
+pub fn terms(text: &str) -> Vec<String> {
+    tokenise(text).into_iter().map(|token| token.term).collect()
+}
+
+fn count_terms(text: &str, terms: &mut HashMap<String, u32>) {
+    for token in tokenise(text) {
+        *terms.entry(token.term).or_insert(0) += 1;
+    }
+}
+
+/// A query word, matched exactly or as a prefix.
+struct QueryTerm {
+    term: String,
+    is_prefix: bool,
+}
+
+impl QueryTerm {
+    fn matches(&self, term: &str) -> bool {
+        if self.is_prefix {
+            term.starts_with(&self.term)
+        } else {
+            term == self.term
+        }
+    }
+
+    fn count(&self, terms: &HashMap<String, u32>) -> u32 {
+        if self.is_prefix {
+            terms
+                .iter()
+                .filter(|(term, _)| self.matches(term))
+                .map(|(_, count)| count)
+                .sum()
+        } else {
+            terms.get(&self.term).copied().unwrap_or(0)
+        }
+    }
+}
+
+fn parse_query(query: &str) -> Vec<QueryTerm> {
+    let mut terms: Vec<QueryTerm> = Vec::new();
+    for token in tokenise(query) {
+        let is_prefix = query[token.end..].starts_with('*');
+        if !terms.iter().any(|other| other.term == token.term) {
+            terms.push(QueryTerm {
+                term: token.term,
+                is_prefix,
+            });
+        }
+    }
+    terms
+}
+
+fn app_data_directory(handle: &AppHandle) -> Result<PathBuf, String> {
+    handle.path().app_data_dir().map_err(|e| {
+        error!("Failed to resolve app data directory: {}", e);
+        format!("Failed to resolve app data directory: {}", e)
+    })
+}
+
+/// Runs `operation` on the index while holding the index lock, loading it
+/// from the library the first time. Operations that change a document write
+/// its entry to the library before changing it in memory.
+fn with_index<T>(
+    handle: &AppHandle,
+    operation: impl FnOnce(&mut LoadedIndex) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut guard = SEARCH_INDEX
+        .lock()
+        .map_err(|e| format!("Failed to lock search index: {}", e))?;
+    let app_data_directory = app_data_directory(handle)?;
+    if guard
+        .as_ref()
+        .map_or(true, |index| index.app_data_directory != app_data_directory)
+    {
+        let (documents, trashed) = load_search_documents(handle)?;
+        let terms = documents.iter().map(DocumentTerms::new).collect();
+        *guard = Some(LoadedIndex {
+            app_data_directory,
+            documents,
+            trashed,
+            terms,
+        });
+    }
+    let index = guard.as_mut().ok_or("Search index not loaded")?;
+    operation(index)
+}
+
+/// Adds `document` to the index, replacing an earlier run of the same stage.
+pub fn index_document(handle: &AppHandle, document: IndexedDocument) -> Result<(), String> {
+    with_index(handle, |index| {
+        save_search_document(handle, &document, false)?;
+        let terms = DocumentTerms::new(&document);
+        index
+            .trashed
+            .retain(|other| other.document_id != document.document_id);
+        match index
+            .documents
+            .iter()
+            .position(|other| other.document_id == document.document_id)
+        {
+            Some(position) => {
+                index.documents[position] = document;
+                index.terms[position] = terms;
+            }
+            None => {
+                index.documents.push(document);
+                index.terms.push(terms);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Points the index at the new path of a renamed output file.
+pub fn move_indexed_document(handle: &AppHandle, old_path: &str, new_path: &str) -> Result<(), String> {
+    with_index(handle, |index| {
+        for (documents, trashed) in [(&mut index.documents, false), (&mut index.trashed, true)] {
+            for document in documents.iter_mut() {
+                if !document.part_paths.iter().any(|path| path == old_path) {
+                    continue;
+                }
+                let mut moved = document.clone();
+                for path in &mut moved.part_paths {
+                    if path == old_path {
+                        *path = new_path.to_string();
+                    }
+                }
+                for page in &mut moved.pages {
+                    if page.document_path == old_path {
+                        page.document_path = new_path.to_string();
+                    }
+                }
+                save_search_document(handle, &moved, trashed)?;
+                *document = moved;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Takes the document with the output at `path` out of search once none of
+/// its outputs is left in place.
+pub fn trash_indexed_document(handle: &AppHandle, path: &str) -> Result<(), String> {
+    with_index(handle, |index| {
+        let Some(position) = index.documents.iter().position(|document| {
+            document.part_paths.iter().any(|part_path| part_path == path)
+        }) else {
+            return Ok(());
+        };
+        let document = &index.documents[position];
+        if document
+            .part_paths
+            .iter()
+            .any(|part_path| Path::new(part_path).exists())
+        {
+            return Ok(());
+        }
+        save_search_document(handle, document, true)?;
+        let document = index.documents.remove(position);
+        index.terms.remove(position);
+        debug!("Removed {} from the search index", document.document_id);
+        index.trashed.push(document);
+        Ok(())
+    })
+}
+
+/// Puts back in search the trashed document with the output at `path`.
+pub fn restore_indexed_document(handle: &AppHandle, path: &str) -> Result<(), String> {
+    with_index(handle, |index| {
+        let Some(position) = index.trashed.iter().position(|document| {
+            document.part_paths.iter().any(|part_path| part_path == path)
+        }) else {
+            return Ok(());
+        };
+        save_search_document(handle, &index.trashed[position], false)?;
+        let document = index.trashed.remove(position);
+        debug!("Restored {} to the search index", document.document_id);
+        index.terms.push(DocumentTerms::new(&document));
+        index.documents.push(document);
+        Ok(())
+    })
+}
+
+/// Drops the trashed documents with an output among `paths`, which were
+/// deleted for good.
+pub fn forget_indexed_documents(handle: &AppHandle, paths: &[String]) -> Result<(), String> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    with_index(handle, |index| {
+        let is_forgotten = |document: &IndexedDocument| {
+            document.part_paths.iter().any(|path| paths.contains(path))
+        };
+        let document_ids: Vec<String> = index
+            .trashed
+            .iter()
+            .filter(|document| is_forgotten(document))
+            .map(|document| document.document_id.clone())
+            .collect();
+        delete_search_documents(handle, &document_ids)?;
+        index.trashed.retain(|document| !is_forgotten(document));
+        Ok(())
+    })
+}
+
+/// The text of every page of `pdf_path`. `ocr_text` is the OCR engine's text
+/// output, pages separated by form feeds; pages it has no text for are read
+/// from the PDF instead.
+pub fn read_page_texts(pdf_path: &Path, ocr_text: Option<&str>) -> Vec<String> {
+    let mut texts: Vec<String> = ocr_text
+        .map(|text| {
+            text.split('\u{c}')
+                .map(|page| {
+                    if page.trim_start().starts_with(OCR_SKIPPED_MARKER) {
+                        String::new()
+                    } else {
+                        page.trim().to_string()
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let document = match Document::load(pdf_path) {
+        Ok(document) => document,
+        Err(e) => {
+            debug!("Failed to load {} for text: {}", pdf_path.display(), e);
+            return texts;
+        }
+    };
+    let page_count = document.get_pages().len();
+    texts.resize(page_count, String::new());
+    for (index, text) in texts.iter_mut().enumerate() {
+        if text.is_empty() {
+            *text = document
+                .extract_text(&[index as u32 + 1])
+                .map(|text| text.trim().to_string())
+                .unwrap_or_default();
+        }
+    }
+    texts
+}
+
+/// The text layer of every page of `pdf_path`, failing when the file or any
+/// page cannot be read, where `read_page_texts` would give empty text. For
+/// checks that must not pass on a file they could not read.
+pub fn read_page_texts_strictly(pdf_path: &Path) -> Result<Vec<String>, String> {
+    let document = Document::load(pdf_path)
+        .map_err(|e| format!("Failed to load {} for text: {}", pdf_path.display(), e))?;
+    (1..=document.get_pages().len() as u32)
+        .map(|page_number| {
+            document
+                .extract_text(&[page_number])
+                .map(|text| text.trim().to_string())
+                .map_err(|e| {
+                    format!(
+                        "Failed to read the text of page {} of {}: {}",
+                        page_number,
+                        pdf_path.display(),
+                        e
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Inclusive ISO bounds of a date with at least a year, e.g. `2024-03-01` to
+/// `2024-03-31` for `2024-03`.
+fn date_bounds(date: &PartialDate) -> Option<(String, String)> {
+    let year = date.year?;
+    Some(match (date.month, date.day) {
+        (Some(month), Some(day)) => {
+            let date = format!("{:04}-{:02}-{:02}", year, month, day);
+            (date.clone(), date)
+        }
+        (Some(month), None) => (
+            format!("{:04}-{:02}-01", year, month),
+            format!("{:04}-{:02}-31", year, month),
+        ),
+        _ => (format!("{:04}-01-01", year), format!("{:04}-12-31", year)),
+    })
+}
+
+fn query_bound(value: Option<&str>, pick_end: bool) -> Result<Option<String>, String> {
+    let Some(value) = value.filter(|value| !value.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let date = PartialDate::parse(value.trim())?;
+    let (start, end) =
+        date_bounds(&date).ok_or_else(|| format!("Date filter needs a year: {}", value))?;
+    Ok(Some(if pick_end { end } else { start }))
+}
+
+/// Up to `SNIPPET_CONTEXT` characters around the first word of `text`
+/// matching `terms`, with whitespace collapsed.
+fn snippet(text: &str, terms: &[QueryTerm]) -> String {
+    let Some(token) = tokenise(text)
+        .into_iter()
+        .find(|token| terms.iter().any(|term| term.matches(&token.term)))
+    else {
+        return String::new();
+    };
+    let start = text[..token.start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT - 1)
+        .map(|(index, _)| index)
+        .unwrap_or(0);
+    let end = text[token.end..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT)
+        .map(|(index, _)| token.end + index)
+        .unwrap_or(text.len());
+    let mut snippet = text[start..end]
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < text.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+fn search(index: &LoadedIndex, query: &SearchQuery) -> Result<Vec<SearchHit>, String> {
+    let terms = parse_query(&query.query);
+    if terms.is_empty() {
+        return Err("Search query has no words to look for".to_string());
+    }
+    let date_from = query_bound(query.date_from.as_deref(), false)?;
+    let date_to = query_bound(query.date_to.as_deref(), true)?;
+    let type_name = query.type_name.as_deref().map(fold);
+
+    let mut hits = Vec::new();
+    for (document, document_terms) in index.documents.iter().zip(&index.terms) {
+        let existing_paths: Vec<&String> = document
+            .part_paths
+            .iter()
+            .filter(|path| Path::new(path).exists())
+            .collect();
+        let Some(document_path) = existing_paths.first() else {
+            continue;
+        };
+        if let Some(type_name) = &type_name {
+            if fold(&document.type_name) != *type_name && fold(&document.type_abbr) != *type_name {
+                continue;
+            }
+        }
+        if let Some(source) = &query.source_document {
+            if !document.source_documents.contains(source) {
+                continue;
+            }
+        }
+        if date_from.is_some() || date_to.is_some() {
+            let in_range = document.dates.iter().any(|date| {
+                let Some((start, end)) = PartialDate::parse(&date.date)
+                    .ok()
+                    .and_then(|date| date_bounds(&date))
+                else {
+                    return false;
+                };
+                date_from.as_ref().map_or(true, |from| end >= *from)
+                    && date_to.as_ref().map_or(true, |to| start <= *to)
+            });
+            if !in_range {
+                continue;
+            }
+        }
+
+        let mut score = 0;
+        let mut all_found = true;
+        for term in &terms {
+            let count = term.count(&document_terms.metadata) * METADATA_WEIGHT
+                + document_terms
+                    .pages
+                    .iter()
+                    .map(|page| term.count(page))
+                    .sum::<u32>();
+            all_found &= count > 0;
+            score += count;
+        }
+        if !all_found {
+            continue;
+        }
+
+        let mut pages: Vec<(u32, PageHit)> = document
+            .pages
+            .iter()
+            .zip(&document_terms.pages)
+            .filter(|(page, _)| existing_paths.contains(&&page.document_path))
+            .filter_map(|(page, page_terms)| {
+                let count: u32 = terms.iter().map(|term| term.count(page_terms)).sum();
+                (count > 0).then(|| {
+                    (
+                        count,
+                        PageHit {
+                            document_path: page.document_path.clone(),
+                            page_number: page.page_number,
+                            snippet: snippet(&page.text, &terms),
+                        },
+                    )
+                })
+            })
+            .collect();
+        pages.sort_by_key(|(count, _)| Reverse(*count));
+        pages.truncate(MAX_PAGE_HITS);
+
+        hits.push((
+            document.indexed_at.as_str(),
+            SearchHit {
+                document_id: document.document_id.clone(),
+                document_path: document_path.to_string(),
+                type_name: document.type_name.clone(),
+                summary: document.summary.clone(),
+                dates: document.dates.clone(),
+                score,
+                pages: pages.into_iter().map(|(_, page)| page).collect(),
+            },
+        ));
+    }
+
+    hits.sort_by(|a, b| b.1.score.cmp(&a.1.score).then_with(|| b.0.cmp(a.0)));
+    Ok(hits
+        .into_iter()
+        .map(|(_, hit)| hit)
+        .take(query.limit.unwrap_or(DEFAULT_RESULT_LIMIT))
+        .collect())
+}
+
+#[tauri::command]
+pub async fn search_documents(
+    handle: AppHandle,
+    query: SearchQuery,
+) -> Result<Vec<SearchHit>, String> {
+    with_index(&handle, |index| search(index, &query))
+}
+
+#[tauri::command]
+pub async fn get_search_facets(handle: AppHandle) -> Result<SearchFacets, String> {
+    with_index(&handle, |index| {
+        let mut type_names = BTreeSet::new();
+        let mut source_documents = BTreeSet::new();
+        let mut dates = BTreeSet::new();
+        for document in &index.documents {
+            if !document.part_paths.iter().any(|path| Path::new(path).exists()) {
+                continue;
+            }
+            type_names.insert(document.type_name.clone());
+            source_documents.extend(document.source_documents.iter().cloned());
+            dates.extend(
+                document
+                    .dates
+                    .iter()
+                    .filter_map(|date| PartialDate::parse(&date.date).ok())
+                    .filter_map(|date| date_bounds(&date)),
+            );
+        }
+        Ok(SearchFacets {
+            type_names: type_names.into_iter().collect(),
+            source_documents: source_documents.into_iter().collect(),
+            earliest_date: dates.iter().map(|(start, _)| start.clone()).min(),
+            latest_date: dates.iter().map(|(_, end)| end.clone()).max(),
+        })
+    })
+}
@@ -0,0 +1,74 @@
+use log::error;
+use std::{fs, path::PathBuf};
+use tauri::{AppHandle, Manager};
+
+use super::models::settings::Settings;
+use super::naming::{save_settings_keeping_counters, validate_naming_settings};
+use super::ocr::validate_ocr_settings;
+use super::optimise::validate_optimisation_settings;
+use super::signing::validate_signature_settings;
+use super::stamping::validate_stamp_settings;
+use super::watcher::validate_watch_settings;
+
+const SETTINGS_FILE_NAME: &str = "settings.json";
+
+fn settings_path(handle: &AppHandle) -> Result<PathBuf, String> {
+    let config_directory = handle.path().app_config_dir().map_err(|e| {
+        error!("Failed to resolve config directory: {}", e);
+        format!("Failed to resolve config directory: {}", e)
+    })?;
+    Ok(config_directory.join(SETTINGS_FILE_NAME))
+}
+
+pub fn load_settings(handle: &AppHandle) -> Result<Settings, String> {
+    let path = settings_path(handle)?;
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| {
+        error!("Failed to read settings: {}", e);
+        format!("Failed to read settings: {}", e)
+    })?;
+    serde_json::from_str(&content).map_err(|e| {
+        error!("Failed to parse settings: {}", e);
+        format!("Failed to parse settings: {}", e)
+    })
+}
+
+pub fn save_settings(handle: &AppHandle, settings: &Settings) -> Result<(), String> {
+    let path = settings_path(handle)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            error!("Failed to create config directory: {}", e);
+            format!("Failed to create config directory: {}", e)
+        })?;
+    }
+    let content = serde_json::to_string_pretty(settings).map_err(|e| {
+        error!("Failed to serialise settings: {}", e);
+        format!("Failed to serialise settings: {}", e)
+    })?;
+    fs::write(&path, content).map_err(|e| {
+        error!("Failed to write settings: {}", e);
+        format!("Failed to write settings: {}", e)
+    })
+}
+
+#[tauri::command]
+pub fn get_settings(handle: AppHandle) -> Result<Settings, String> {
+    load_settings(&handle)
+}
+
+#[tauri::command]
+pub fn update_settings(handle: AppHandle, mut settings: Settings) -> Result<Settings, String> {
+    // The path scope only changes through `add_allowed_root` and
+    // `remove_allowed_root`, so the webview cannot widen it.
+    settings.paths = load_settings(&handle)?.paths;
+    validate_naming_settings(&settings.naming)?;
+    validate_ocr_settings(&settings.ocr)?;
+    validate_optimisation_settings(&settings.optimisation)?;
+    validate_signature_settings(&settings.signatures)?;
+    validate_stamp_settings(&settings.stamping)?;
+    validate_watch_settings(&settings.watch, &settings.paths)?;
+    save_settings_keeping_counters(&handle, &mut settings)?;
+    Ok(settings)
+}
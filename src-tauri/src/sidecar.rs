@@ -0,0 +1,186 @@
+use log::{debug, error, warn};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::models::settings::SidecarFormat;
+use super::models::sidecar::DocumentSidecar;
+use super::models::workflows::PageReference;
+
+const SIDECAR_ROOT_ELEMENT: &str = "document";
+const SIDECAR_FORMATS: [SidecarFormat; 3] =
+    [SidecarFormat::Json, SidecarFormat::Xml, SidecarFormat::Csv];
+
+/// `<name>.metadata.<format>` next to the PDF at `document_path`.
+pub fn sidecar_path(document_path: &Path, format: SidecarFormat) -> PathBuf {
+    let extension = match format {
+        SidecarFormat::Json => "metadata.json",
+        SidecarFormat::Xml => "metadata.xml",
+        SidecarFormat::Csv => "metadata.csv",
+    };
+    document_path.with_extension(extension)
+}
+
+/// Where the sidecars of the document at `document_path` go, one per format.
+pub fn sidecar_paths(document_path: &Path) -> [PathBuf; 3] {
+    SIDECAR_FORMATS.map(|format| sidecar_path(document_path, format))
+}
+
+pub fn write_sidecar(
+    path: &Path,
+    sidecar: &DocumentSidecar,
+    format: SidecarFormat,
+) -> Result<(), String> {
+    let content = match format {
+        SidecarFormat::Json => serde_json::to_string_pretty(sidecar)
+            .map_err(|e| format!("Failed to serialise sidecar: {}", e))?,
+        SidecarFormat::Xml => quick_xml::se::to_string_with_root(SIDECAR_ROOT_ELEMENT, sidecar)
+            .map(|xml| format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}", xml))
+            .map_err(|e| format!("Failed to serialise sidecar: {}", e))?,
+        SidecarFormat::Csv => to_csv(sidecar),
+    };
+    fs::write(path, content).map_err(|e| {
+        error!("Failed to write sidecar {}: {}", path.display(), e);
+        format!("Failed to write sidecar {}: {}", path.display(), e)
+    })
+}
+
+/// Renames the sidecars of a document that moved, whatever their format.
+pub fn move_sidecars(old_document_path: &Path, new_document_path: &Path) {
+    for (old_path, new_path) in sidecar_paths(old_document_path)
+        .into_iter()
+        .zip(sidecar_paths(new_document_path))
+    {
+        if !old_path.exists() {
+            continue;
+        }
+        match fs::rename(&old_path, &new_path) {
+            Ok(()) => debug!("Moved sidecar to {}", new_path.display()),
+            Err(e) => warn!("Failed to move sidecar {}: {}", old_path.display(), e),
+        }
+    }
+}
+
+/// Compact page ranges per source document, e.g. `a.pdf:1-3,5; b.pdf:2`.
+/// The file name is left out when every page comes from one document.
+pub fn page_range(references: &[PageReference]) -> String {
+    let mut groups: Vec<(&str, Vec<u32>)> = Vec::new();
+    for reference in references {
+        match groups.last_mut() {
+            Some((path, pages)) if *path == reference.document_path => {
+                pages.push(reference.page_number)
+            }
+            _ => groups.push((&reference.document_path, vec![reference.page_number])),
+        }
+    }
+    let single_source = groups
+        .iter()
+        .all(|(path, _)| *path == groups[0].0);
+
+    groups
+        .iter()
+        .map(|(path, pages)| {
+            let ranges = ranges(pages);
+            if single_source {
+                ranges
+            } else {
+                let name = Path::new(path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.to_string());
+                format!("{}:{}", name, ranges)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+fn ranges(pages: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for &page in pages {
+        match ranges.last_mut() {
+            Some((_, end)) if page == *end + 1 => *end = page,
+            _ => ranges.push((page, page)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// One header row and one value row. Lists are spread over numbered columns
+/// (`date1`, `date1Description`, `part1`, `identifier1`, ...) and custom
+/// fields become `field.<name>`.
+fn to_csv(sidecar: &DocumentSidecar) -> String {
+    let mut columns: Vec<(String, String)> = vec![
+        ("schemaVersion".into(), sidecar.schema_version.to_string()),
+        ("documentId".into(), sidecar.document_id.clone()),
+        ("fileName".into(), sidecar.file_name.clone()),
+        ("pageCount".into(), sidecar.page_count.to_string()),
+        ("pageRange".into(), sidecar.page_range.clone()),
+        ("outputSha256".into(), sidecar.output_sha256.clone()),
+        ("outputSize".into(), sidecar.output_size.to_string()),
+        ("ocrProfile".into(), sidecar.ocr_profile.clone()),
+        ("processingStartedAt".into(), sidecar.processing_started_at.clone()),
+        ("processingFinishedAt".into(), sidecar.processing_finished_at.clone()),
+        ("typeName".into(), sidecar.type_name.clone()),
+        ("typeAbbr".into(), sidecar.type_abbr.clone()),
+        ("summary".into(), sidecar.summary.clone()),
+        ("suggestedFileName".into(), sidecar.suggested_file_name.clone()),
+    ];
+    for (index, source) in sidecar.source_documents.iter().enumerate() {
+        columns.push((format!("source{}", index + 1), source.path.clone()));
+        columns.push((format!("source{}Sha256", index + 1), source.sha256.clone()));
+    }
+    for (index, date) in sidecar.dates.iter().enumerate() {
+        columns.push((format!("date{}", index + 1), date.date.clone()));
+        columns.push((format!("date{}Description", index + 1), date.description.clone()));
+    }
+    for (index, part) in sidecar.parts.iter().enumerate() {
+        columns.push((format!("part{}", index + 1), part.file_name.clone()));
+        columns.push((format!("part{}PageRange", index + 1), part.page_range.clone()));
+        columns.push((format!("part{}Sha256", index + 1), part.sha256.clone()));
+        columns.push((format!("part{}Size", index + 1), part.size.to_string()));
+    }
+    for (index, identifier) in sidecar.identifiers.iter().enumerate() {
+        columns.push((
+            format!("identifier{}Kind", index + 1),
+            identifier.kind.field_name().to_string(),
+        ));
+        columns.push((format!("identifier{}", index + 1), identifier.value.clone()));
+        columns.push((format!("identifier{}Valid", index + 1), identifier.valid.to_string()));
+    }
+    for field in &sidecar.custom_fields {
+        columns.push((format!("field.{}", field.name), field.value.clone()));
+    }
+
+    let row = |values: Vec<&str>| {
+        values
+            .into_iter()
+            .map(csv_field)
+            .collect::<Vec<String>>()
+            .join(",")
+    };
+    format!(
+        "{}\r\n{}\r\n",
+        row(columns.iter().map(|(name, _)| name.as_str()).collect()),
+        row(columns.iter().map(|(_, value)| value.as_str()).collect())
+    )
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,726 @@
+use chrono::{DateTime, Utc};
+use cms::{
+    cert::CertificateChoices,
+    content_info::ContentInfo,
+    signed_data::{SignedData, SignerIdentifier},
+};
+use der::{asn1::OctetString, oid::ObjectIdentifier, Decode, Encode, SliceReader};
+use log::{debug, error, warn};
+use lopdf::{Dictionary, Document, Object};
+use p256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+use rsa::{pkcs8::DecodePublicKey, Pkcs1v15Sign, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tauri::AppHandle;
+use x509_cert::{
+    ext::pkix::{BasicConstraints, KeyUsage, SubjectKeyIdentifier},
+    name::Name,
+    spki::SubjectPublicKeyInfoOwned,
+    time::Time,
+    Certificate,
+};
+
+use super::metadata::{parse_pdf_date, read_text};
+use super::models::settings::SignatureSettings;
+use super::models::signatures::{
+    CertificateSummary, SignatureReport, SignatureStatus, ValidityBasis,
+};
+use super::scope::PathScope;
+use super::settings::load_settings;
+
+/// Written to the data directory by the extraction stage.
+const SIGNATURES_FILE_NAME: &str = "signatures.json";
+const TRUST_STORE_EXTENSIONS: [&str; 4] = ["cer", "crt", "der", "pem"];
+/// Longest certificate chain followed, against loops in odd chains.
+const MAX_CHAIN_LENGTH: usize = 10;
+/// Deepest field hierarchy followed in the AcroForm.
+const MAX_FIELD_DEPTH: usize = 32;
+/// Signature formats whose contents are a detached CMS signature, the only
+/// ones ICP-Brasil signers produce.
+const SUPPORTED_SUB_FILTERS: [&str; 2] = ["adbe.pkcs7.detached", "ETSI.CAdES.detached"];
+
+pub const SIGNED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+pub const MESSAGE_DIGEST: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+const SIGNING_TIME: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.5");
+const COMMON_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.3");
+
+pub const SHA1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.14.3.2.26");
+pub const SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+const SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.2");
+const SHA512: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.3");
+
+const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const SHA1_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.5");
+pub const SHA256_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+const SHA384_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.12");
+const SHA512_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.13");
+const EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+pub const ECDSA_WITH_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+const ECDSA_WITH_SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3");
+const ECDSA_WITH_SHA512: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.4");
+const SECP256R1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+
+#[derive(Debug, Clone, Copy)]
+enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn from_digest_oid(oid: &ObjectIdentifier) -> Option<Self> {
+        match *oid {
+            SHA1 => Some(HashAlgorithm::Sha1),
+            SHA256 => Some(HashAlgorithm::Sha256),
+            SHA384 => Some(HashAlgorithm::Sha384),
+            SHA512 => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    /// The hash of a signature algorithm. Some signers name only the key
+    /// algorithm, in which case the digest algorithm applies.
+    fn from_signature_oid(oid: &ObjectIdentifier, digest: Option<Self>) -> Option<Self> {
+        match *oid {
+            SHA1_WITH_RSA => Some(HashAlgorithm::Sha1),
+            SHA256_WITH_RSA | ECDSA_WITH_SHA256 => Some(HashAlgorithm::Sha256),
+            SHA384_WITH_RSA | ECDSA_WITH_SHA384 => Some(HashAlgorithm::Sha384),
+            SHA512_WITH_RSA | ECDSA_WITH_SHA512 => Some(HashAlgorithm::Sha512),
+            RSA_ENCRYPTION | EC_PUBLIC_KEY => digest,
+            _ => None,
+        }
+    }
+
+    fn digest(self, chunks: &[&[u8]]) -> Vec<u8> {
+        fn digest<D: Digest>(chunks: &[&[u8]]) -> Vec<u8> {
+            let mut hasher = D::new();
+            for chunk in chunks {
+                hasher.update(chunk);
+            }
+            hasher.finalize().to_vec()
+        }
+        match self {
+            HashAlgorithm::Sha1 => digest::<Sha1>(chunks),
+            HashAlgorithm::Sha256 => digest::<Sha256>(chunks),
+            HashAlgorithm::Sha384 => digest::<Sha384>(chunks),
+            HashAlgorithm::Sha512 => digest::<Sha512>(chunks),
+        }
+    }
+
+    fn pkcs1v15(self) -> Pkcs1v15Sign {
+        match self {
+            HashAlgorithm::Sha1 => Pkcs1v15Sign::new::<Sha1>(),
+            HashAlgorithm::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+            HashAlgorithm::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+            HashAlgorithm::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+        }
+    }
+}
+
+/// Checks `signature` over `message` with `public_key`: RSA PKCS#1 v1.5 or
+/// ECDSA on P-256. Fails when the key type is not one of those.
+fn verify_signature(
+    public_key: &SubjectPublicKeyInfoOwned,
+    hash: HashAlgorithm,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<bool, String> {
+    let key = public_key
+        .to_der()
+        .map_err(|e| format!("Failed to encode public key: {}", e))?;
+    let hashed = hash.digest(&[message]);
+    match public_key.algorithm.oid {
+        RSA_ENCRYPTION => {
+            let key = RsaPublicKey::from_public_key_der(&key)
+                .map_err(|e| format!("Failed to read RSA key: {}", e))?;
+            Ok(key.verify(hash.pkcs1v15(), &hashed, signature).is_ok())
+        }
+        EC_PUBLIC_KEY => {
+            let curve = public_key
+                .algorithm
+                .parameters
+                .as_ref()
+                .and_then(|parameters| parameters.decode_as::<ObjectIdentifier>().ok());
+            if curve != Some(SECP256R1) {
+                return Err(format!("Unsupported elliptic curve: {:?}", curve));
+            }
+            let key = VerifyingKey::from_public_key_der(&key)
+                .map_err(|e| format!("Failed to read ECDSA key: {}", e))?;
+            Ok(Signature::from_der(signature)
+                .is_ok_and(|signature| key.verify_prehash(&hashed, &signature).is_ok()))
+        }
+        oid => Err(format!("Unsupported key algorithm: {}", oid)),
+    }
+}
+
+/// Whether `issuer` signed `certificate`.
+fn issued_by(certificate: &Certificate, issuer: &Certificate) -> bool {
+    if certificate.tbs_certificate.issuer != issuer.tbs_certificate.subject {
+        return false;
+    }
+    let Some(hash) = HashAlgorithm::from_signature_oid(&certificate.signature_algorithm.oid, None)
+    else {
+        return false;
+    };
+    let Ok(message) = certificate.tbs_certificate.to_der() else {
+        return false;
+    };
+    verify_signature(
+        &issuer.tbs_certificate.subject_public_key_info,
+        hash,
+        &message,
+        certificate.signature.raw_bytes(),
+    )
+    .unwrap_or(false)
+}
+
+pub fn common_name(name: &Name) -> Option<String> {
+    name.0
+        .iter()
+        .flat_map(|names| names.0.iter())
+        .find(|attribute| attribute.oid == COMMON_NAME)
+        .map(|attribute| String::from_utf8_lossy(attribute.value.value()).into_owned())
+}
+
+pub fn format_time(time: Duration) -> String {
+    DateTime::<Utc>::from_timestamp(time.as_secs() as i64, 0)
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_default()
+}
+
+fn summarise(certificate: &Certificate, trusted: bool) -> CertificateSummary {
+    let tbs = &certificate.tbs_certificate;
+    CertificateSummary {
+        subject: tbs.subject.to_string(),
+        issuer: tbs.issuer.to_string(),
+        serial_number: tbs.serial_number.to_string(),
+        not_before: format_time(tbs.validity.not_before.to_unix_duration()),
+        not_after: format_time(tbs.validity.not_after.to_unix_duration()),
+        trusted,
+    }
+}
+
+/// Whether `issuer` may sign certificates with `below` CA certificates under
+/// it: it must be a CA by its basic constraints, allowed to sign certificates
+/// by its key usage, and its path length must allow the chain below.
+fn may_issue(issuer: &Certificate, below: usize) -> Result<(), String> {
+    let subject = &issuer.tbs_certificate.subject;
+    let constraints = issuer
+        .tbs_certificate
+        .get::<BasicConstraints>()
+        .map_err(|e| format!("Malformed basic constraints in {}: {}", subject, e))?;
+    let Some((_, constraints)) = constraints.filter(|(_, constraints)| constraints.ca) else {
+        return Err(format!(
+            "A certificate that is not a certification authority issued another: {}",
+            subject
+        ));
+    };
+    let key_usage = issuer
+        .tbs_certificate
+        .get::<KeyUsage>()
+        .map_err(|e| format!("Malformed key usage in {}: {}", subject, e))?;
+    if key_usage.is_some_and(|(_, key_usage)| !key_usage.key_cert_sign()) {
+        return Err(format!(
+            "A certificate not allowed to sign certificates issued another: {}",
+            subject
+        ));
+    }
+    if constraints
+        .path_len_constraint
+        .is_some_and(|length| below > usize::from(length))
+    {
+        return Err(format!(
+            "The chain below {} is longer than its path length allows",
+            subject
+        ));
+    }
+    Ok(())
+}
+
+/// Follows issuers up from `signer`, preferring the trust store's copy of a
+/// certificate to the one embedded in the signature. Only certification
+/// authorities count as issuers. Returns the chain and whether it reaches a
+/// trusted certificate.
+fn build_chain(
+    signer: &Certificate,
+    embedded: &[Certificate],
+    trust_store: &[Certificate],
+    issues: &mut Vec<String>,
+) -> (Vec<Certificate>, bool) {
+    let mut chain = vec![signer.clone()];
+    while chain.len() < MAX_CHAIN_LENGTH {
+        let current = &chain[chain.len() - 1];
+        if trust_store.contains(current) {
+            return (chain, true);
+        }
+        if current.tbs_certificate.issuer == current.tbs_certificate.subject {
+            issues.push(format!(
+                "The root certificate is not in the trust store: {}",
+                current.tbs_certificate.subject
+            ));
+            return (chain, false);
+        }
+        let issuers: Vec<&Certificate> = trust_store
+            .iter()
+            .chain(embedded)
+            .filter(|candidate| issued_by(current, candidate))
+            .collect();
+        // Certificates below the issuer, other than the signer's.
+        let below = chain.len() - 1;
+        let mut refused = Vec::new();
+        let issuer = issuers
+            .into_iter()
+            .find(|issuer| match may_issue(issuer, below) {
+                Ok(()) => true,
+                Err(reason) => {
+                    refused.push(reason);
+                    false
+                }
+            });
+        match issuer {
+            Some(issuer) => chain.push(issuer.clone()),
+            None if !refused.is_empty() => {
+                issues.extend(refused);
+                return (chain, false);
+            }
+            None => {
+                issues.push(format!(
+                    "No certificate of the signature or the trust store issued {}",
+                    current.tbs_certificate.subject
+                ));
+                return (chain, false);
+            }
+        }
+    }
+    issues.push("The certificate chain is too long".to_string());
+    (chain, false)
+}
+
+/// Byte range of the signature as offset and length pairs, checked against
+/// the file length.
+fn byte_ranges(signature: &Dictionary, length: usize) -> Result<[(usize, usize); 2], String> {
+    let values = signature
+        .get(b"ByteRange")
+        .and_then(Object::as_array)
+        .map_err(|_| "The signature has no byte range".to_string())?
+        .iter()
+        .map(|value| value.as_i64().ok().and_then(|value| usize::try_from(value).ok()))
+        .collect::<Option<Vec<usize>>>()
+        .filter(|values| values.len() == 4)
+        .ok_or_else(|| "The byte range is malformed".to_string())?;
+    let ranges = [(values[0], values[1]), (values[2], values[3])];
+    if ranges
+        .iter()
+        .any(|&(offset, size)| offset.checked_add(size).map_or(true, |end| end > length))
+    {
+        return Err("The byte range extends beyond the end of the file".to_string());
+    }
+    Ok(ranges)
+}
+
+/// Verifies the detached CMS signature in `signature` over the signed bytes
+/// of `bytes`, filling in `report`. Errors mean the signature could not be
+/// checked at all; a signature found not to match is not an error.
+fn check_signature(
+    bytes: &[u8],
+    signature: &Dictionary,
+    trust_store: &[Certificate],
+    report: &mut SignatureReport,
+) -> Result<(), String> {
+    let sub_filter = report.sub_filter.as_deref().unwrap_or_default();
+    if !SUPPORTED_SUB_FILTERS.contains(&sub_filter) {
+        return Err(format!("Signatures of type {:?} are not supported", sub_filter));
+    }
+    let ranges = byte_ranges(signature, bytes.len())?;
+    let [(first_offset, first_size), (second_offset, second_size)] = ranges;
+    // Anything but the hexadecimal signature value left out of the range was
+    // added or changed after signing.
+    let gap = first_offset + first_size..second_offset;
+    report.covers_whole_document = first_offset == 0
+        && second_offset + second_size == bytes.len()
+        && gap.start < gap.end
+        && bytes[gap.start] == b'<'
+        && bytes[gap.end - 1] == b'>';
+
+    // The value is padded with zeros up to the reserved size.
+    let contents = signature
+        .get(b"Contents")
+        .and_then(Object::as_str)
+        .map_err(|_| "The signature has no contents".to_string())?;
+    let content_info = SliceReader::new(contents)
+        .and_then(|mut reader| ContentInfo::decode(&mut reader))
+        .map_err(|e| format!("Failed to read the signature: {}", e))?;
+    if content_info.content_type != SIGNED_DATA {
+        return Err(format!(
+            "Unexpected signature content type: {}",
+            content_info.content_type
+        ));
+    }
+    let signed_data = content_info
+        .content
+        .decode_as::<SignedData>()
+        .map_err(|e| format!("Failed to read the signature: {}", e))?;
+    let signer_info = signed_data
+        .signer_infos
+        .0
+        .iter()
+        .next()
+        .ok_or_else(|| "The signature has no signer".to_string())?;
+
+    let embedded: Vec<Certificate> = signed_data
+        .certificates
+        .iter()
+        .flat_map(|certificates| certificates.0.iter())
+        .filter_map(|choice| match choice {
+            CertificateChoices::Certificate(certificate) => Some(certificate.clone()),
+            CertificateChoices::Other(_) => None,
+        })
+        .collect();
+    let signer = embedded
+        .iter()
+        .find(|certificate| match &signer_info.sid {
+            SignerIdentifier::IssuerAndSerialNumber(id) => {
+                certificate.tbs_certificate.issuer == id.issuer
+                    && certificate.tbs_certificate.serial_number == id.serial_number
+            }
+            SignerIdentifier::SubjectKeyIdentifier(id) => certificate
+                .tbs_certificate
+                .get::<SubjectKeyIdentifier>()
+                .is_ok_and(|extension| extension.is_some_and(|(_, key_id)| key_id == *id)),
+        })
+        .ok_or_else(|| "The signing certificate is not in the signature".to_string())?;
+    if let Some(name) = common_name(&signer.tbs_certificate.subject) {
+        report.signer_name = Some(name);
+    }
+
+    let hash = HashAlgorithm::from_digest_oid(&signer_info.digest_alg.oid).ok_or_else(|| {
+        format!(
+            "Unsupported digest algorithm: {}",
+            signer_info.digest_alg.oid
+        )
+    })?;
+    let signed_bytes = [
+        &bytes[first_offset..first_offset + first_size],
+        &bytes[second_offset..second_offset + second_size],
+    ];
+    let content_digest = hash.digest(&signed_bytes);
+    let mut signing_time = None;
+    let message = match &signer_info.signed_attrs {
+        Some(attributes) => {
+            let message_digest = attributes
+                .iter()
+                .find(|attribute| attribute.oid == MESSAGE_DIGEST)
+                .and_then(|attribute| attribute.values.iter().next())
+                .and_then(|value| value.decode_as::<OctetString>().ok());
+            report.digest_matches = message_digest
+                .is_some_and(|message_digest| message_digest.as_bytes() == content_digest);
+            signing_time = attributes
+                .iter()
+                .find(|attribute| attribute.oid == SIGNING_TIME)
+                .and_then(|attribute| attribute.values.iter().next())
+                .and_then(|value| value.to_der().ok())
+                .and_then(|value| Time::from_der(&value).ok())
+                .map(|time| time.to_unix_duration());
+            attributes
+                .to_der()
+                .map_err(|e| format!("Failed to encode signed attributes: {}", e))?
+        }
+        // Without signed attributes the signature is over the content itself.
+        None => {
+            report.digest_matches = true;
+            signed_bytes.concat()
+        }
+    };
+    if let Some(time) = signing_time {
+        report.signing_time = Some(format_time(time));
+    }
+    if !report.digest_matches {
+        report
+            .issues
+            .push("The signed bytes do not match the digest in the signature".to_string());
+        return Ok(());
+    }
+
+    let signature_hash =
+        HashAlgorithm::from_signature_oid(&signer_info.signature_algorithm.oid, Some(hash))
+            .ok_or_else(|| {
+                format!(
+                    "Unsupported signature algorithm: {}",
+                    signer_info.signature_algorithm.oid
+                )
+            })?;
+    report.signature_valid = verify_signature(
+        &signer.tbs_certificate.subject_public_key_info,
+        signature_hash,
+        &message,
+        signer_info.signature.as_bytes(),
+    )?;
+    if !report.signature_valid {
+        report
+            .issues
+            .push("The signature value does not match the signing certificate".to_string());
+        return Ok(());
+    }
+
+    // Validity is checked at the time the signer claims, as ICP-Brasil
+    // certificates often expire before the documents they signed are filed.
+    // The claim is only as good as the signer, so the report says which time
+    // was used. Revocation is not checked.
+    let claimed = signing_time.or_else(|| {
+        report
+            .signing_time
+            .as_deref()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .and_then(|time| u64::try_from(time.timestamp()).ok())
+            .map(Duration::from_secs)
+    });
+    let (checked_at, basis) = match claimed {
+        Some(time) => (time, ValidityBasis::ClaimedSigningTime),
+        None => (
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default(),
+            ValidityBasis::Now,
+        ),
+    };
+    report.validity_checked_at = Some(format_time(checked_at));
+    report.validity_basis = Some(basis);
+    let (chain, anchored) = build_chain(signer, &embedded, trust_store, &mut report.issues);
+    let mut chain_valid = true;
+    for certificate in &chain {
+        let validity = &certificate.tbs_certificate.validity;
+        if checked_at < validity.not_before.to_unix_duration()
+            || checked_at > validity.not_after.to_unix_duration()
+        {
+            chain_valid = false;
+            report.issues.push(format!(
+                "Certificate not valid at {}: {}",
+                report.validity_checked_at.as_deref().unwrap_or_default(),
+                certificate.tbs_certificate.subject
+            ));
+        }
+    }
+    report.certificate_chain = chain
+        .iter()
+        .map(|certificate| summarise(certificate, trust_store.contains(certificate)))
+        .collect();
+    report.trusted = anchored && chain_valid;
+    Ok(())
+}
+
+fn inspect_signature(
+    bytes: &[u8],
+    field_name: String,
+    signature: &Dictionary,
+    trust_store: &[Certificate],
+) -> SignatureReport {
+    let text = |key: &[u8]| signature.get(key).ok().and_then(read_text);
+    let mut report = SignatureReport {
+        field_name,
+        signer_name: text(b"Name"),
+        signing_time: text(b"M")
+            .and_then(|date| parse_pdf_date(&date))
+            .map(|date| date.to_rfc3339()),
+        reason: text(b"Reason"),
+        location: text(b"Location"),
+        sub_filter: signature
+            .get(b"SubFilter")
+            .and_then(Object::as_name_str)
+            .ok()
+            .map(str::to_string),
+        certificate_chain: Vec::new(),
+        covers_whole_document: false,
+        digest_matches: false,
+        signature_valid: false,
+        trusted: false,
+        validity_checked_at: None,
+        validity_basis: None,
+        status: SignatureStatus::Unsupported,
+        issues: Vec::new(),
+    };
+    let checked = check_signature(bytes, signature, trust_store, &mut report);
+    if let Err(e) = &checked {
+        report.issues.push(e.clone());
+    }
+    if !report.covers_whole_document && report.is_intact() {
+        report
+            .issues
+            .push("The document was changed after it was signed".to_string());
+    }
+    report.status = if checked.is_err() {
+        SignatureStatus::Unsupported
+    } else if !report.is_intact() {
+        SignatureStatus::Invalid
+    } else if !report.covers_whole_document {
+        SignatureStatus::Modified
+    } else if !report.trusted {
+        SignatureStatus::Untrusted
+    } else {
+        SignatureStatus::Valid
+    };
+    report
+}
+
+/// Signed signature fields under `fields`, with their fully qualified names.
+/// The field type may be inherited from a parent field.
+fn collect_signature_fields<'a>(
+    document: &'a Document,
+    fields: &'a [Object],
+    parent_name: &str,
+    parent_is_signature: bool,
+    depth: usize,
+    found: &mut Vec<(String, &'a Dictionary)>,
+) {
+    if depth > MAX_FIELD_DEPTH {
+        return;
+    }
+    for field in fields {
+        let Ok(field) = document.dereference(field).and_then(|(_, field)| field.as_dict()) else {
+            continue;
+        };
+        let name = match field.get(b"T").ok().and_then(read_text) {
+            Some(partial) if parent_name.is_empty() => partial,
+            Some(partial) => format!("{}.{}", parent_name, partial),
+            None => parent_name.to_string(),
+        };
+        let is_signature = match field.get(b"FT").and_then(Object::as_name) {
+            Ok(field_type) => field_type == b"Sig",
+            Err(_) => parent_is_signature,
+        };
+        if is_signature {
+            if let Ok(value) = field.get_deref(b"V", document).and_then(Object::as_dict) {
+                found.push((name.clone(), value));
+            }
+        }
+        if let Ok(kids) = field.get_deref(b"Kids", document).and_then(Object::as_array) {
+            collect_signature_fields(document, kids, &name, is_signature, depth + 1, found);
+        }
+    }
+}
+
+/// Reports on every signed signature field of the PDF at `path`, verified
+/// against the certificates in `trust_store`.
+pub fn inspect_signatures(
+    path: &Path,
+    trust_store: &[Certificate],
+) -> Result<Vec<SignatureReport>, String> {
+    let bytes = fs::read(path).map_err(|e| {
+        error!("Failed to read {}: {}", path.display(), e);
+        format!("Failed to read {}: {}", path.display(), e)
+    })?;
+    let document = Document::load_mem(&bytes)
+        .map_err(|e| format!("Failed to load PDF {}: {}", path.display(), e))?;
+    let Ok(fields) = document
+        .catalog()
+        .and_then(|catalog| catalog.get_deref(b"AcroForm", &document))
+        .and_then(Object::as_dict)
+        .and_then(|form| form.get_deref(b"Fields", &document))
+        .and_then(Object::as_array)
+    else {
+        return Ok(Vec::new());
+    };
+    let mut found = Vec::new();
+    collect_signature_fields(&document, fields, "", false, 0, &mut found);
+    let reports: Vec<SignatureReport> = found
+        .into_iter()
+        .map(|(name, signature)| inspect_signature(&bytes, name, signature, trust_store))
+        .collect();
+    if !reports.is_empty() {
+        debug!("Found {} signature(s) in {}", reports.len(), path.display());
+    }
+    Ok(reports)
+}
+
+/// Certificates in the configured trust store directory. Files that are not
+/// certificates are skipped.
+pub fn load_trust_store(settings: &SignatureSettings) -> Result<Vec<Certificate>, String> {
+    let Some(directory) = settings.trust_store_directory.as_deref() else {
+        return Ok(Vec::new());
+    };
+    let entries = fs::read_dir(directory).map_err(|e| {
+        error!("Failed to read trust store {}: {}", directory, e);
+        format!("Failed to read trust store {}: {}", directory, e)
+    })?;
+    let mut certificates = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_certificate = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                TRUST_STORE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+            });
+        if !is_certificate {
+            continue;
+        }
+        let loaded = fs::read(&path).map_err(|e| e.to_string()).and_then(|content| {
+            if content.starts_with(b"-----BEGIN") {
+                Certificate::load_pem_chain(&content).map_err(|e| e.to_string())
+            } else {
+                Certificate::from_der(&content)
+                    .map(|certificate| vec![certificate])
+                    .map_err(|e| e.to_string())
+            }
+        });
+        match loaded {
+            Ok(loaded) => certificates.extend(loaded),
+            Err(e) => warn!("Skipping trust store file {}: {}", path.display(), e),
+        }
+    }
+    debug!("Loaded {} trusted certificate(s)", certificates.len());
+    Ok(certificates)
+}
+
+/// Inspects the signatures of `path` against the trust store from settings.
+pub fn check_document_signatures(
+    handle: &AppHandle,
+    path: &Path,
+) -> Result<Vec<SignatureReport>, String> {
+    let settings = load_settings(handle)?;
+    let trust_store = load_trust_store(&settings.signatures)?;
+    inspect_signatures(path, &trust_store)
+}
+
+pub fn write_signature_reports(
+    data_directory: &Path,
+    reports: &[SignatureReport],
+) -> Result<(), String> {
+    let path = data_directory.join(SIGNATURES_FILE_NAME);
+    let content = serde_json::to_string_pretty(reports).map_err(|e| {
+        error!("Failed to serialise signature reports: {}", e);
+        format!("Failed to serialise signature reports: {}", e)
+    })?;
+    fs::write(&path, content).map_err(|e| {
+        error!("Failed to write {}: {}", path.display(), e);
+        format!("Failed to write {}: {}", path.display(), e)
+    })
+}
+
+/// Reports written by the extraction stage; none when it has not run.
+pub fn read_signature_reports(data_directory: &Path) -> Result<Vec<SignatureReport>, String> {
+    let path = data_directory.join(SIGNATURES_FILE_NAME);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| {
+        error!("Failed to read {}: {}", path.display(), e);
+        format!("Failed to read {}: {}", path.display(), e)
+    })?;
+    serde_json::from_str(&content).map_err(|e| {
+        error!("Failed to parse {}: {}", path.display(), e);
+        format!("Failed to parse {}: {}", path.display(), e)
+    })
+}
+
+#[tauri::command]
+pub fn verify_document_signatures(
+    handle: AppHandle,
+    document_path: String,
+) -> Result<Vec<SignatureReport>, String> {
+    let document_path = PathScope::current(&handle)?.check(&document_path)?;
+    check_document_signatures(&handle, &document_path)
+}
@@ -0,0 +1,1408 @@
+use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, InnerIvInit, KeyIvInit};
+use chrono::{DateTime, FixedOffset, Local};
+use cms::{
+    cert::{CertificateChoices, IssuerAndSerialNumber},
+    content_info::{CmsVersion, ContentInfo},
+    encrypted_data::EncryptedData,
+    signed_data::{
+        CertificateSet, EncapsulatedContentInfo, SignedData, SignerIdentifier, SignerInfo,
+        SignerInfos,
+    },
+};
+use der::{
+    asn1::{GeneralizedTime, OctetString, SetOfVec},
+    oid::ObjectIdentifier,
+    Any, AnyRef, Decode, Encode, Sequence, Tag, Tagged,
+};
+use hmac::{Mac, SimpleHmac};
+use log::{debug, error};
+use lopdf::{
+    dictionary, Dictionary, Document, IncrementalDocument, Object, ObjectId, Stream, StringFormat,
+};
+use p256::ecdsa::{signature::Signer as _, DerSignature, SigningKey};
+use pkcs12::{
+    cert_type::CertBag,
+    kdf::{derive_key_utf8, Pkcs12KeyType},
+    mac_data::MacData,
+    pbe_params::{EncryptedPrivateKeyInfo, Pkcs12PbeParams},
+    pfx::Pfx,
+    safe_bag::{SafeBag, SafeContents},
+    PKCS_12_CERT_BAG_OID, PKCS_12_KEY_BAG_OID, PKCS_12_PBEWITH_SHAAND40_BIT_RC2_CBC,
+    PKCS_12_PBE_WITH_SHAAND128_BIT_RC2_CBC, PKCS_12_PBE_WITH_SHAAND3_KEY_TRIPLE_DES_CBC,
+    PKCS_12_PKCS8_KEY_BAG_OID, PKCS_12_X509_CERT_OID,
+};
+use rsa::{
+    pkcs8::{DecodePrivateKey, EncodePublicKey},
+    Pkcs1v15Sign, RsaPrivateKey,
+};
+use sha1::Sha1;
+use sha2::{
+    digest::{core_api::BlockSizeUser, FixedOutputReset},
+    Digest, Sha256,
+};
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tauri::AppHandle;
+use tauri_plugin_http::reqwest;
+use uuid::Uuid;
+use x509_cert::{attr::Attribute, spki::AlgorithmIdentifierOwned, Certificate};
+
+use super::metadata::{pdf_date, read_text, text_string};
+use super::models::settings::SignatureSettings;
+use super::models::signatures::{SignatureStamp, SigningOptions, SigningReport};
+use super::ocr::MAGICK_UTILITY;
+use super::signatures::{
+    common_name, format_time, inspect_signatures, ECDSA_WITH_SHA256, MESSAGE_DIGEST, SHA1, SHA256,
+    SHA256_WITH_RSA, SIGNED_DATA,
+};
+use super::utilities::call_utility;
+
+const DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1");
+const ENCRYPTED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.6");
+const CONTENT_TYPE: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.3");
+const SIGNING_CERTIFICATE_V2: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.16.2.47");
+const TIME_STAMP_TOKEN: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.16.2.14");
+const PBES2: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.5.13");
+
+/// Space reserved for the signature besides the certificates it embeds.
+const SIGNATURE_RESERVE: usize = 8192;
+/// Extra space reserved for a timestamp token, which embeds the authority's
+/// own certificates.
+const TIMESTAMP_RESERVE: usize = 12288;
+/// Written in the byte range until the real offsets are known; as wide as any
+/// offset they are replaced with.
+const BYTE_RANGE_PLACEHOLDER: i64 = 9_999_999_999;
+const TIMESTAMP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Resolution the visible stamp is rendered at.
+const STAMP_DENSITY: f64 = 200.0;
+const WRONG_PASSWORD: &str = "Wrong password for the certificate file";
+
+/// `ESSCertIDv2` of RFC 5035, with the default SHA-256 hash algorithm.
+#[derive(Sequence)]
+struct EssCertIdV2 {
+    cert_hash: OctetString,
+}
+
+/// `SigningCertificateV2` of RFC 5035, which binds the signer's certificate
+/// to the signature as PAdES requires.
+#[derive(Sequence)]
+struct SigningCertificateV2 {
+    certs: Vec<EssCertIdV2>,
+}
+
+/// `MessageImprint` of RFC 3161.
+#[derive(Sequence)]
+struct MessageImprint {
+    hash_algorithm: AlgorithmIdentifierOwned,
+    hashed_message: OctetString,
+}
+
+/// `TimeStampReq` of RFC 3161, without policy or extensions.
+#[derive(Sequence)]
+struct TimeStampRequest {
+    version: u8,
+    message_imprint: MessageImprint,
+    nonce: u64,
+    cert_req: bool,
+}
+
+/// 8-bit grey pixels with their width and height.
+type GreyImage = (Vec<u8>, u32, u32);
+
+enum PrivateKey {
+    Rsa(Box<RsaPrivateKey>),
+    Ecdsa(SigningKey),
+}
+
+impl PrivateKey {
+    fn public_key_der(&self) -> Result<Vec<u8>, String> {
+        let encoded = match self {
+            PrivateKey::Rsa(key) => key.to_public_key().to_public_key_der(),
+            PrivateKey::Ecdsa(key) => key.verifying_key().to_public_key_der(),
+        };
+        encoded
+            .map(|document| document.as_bytes().to_vec())
+            .map_err(|e| format!("Failed to encode public key: {}", e))
+    }
+
+    fn signature_algorithm(&self) -> AlgorithmIdentifierOwned {
+        match self {
+            PrivateKey::Rsa(_) => AlgorithmIdentifierOwned {
+                oid: SHA256_WITH_RSA,
+                parameters: Some(Any::null()),
+            },
+            PrivateKey::Ecdsa(_) => AlgorithmIdentifierOwned {
+                oid: ECDSA_WITH_SHA256,
+                parameters: None,
+            },
+        }
+    }
+
+    /// Signs `message` with SHA-256.
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            PrivateKey::Rsa(key) => key
+                .sign(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(message))
+                .map_err(|e| format!("Failed to sign: {}", e)),
+            PrivateKey::Ecdsa(key) => {
+                let signature: DerSignature = key.sign(message);
+                Ok(signature.as_bytes().to_vec())
+            }
+        }
+    }
+}
+
+/// A key and certificate loaded from the configured PKCS#12 file, ready to
+/// sign any number of documents.
+pub struct Signer {
+    key: PrivateKey,
+    /// The signer's certificate first, then the rest of the file's.
+    certificates: Vec<Certificate>,
+    signer_name: String,
+    timestamp_url: Option<String>,
+    reason: Option<String>,
+    location: Option<String>,
+}
+
+fn sha256(data: &[u8]) -> Vec<u8> {
+    Sha256::digest(data).to_vec()
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+fn pkcs12_mac<D>(password: &str, mac_data: &MacData, data: &[u8]) -> Result<Vec<u8>, String>
+where
+    D: Digest + FixedOutputReset + BlockSizeUser,
+{
+    let key = derive_key_utf8::<D>(
+        password,
+        mac_data.mac_salt.as_bytes(),
+        Pkcs12KeyType::Mac,
+        mac_data.iterations,
+        <D as Digest>::output_size(),
+    )
+    .map_err(|e| format!("Failed to derive the certificate file key: {}", e))?;
+    let mut mac = <SimpleHmac<D> as Mac>::new_from_slice(&key)
+        .map_err(|e| format!("Failed to check the certificate file: {}", e))?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+/// Checks the password against the integrity check of the file, which tells
+/// a wrong password apart from a damaged file.
+fn verify_mac(mac_data: &MacData, password: &str, data: &[u8]) -> Result<(), String> {
+    let computed = match mac_data.mac.algorithm.oid {
+        SHA1 => pkcs12_mac::<Sha1>(password, mac_data, data)?,
+        SHA256 => pkcs12_mac::<Sha256>(password, mac_data, data)?,
+        oid => {
+            return Err(format!(
+                "Unsupported integrity check in the certificate file: {}",
+                oid
+            ))
+        }
+    };
+    if computed != mac_data.mac.digest.as_bytes() {
+        return Err(WRONG_PASSWORD.to_string());
+    }
+    Ok(())
+}
+
+/// Decrypts part of a PKCS#12 file: PBES2, as current exports use, or the
+/// legacy triple DES and RC2 of older ones.
+fn decrypt(
+    algorithm: &AlgorithmIdentifierOwned,
+    data: &[u8],
+    password: &str,
+) -> Result<Vec<u8>, String> {
+    match algorithm.oid {
+        PBES2 => {
+            let encoded = algorithm
+                .to_der()
+                .map_err(|e| format!("Failed to read the certificate file encryption: {}", e))?;
+            let scheme = pkcs5::EncryptionScheme::try_from(encoded.as_slice())
+                .map_err(|e| format!("Unsupported certificate file encryption: {}", e))?;
+            scheme
+                .decrypt(password, data)
+                .map_err(|_| WRONG_PASSWORD.to_string())
+        }
+        PKCS_12_PBE_WITH_SHAAND3_KEY_TRIPLE_DES_CBC
+        | PKCS_12_PBE_WITH_SHAAND128_BIT_RC2_CBC
+        | PKCS_12_PBEWITH_SHAAND40_BIT_RC2_CBC => {
+            let parameters = algorithm
+                .parameters
+                .as_ref()
+                .ok_or_else(|| "The certificate file encryption has no parameters".to_string())?
+                .decode_as::<Pkcs12PbeParams>()
+                .map_err(|e| format!("Failed to read the certificate file encryption: {}", e))?;
+            let derive = |kind, length| {
+                derive_key_utf8::<Sha1>(
+                    password,
+                    parameters.salt.as_bytes(),
+                    kind,
+                    parameters.iterations,
+                    length,
+                )
+                .map_err(|e| format!("Failed to derive the certificate file key: {}", e))
+            };
+            let iv = derive(Pkcs12KeyType::Iv, 8)?;
+            let decrypted = match algorithm.oid {
+                PKCS_12_PBE_WITH_SHAAND3_KEY_TRIPLE_DES_CBC => {
+                    let key = derive(Pkcs12KeyType::EncryptionKey, 24)?;
+                    cbc::Decryptor::<des::TdesEde3>::new_from_slices(&key, &iv)
+                        .map_err(|e| format!("Failed to decrypt the certificate file: {}", e))?
+                        .decrypt_padded_vec_mut::<Pkcs7>(data)
+                }
+                // Older exports encrypt the certificates with RC2, whose
+                // effective key length is the key length here.
+                oid => {
+                    let length = if oid == PKCS_12_PBEWITH_SHAAND40_BIT_RC2_CBC {
+                        5
+                    } else {
+                        16
+                    };
+                    let key = derive(Pkcs12KeyType::EncryptionKey, length)?;
+                    cbc::Decryptor::inner_iv_slice_init(
+                        rc2::Rc2::new_with_eff_key_len(&key, length * 8),
+                        &iv,
+                    )
+                    .map_err(|e| format!("Failed to decrypt the certificate file: {}", e))?
+                    .decrypt_padded_vec_mut::<Pkcs7>(data)
+                }
+            };
+            decrypted.map_err(|_| WRONG_PASSWORD.to_string())
+        }
+        oid => Err(format!(
+            "Unsupported certificate file encryption ({}); export the certificate again with AES",
+            oid
+        )),
+    }
+}
+
+fn read_private_key(der: &[u8]) -> Result<PrivateKey, String> {
+    if let Ok(key) = RsaPrivateKey::from_pkcs8_der(der) {
+        return Ok(PrivateKey::Rsa(Box::new(key)));
+    }
+    SigningKey::from_pkcs8_der(der)
+        .map(PrivateKey::Ecdsa)
+        .map_err(|_| "The private key is neither RSA nor ECDSA on P-256".to_string())
+}
+
+fn read_bag(
+    bag: &SafeBag,
+    password: &str,
+    keys: &mut Vec<PrivateKey>,
+    certificates: &mut Vec<Certificate>,
+) -> Result<(), String> {
+    let read_error = |e: der::Error| format!("Failed to read the certificate file: {}", e);
+    // The value still carries its explicit tag.
+    let value = AnyRef::from_der(&bag.bag_value).map_err(read_error)?;
+    match bag.bag_id {
+        PKCS_12_PKCS8_KEY_BAG_OID => {
+            let info = EncryptedPrivateKeyInfo::from_der(value.value()).map_err(read_error)?;
+            let key = decrypt(
+                &info.encryption_algorithm,
+                info.encrypted_data.as_bytes(),
+                password,
+            )?;
+            keys.push(read_private_key(&key)?);
+        }
+        PKCS_12_KEY_BAG_OID => keys.push(read_private_key(value.value())?),
+        PKCS_12_CERT_BAG_OID => {
+            let bag = CertBag::from_der(value.value()).map_err(read_error)?;
+            if bag.cert_id == PKCS_12_X509_CERT_OID {
+                certificates
+                    .push(Certificate::from_der(bag.cert_value.as_bytes()).map_err(read_error)?);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The private key of a PKCS#12 file and its certificates, the one matching
+/// the key first.
+fn load_credentials(path: &Path, password: &str) -> Result<(PrivateKey, Vec<Certificate>), String> {
+    let content = fs::read(path).map_err(|e| {
+        error!("Failed to read {}: {}", path.display(), e);
+        format!("Failed to read {}: {}", path.display(), e)
+    })?;
+    let read_error = |e: der::Error| format!("Failed to read {}: {}", path.display(), e);
+    let pfx = Pfx::from_der(&content).map_err(read_error)?;
+    if pfx.auth_safe.content_type != DATA {
+        return Err("Certificate files protected with a public key are not supported".to_string());
+    }
+    let auth_safe = pfx
+        .auth_safe
+        .content
+        .decode_as::<OctetString>()
+        .map_err(read_error)?;
+    if let Some(mac_data) = &pfx.mac_data {
+        verify_mac(mac_data, password, auth_safe.as_bytes())?;
+    }
+
+    let mut keys = Vec::new();
+    let mut certificates = Vec::new();
+    for content_info in Vec::<ContentInfo>::from_der(auth_safe.as_bytes()).map_err(read_error)? {
+        let safe_contents = match content_info.content_type {
+            DATA => content_info
+                .content
+                .decode_as::<OctetString>()
+                .map_err(read_error)?
+                .into_bytes(),
+            ENCRYPTED_DATA => {
+                let encrypted = content_info
+                    .content
+                    .decode_as::<EncryptedData>()
+                    .map_err(read_error)?
+                    .enc_content_info;
+                let data = encrypted
+                    .encrypted_content
+                    .ok_or_else(|| format!("Empty encrypted content in {}", path.display()))?;
+                decrypt(&encrypted.content_enc_alg, data.as_bytes(), password)?
+            }
+            oid => {
+                return Err(format!(
+                    "Unsupported content in {}: {}",
+                    path.display(),
+                    oid
+                ))
+            }
+        };
+        for bag in SafeContents::from_der(&safe_contents).map_err(read_error)? {
+            read_bag(&bag, password, &mut keys, &mut certificates)?;
+        }
+    }
+
+    let key = keys
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("{} has no private key", path.display()))?;
+    let public_key = key.public_key_der()?;
+    let position = certificates
+        .iter()
+        .position(|certificate| {
+            certificate
+                .tbs_certificate
+                .subject_public_key_info
+                .to_der()
+                .is_ok_and(|encoded| encoded == public_key)
+        })
+        .ok_or_else(|| {
+            format!(
+                "No certificate in {} matches its private key",
+                path.display()
+            )
+        })?;
+    let signer = certificates.remove(position);
+    certificates.insert(0, signer);
+    Ok((key, certificates))
+}
+
+fn attribute(oid: ObjectIdentifier, value: Any) -> Result<Attribute, String> {
+    Ok(Attribute {
+        oid,
+        values: SetOfVec::try_from(vec![value])
+            .map_err(|e| format!("Failed to encode attribute {}: {}", oid, e))?,
+    })
+}
+
+fn encode_error(e: der::Error) -> String {
+    format!("Failed to encode the signature: {}", e)
+}
+
+/// Asks the timestamp authority at `url` to certify `digest`, the SHA-256 of
+/// a signature value. Returns the token and the certified time.
+async fn request_timestamp(url: &str, digest: &[u8]) -> Result<(Any, Duration), String> {
+    let nonce = Uuid::new_v4().as_u64_pair().0 >> 1;
+    let request = TimeStampRequest {
+        version: 1,
+        message_imprint: MessageImprint {
+            hash_algorithm: AlgorithmIdentifierOwned {
+                oid: SHA256,
+                parameters: None,
+            },
+            hashed_message: OctetString::new(digest).map_err(encode_error)?,
+        },
+        nonce,
+        cert_req: true,
+    }
+    .to_der()
+    .map_err(encode_error)?;
+    let response = reqwest::Client::new()
+        .post(url)
+        .header("Content-Type", "application/timestamp-query")
+        .body(request)
+        .timeout(TIMESTAMP_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| {
+            error!("Timestamp request to {} failed: {}", url, e);
+            format!("Timestamp request to {} failed: {}", url, e)
+        })?
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read the timestamp response: {}", e))?
+        .to_vec();
+
+    let read_error = |e: der::Error| format!("Failed to read the timestamp response: {}", e);
+    let response = Vec::<Any>::from_der(&response).map_err(read_error)?;
+    // PKIStatus 0 is granted and 1 granted with modifications.
+    let status = response
+        .first()
+        .and_then(|status| status.decode_as::<Vec<Any>>().ok())
+        .and_then(|status| {
+            status
+                .first()
+                .and_then(|value| value.decode_as::<u8>().ok())
+        });
+    if !matches!(status, Some(0 | 1)) {
+        return Err(format!(
+            "The timestamp authority refused the request (status {:?})",
+            status
+        ));
+    }
+    let token = response
+        .get(1)
+        .ok_or_else(|| "The timestamp response has no token".to_string())?;
+    let content_info = token.decode_as::<ContentInfo>().map_err(read_error)?;
+    if content_info.content_type != SIGNED_DATA {
+        return Err("The timestamp token is not signed data".to_string());
+    }
+    let info = content_info
+        .content
+        .decode_as::<SignedData>()
+        .map_err(read_error)?
+        .encap_content_info
+        .econtent
+        .ok_or_else(|| "The timestamp token has no content".to_string())?
+        .decode_as::<OctetString>()
+        .map_err(read_error)?;
+    // TSTInfo: version, policy, messageImprint, serialNumber, genTime, then
+    // optional fields among which the nonce is the only integer.
+    let info = Vec::<Any>::from_der(info.as_bytes()).map_err(read_error)?;
+    let imprint = info
+        .get(2)
+        .and_then(|imprint| imprint.decode_as::<MessageImprint>().ok())
+        .ok_or_else(|| "The timestamp token has no message imprint".to_string())?;
+    if imprint.hashed_message.as_bytes() != digest {
+        return Err("The timestamp token certifies a different signature".to_string());
+    }
+    let echoed_nonce = info
+        .iter()
+        .skip(5)
+        .find(|field| field.tag() == Tag::Integer)
+        .and_then(|field| field.decode_as::<u64>().ok());
+    if echoed_nonce != Some(nonce) {
+        return Err("The timestamp token does not answer this request".to_string());
+    }
+    let time = info
+        .get(4)
+        .and_then(|time| time.decode_as::<GeneralizedTime>().ok())
+        .ok_or_else(|| "The timestamp token has no time".to_string())?;
+    Ok((token.clone(), time.to_unix_duration()))
+}
+
+/// Writes `text` on a white box of `width` by `height` points and returns the
+/// 8-bit grey pixels with their dimensions.
+async fn render_stamp(
+    handle: &AppHandle,
+    text: &str,
+    width: f64,
+    height: f64,
+    scratch: &Path,
+) -> Result<GreyImage, String> {
+    let pixels = |points: f64| ((points * STAMP_DENSITY / 72.0).round() as u32).max(1);
+    let (pixel_width, pixel_height) = (pixels(width), pixels(height));
+    let margin = (pixel_height / 10).max(1);
+    let args = vec![
+        "-size".to_owned(),
+        format!(
+            "{}x{}",
+            pixel_width.saturating_sub(2 * margin).max(1),
+            pixel_height.saturating_sub(2 * margin).max(1)
+        ),
+        "-background".to_owned(),
+        "white".to_owned(),
+        "-fill".to_owned(),
+        "black".to_owned(),
+        "-gravity".to_owned(),
+        "West".to_owned(),
+        // `%` starts an escape in caption text.
+        format!("caption:{}", text.replace('%', "%%")),
+        "-gravity".to_owned(),
+        "center".to_owned(),
+        "-extent".to_owned(),
+        format!("{}x{}", pixel_width, pixel_height),
+        "-depth".to_owned(),
+        "8".to_owned(),
+        format!("gray:{}", scratch.display()),
+    ];
+    if !call_utility(handle.clone(), MAGICK_UTILITY.to_owned(), args, false).await {
+        error!("Failed to render the signature stamp");
+        return Err("Failed to render the signature stamp".to_string());
+    }
+    let pixels =
+        fs::read(scratch).map_err(|e| format!("Failed to read {}: {}", scratch.display(), e))?;
+    if pixels.len() != (pixel_width * pixel_height) as usize {
+        return Err("The rendered signature stamp has an unexpected size".to_string());
+    }
+    Ok((pixels, pixel_width, pixel_height))
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| position + from)
+}
+
+/// A page attribute, looked up through the page tree if the page inherits it.
+pub fn inherited<'a>(document: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut node = document.get_dictionary(page_id).ok()?;
+    for _ in 0..32 {
+        if let Ok(value) = node.get(key) {
+            return Some(value);
+        }
+        node = node
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|parent| document.get_dictionary(parent))
+            .ok()?;
+    }
+    None
+}
+
+/// Where a visible signature goes on its page.
+struct Placement {
+    /// Widget rectangle in PDF user space.
+    rectangle: [f64; 4],
+    /// Size of the stamp as the page is displayed, in points.
+    width: f64,
+    height: f64,
+    /// Clockwise rotation of the page when displayed, in degrees.
+    rotation: i64,
+}
+
+/// Maps `stamp`, given on the page as displayed, to user space through the
+/// page rotation.
+fn place_stamp(document: &Document, page_id: ObjectId, stamp: &SignatureStamp) -> Placement {
+    let media_box: Vec<f64> = inherited(document, page_id, b"MediaBox")
+        .and_then(|media_box| document.dereference(media_box).ok())
+        .and_then(|(_, media_box)| media_box.as_array().ok())
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(|value| value.as_float().ok().map(f64::from))
+                .collect()
+        })
+        .filter(|values: &Vec<f64>| values.len() == 4)
+        .unwrap_or_else(|| vec![0.0, 0.0, 612.0, 792.0]);
+    let [left, bottom, right, top] = [media_box[0], media_box[1], media_box[2], media_box[3]];
+    let rotation = inherited(document, page_id, b"Rotate")
+        .and_then(|rotation| rotation.as_i64().ok())
+        .unwrap_or(0)
+        .rem_euclid(360);
+    let (page_width, page_height) = match rotation {
+        90 | 270 => (top - bottom, right - left),
+        _ => (right - left, top - bottom),
+    };
+    let x = stamp.x.clamp(0.0, 1.0) * page_width;
+    let y = stamp.y.clamp(0.0, 1.0) * page_height;
+    let width = (stamp.width.clamp(0.0, 1.0) * page_width).min(page_width - x);
+    let height = (stamp.height.clamp(0.0, 1.0) * page_height).min(page_height - y);
+    // A point at (x, y) from the displayed top left corner, in user space.
+    let to_user = |x: f64, y: f64| match rotation {
+        90 => (left + y, bottom + x),
+        180 => (right - x, bottom + y),
+        270 => (right - y, top - x),
+        _ => (left + x, top - y),
+    };
+    let (x1, y1) = to_user(x, y);
+    let (x2, y2) = to_user(x + width, y + height);
+    Placement {
+        rectangle: [x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)],
+        width,
+        height,
+        rotation,
+    }
+}
+
+/// Appearance of a visible signature: the rendered text inside a frame.
+fn stamp_appearance(
+    update: &mut IncrementalDocument,
+    (pixels, pixel_width, pixel_height): GreyImage,
+    placement: &Placement,
+) -> Result<ObjectId, String> {
+    let (width, height) = (placement.width, placement.height);
+    let mut image = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => pixel_width as i64,
+            "Height" => pixel_height as i64,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 8,
+        },
+        pixels,
+    );
+    image
+        .compress()
+        .map_err(|e| format!("Failed to compress the signature stamp: {}", e))?;
+    let image_id = update.new_document.add_object(image);
+    let content = format!(
+        "q {w:.2} 0 0 {h:.2} 0 0 cm /Im0 Do Q\nq 0.5 w 0 0 0 RG 0.25 0.25 {fw:.2} {fh:.2} re S Q\n",
+        w = width,
+        h = height,
+        fw = (width - 0.5).max(0.0),
+        fh = (height - 0.5).max(0.0),
+    );
+    let form = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), Object::Real(width as f32), Object::Real(height as f32)],
+            // Turns the stamp against the page rotation so it reads upright.
+            "Matrix" => match placement.rotation {
+                90 => vec![0.into(), 1.into(), (-1).into(), 0.into(), 0.into(), 0.into()],
+                180 => vec![(-1).into(), 0.into(), 0.into(), (-1).into(), 0.into(), 0.into()],
+                270 => vec![0.into(), (-1).into(), 1.into(), 0.into(), 0.into(), 0.into()],
+                _ => vec![1.into(), 0.into(), 0.into(), 1.into(), 0.into(), 0.into()],
+            },
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Im0" => image_id },
+            },
+        },
+        content.into_bytes(),
+    );
+    Ok(update.new_document.add_object(form))
+}
+
+/// Adds `widget_id` to the AcroForm fields, creating the form if needed, and
+/// returns a field name no top-level field uses yet.
+fn add_signature_field(
+    update: &mut IncrementalDocument,
+    widget_id: ObjectId,
+) -> Result<String, String> {
+    let previous = update.get_prev_documents();
+    let catalog_id = previous
+        .trailer
+        .get(b"Root")
+        .and_then(Object::as_reference)
+        .map_err(|_| "The document has no catalog".to_string())?;
+    let mut catalog = previous
+        .get_dictionary(catalog_id)
+        .map_err(|e| format!("Failed to read the catalog: {}", e))?
+        .clone();
+    let (form_id, mut form) = match catalog.get(b"AcroForm") {
+        Ok(Object::Reference(id)) => (
+            Some(*id),
+            previous
+                .get_dictionary(*id)
+                .map_err(|e| format!("Failed to read the form: {}", e))?
+                .clone(),
+        ),
+        Ok(Object::Dictionary(form)) => (None, form.clone()),
+        _ => (None, Dictionary::new()),
+    };
+    let (fields_id, mut fields) = match form.get(b"Fields") {
+        Ok(Object::Reference(id)) => (
+            Some(*id),
+            previous
+                .get_object(*id)
+                .and_then(Object::as_array)
+                .map_err(|e| format!("Failed to read the form fields: {}", e))?
+                .clone(),
+        ),
+        Ok(Object::Array(fields)) => (None, fields.clone()),
+        _ => (None, Vec::new()),
+    };
+
+    let names: Vec<String> = fields
+        .iter()
+        .filter_map(|field| previous.dereference(field).ok())
+        .filter_map(|(_, field)| field.as_dict().ok())
+        .filter_map(|field| field.get(b"T").ok().and_then(read_text))
+        .collect();
+    let name = (fields.len() + 1..)
+        .map(|number| format!("Assinatura{}", number))
+        .find(|name| !names.contains(name))
+        .unwrap_or_default();
+
+    fields.push(Object::Reference(widget_id));
+    match fields_id {
+        Some(id) => update.new_document.set_object(id, fields),
+        None => form.set("Fields", fields),
+    }
+    // SignaturesExist and AppendOnly.
+    form.set("SigFlags", 3);
+    match form_id {
+        Some(id) => update.new_document.set_object(id, form),
+        None => {
+            catalog.set("AcroForm", form);
+            update.new_document.set_object(catalog_id, catalog);
+        }
+    }
+    Ok(name)
+}
+
+fn add_annotation(
+    update: &mut IncrementalDocument,
+    page_id: ObjectId,
+    widget_id: ObjectId,
+) -> Result<(), String> {
+    let previous = update.get_prev_documents();
+    let mut page = previous
+        .get_dictionary(page_id)
+        .map_err(|e| format!("Failed to read the page: {}", e))?
+        .clone();
+    match page.get(b"Annots") {
+        Ok(Object::Reference(id)) => {
+            let id = *id;
+            let mut annotations = previous
+                .get_object(id)
+                .and_then(Object::as_array)
+                .map_err(|e| format!("Failed to read the page annotations: {}", e))?
+                .clone();
+            annotations.push(Object::Reference(widget_id));
+            update.new_document.set_object(id, annotations);
+        }
+        Ok(Object::Array(annotations)) => {
+            let mut annotations = annotations.clone();
+            annotations.push(Object::Reference(widget_id));
+            page.set("Annots", annotations);
+            update.new_document.set_object(page_id, page);
+        }
+        _ => {
+            page.set("Annots", vec![Object::Reference(widget_id)]);
+            update.new_document.set_object(page_id, page);
+        }
+    }
+    Ok(())
+}
+
+impl Signer {
+    /// Opens the PKCS#12 file from settings with the password of `options`.
+    /// Fails early on a wrong password or an expired certificate, before any
+    /// document is processed.
+    pub fn load(settings: &SignatureSettings, options: &SigningOptions) -> Result<Self, String> {
+        let path = settings
+            .certificate_path
+            .as_deref()
+            .ok_or_else(|| "No signing certificate is configured".to_string())?;
+        let (key, certificates) = load_credentials(Path::new(path), &options.password)?;
+        let validity = &certificates[0].tbs_certificate.validity;
+        let now = now();
+        if now < validity.not_before.to_unix_duration() {
+            return Err(format!(
+                "The signing certificate is not valid before {}",
+                format_time(validity.not_before.to_unix_duration())
+            ));
+        }
+        if now > validity.not_after.to_unix_duration() {
+            return Err(format!(
+                "The signing certificate expired on {}",
+                format_time(validity.not_after.to_unix_duration())
+            ));
+        }
+        let subject = &certificates[0].tbs_certificate.subject;
+        let signer_name = common_name(subject).unwrap_or_else(|| subject.to_string());
+        debug!("Loaded signing certificate of {}", signer_name);
+        Ok(Signer {
+            key,
+            certificates,
+            signer_name,
+            timestamp_url: settings.timestamp_url.clone(),
+            reason: options.reason.clone().or_else(|| settings.reason.clone()),
+            location: settings.location.clone(),
+        })
+    }
+
+    /// The detached CAdES signature of `digest`, with a timestamp on the
+    /// signature value when an authority is configured.
+    async fn build_signature(&self, digest: &[u8]) -> Result<(Vec<u8>, Option<Duration>), String> {
+        let certificate = &self.certificates[0];
+        let signing_certificate = SigningCertificateV2 {
+            certs: vec![EssCertIdV2 {
+                cert_hash: OctetString::new(sha256(&certificate.to_der().map_err(encode_error)?))
+                    .map_err(encode_error)?,
+            }],
+        };
+        let signed_attributes = SetOfVec::try_from(vec![
+            attribute(CONTENT_TYPE, Any::encode_from(&DATA).map_err(encode_error)?)?,
+            attribute(
+                MESSAGE_DIGEST,
+                Any::encode_from(&OctetString::new(digest).map_err(encode_error)?)
+                    .map_err(encode_error)?,
+            )?,
+            attribute(
+                SIGNING_CERTIFICATE_V2,
+                Any::encode_from(&signing_certificate).map_err(encode_error)?,
+            )?,
+        ])
+        .map_err(encode_error)?;
+        let signature = self
+            .key
+            .sign(&signed_attributes.to_der().map_err(encode_error)?)?;
+
+        let mut timestamp_time = None;
+        let unsigned_attributes = match &self.timestamp_url {
+            Some(url) => {
+                let (token, time) = request_timestamp(url, &sha256(&signature)).await?;
+                timestamp_time = Some(time);
+                Some(
+                    SetOfVec::try_from(vec![attribute(TIME_STAMP_TOKEN, token)?])
+                        .map_err(encode_error)?,
+                )
+            }
+            None => None,
+        };
+
+        let digest_algorithm = AlgorithmIdentifierOwned {
+            oid: SHA256,
+            parameters: None,
+        };
+        let signer_info = SignerInfo {
+            version: CmsVersion::V1,
+            sid: SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+                issuer: certificate.tbs_certificate.issuer.clone(),
+                serial_number: certificate.tbs_certificate.serial_number.clone(),
+            }),
+            digest_alg: digest_algorithm.clone(),
+            signed_attrs: Some(signed_attributes),
+            signature_algorithm: self.key.signature_algorithm(),
+            signature: OctetString::new(signature).map_err(encode_error)?,
+            unsigned_attrs: unsigned_attributes,
+        };
+        let signed_data = SignedData {
+            version: CmsVersion::V1,
+            digest_algorithms: SetOfVec::try_from(vec![digest_algorithm]).map_err(encode_error)?,
+            encap_content_info: EncapsulatedContentInfo {
+                econtent_type: DATA,
+                econtent: None,
+            },
+            certificates: Some(CertificateSet(
+                SetOfVec::try_from(
+                    self.certificates
+                        .iter()
+                        .cloned()
+                        .map(CertificateChoices::Certificate)
+                        .collect::<Vec<_>>(),
+                )
+                .map_err(encode_error)?,
+            )),
+            crls: None,
+            signer_infos: SignerInfos(SetOfVec::try_from(vec![signer_info]).map_err(encode_error)?),
+        };
+        let content_info = ContentInfo {
+            content_type: SIGNED_DATA,
+            content: Any::encode_from(&signed_data).map_err(encode_error)?,
+        };
+        Ok((content_info.to_der().map_err(encode_error)?, timestamp_time))
+    }
+
+    fn stamp_text(&self, signed_at: &DateTime<FixedOffset>) -> String {
+        // ICP-Brasil names end in the holder's CPF, which the stamp leaves out.
+        let name = self
+            .signer_name
+            .split_once(':')
+            .map_or(self.signer_name.as_str(), |(name, _)| name);
+        let mut lines = vec![
+            "Assinado digitalmente por".to_string(),
+            name.to_string(),
+            format!("Data: {}", signed_at.format("%d/%m/%Y %H:%M:%S %:z")),
+        ];
+        if let Some(reason) = &self.reason {
+            lines.push(format!("Motivo: {}", reason));
+        }
+        if let Some(location) = &self.location {
+            lines.push(format!("Local: {}", location));
+        }
+        lines.join("\n")
+    }
+
+    /// Signs `input` into `output` with an incremental update, so the bytes
+    /// of `input` and any PDF/A conformance are left as they are. `stamp`
+    /// places a visible signature; `scratch` is a workspace file used to
+    /// render it. The result is verified before returning.
+    pub async fn sign(
+        &self,
+        handle: &AppHandle,
+        input: &Path,
+        output: &Path,
+        stamp: Option<&SignatureStamp>,
+        scratch: &Path,
+    ) -> Result<SigningReport, String> {
+        let page_number = stamp.map_or(1, |stamp| stamp.page_number);
+        let (bytes, document, page_id) = open_for_signing(input, page_number)?;
+        let signed_at = Local::now().fixed_offset();
+        let stamp = match stamp {
+            Some(stamp) => {
+                let placement = place_stamp(&document, page_id, stamp);
+                let image = render_stamp(
+                    handle,
+                    &self.stamp_text(&signed_at),
+                    placement.width,
+                    placement.height,
+                    scratch,
+                )
+                .await?;
+                Some((placement, image))
+            }
+            None => None,
+        };
+        self.sign_opened(bytes, document, page_id, signed_at, stamp, output)
+            .await
+    }
+
+    /// Signs `document`, loaded from `bytes`, into `output` with the
+    /// signature widget on `page_id` and the rendered `stamp`, if any.
+    async fn sign_opened(
+        &self,
+        bytes: Vec<u8>,
+        document: Document,
+        page_id: ObjectId,
+        signed_at: DateTime<FixedOffset>,
+        stamp: Option<(Placement, GreyImage)>,
+        output: &Path,
+    ) -> Result<SigningReport, String> {
+        let rectangle = stamp
+            .as_ref()
+            .map_or([0.0; 4], |(placement, _)| placement.rectangle);
+
+        let version = document.version.clone();
+        let previous_length = bytes.len();
+        let mut update = IncrementalDocument::create_from(bytes, document);
+        update.new_document.version = version;
+        // Left over from a cross-reference stream; the writer sets its own.
+        for key in [
+            b"DecodeParms".as_slice(),
+            b"Filter",
+            b"Index",
+            b"Length",
+            b"Type",
+            b"W",
+            b"XRefStm",
+        ] {
+            update.new_document.trailer.remove(key);
+        }
+
+        let reserved = SIGNATURE_RESERVE
+            + self
+                .certificates
+                .iter()
+                .map(|certificate| certificate.to_der().map_or(0, |encoded| encoded.len()))
+                .sum::<usize>()
+            + if self.timestamp_url.is_some() {
+                TIMESTAMP_RESERVE
+            } else {
+                0
+            };
+        let mut signature = dictionary! {
+            "Type" => "Sig",
+            "Filter" => "Adobe.PPKLite",
+            "SubFilter" => "ETSI.CAdES.detached",
+            "ByteRange" => vec![0.into(), BYTE_RANGE_PLACEHOLDER.into(), BYTE_RANGE_PLACEHOLDER.into(), BYTE_RANGE_PLACEHOLDER.into()],
+            "Contents" => Object::String(vec![0; reserved], StringFormat::Hexadecimal),
+            "M" => text_string(&pdf_date(&signed_at)),
+        };
+        if let Some(reason) = &self.reason {
+            signature.set("Reason", text_string(reason));
+        }
+        if let Some(location) = &self.location {
+            signature.set("Location", text_string(location));
+        }
+        let signature_id = update.new_document.add_object(signature);
+
+        let widget_id = update.new_document.new_object_id();
+        let field_name = add_signature_field(&mut update, widget_id)?;
+        add_annotation(&mut update, page_id, widget_id)?;
+        let mut widget = dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Widget",
+            "FT" => "Sig",
+            "T" => text_string(&field_name),
+            // Print and Locked.
+            "F" => 132,
+            "P" => page_id,
+            "Rect" => rectangle.iter().map(|&value| Object::Real(value as f32)).collect::<Vec<_>>(),
+            "V" => signature_id,
+        };
+        if let Some((placement, image)) = stamp {
+            let appearance = stamp_appearance(&mut update, image, &placement)?;
+            widget.set("AP", dictionary! { "N" => appearance });
+        }
+        update.new_document.set_object(widget_id, widget);
+
+        let mut signed = Vec::new();
+        update
+            .save_to(&mut signed)
+            .map_err(|e| format!("Failed to write the signature: {}", e))?;
+
+        // The placeholders are only in the appended update.
+        let mut placeholder = vec![b'<'];
+        placeholder.resize(1 + 2 * reserved, b'0');
+        placeholder.push(b'>');
+        let contents_start = find(&signed, &placeholder, previous_length)
+            .ok_or_else(|| "The signature placeholder is missing".to_string())?;
+        let contents_end = contents_start + placeholder.len();
+        let byte_range_placeholder = format!("[0 {0} {0} {0}]", BYTE_RANGE_PLACEHOLDER);
+        let byte_range_start = find(&signed, byte_range_placeholder.as_bytes(), previous_length)
+            .ok_or_else(|| "The byte range placeholder is missing".to_string())?;
+        let byte_range = format!(
+            "[0 {} {} {}]",
+            contents_start,
+            contents_end,
+            signed.len() - contents_end
+        );
+        let byte_range = format!(
+            "{:width$}",
+            byte_range,
+            width = byte_range_placeholder.len()
+        );
+        signed[byte_range_start..byte_range_start + byte_range.len()]
+            .copy_from_slice(byte_range.as_bytes());
+
+        let mut hasher = Sha256::new();
+        hasher.update(&signed[..contents_start]);
+        hasher.update(&signed[contents_end..]);
+        let (value, timestamp_time) = self.build_signature(&hasher.finalize()).await?;
+        if value.len() > reserved {
+            return Err(format!(
+                "The signature takes {} bytes but only {} were reserved",
+                value.len(),
+                reserved
+            ));
+        }
+        let hexadecimal: String = value.iter().map(|byte| format!("{:02X}", byte)).collect();
+        signed[contents_start + 1..contents_start + 1 + hexadecimal.len()]
+            .copy_from_slice(hexadecimal.as_bytes());
+
+        fs::write(output, &signed).map_err(|e| {
+            error!("Failed to write {}: {}", output.display(), e);
+            format!("Failed to write {}: {}", output.display(), e)
+        })?;
+
+        let verified = inspect_signatures(output, &[])?
+            .into_iter()
+            .find(|report| report.field_name == field_name)
+            .is_some_and(|report| report.is_intact() && report.covers_whole_document);
+        if !verified {
+            return Err(format!(
+                "The signature of {} does not verify",
+                output.display()
+            ));
+        }
+        debug!("Signed {} as {}", output.display(), self.signer_name);
+        Ok(SigningReport {
+            field_name,
+            signer_name: self.signer_name.clone(),
+            signing_time: signed_at.to_rfc3339(),
+            timestamp_time: timestamp_time.map(format_time),
+        })
+    }
+}
+
+/// Reads `input` for signing and finds page `page_number` (1-based).
+fn open_for_signing(
+    input: &Path,
+    page_number: u32,
+) -> Result<(Vec<u8>, Document, ObjectId), String> {
+    let bytes = fs::read(input).map_err(|e| {
+        error!("Failed to read {}: {}", input.display(), e);
+        format!("Failed to read {}: {}", input.display(), e)
+    })?;
+    let document = Document::load_mem(&bytes)
+        .map_err(|e| format!("Failed to load PDF {}: {}", input.display(), e))?;
+    if document.is_encrypted() {
+        return Err(format!("Cannot sign encrypted PDF {}", input.display()));
+    }
+    let page_id = *document
+        .get_pages()
+        .get(&page_number)
+        .ok_or_else(|| format!("Page {} does not exist in {}", page_number, input.display()))?;
+    Ok((bytes, document, page_id))
+}
+
+pub fn validate_signature_settings(settings: &SignatureSettings) -> Result<(), String> {
+    if let Some(path) = &settings.certificate_path {
+        if !Path::new(path).is_file() {
+            return Err(format!("Signing certificate not found: {}", path));
+        }
+    }
+    if let Some(directory) = &settings.trust_store_directory {
+        if !Path::new(directory).is_dir() {
+            return Err(format!("Trust store directory not found: {}", directory));
+        }
+    }
+    if let Some(url) = &settings.timestamp_url {
+        let scheme = url::Url::parse(url)
+            .map_err(|e| format!("Invalid timestamp authority URL {}: {}", url, e))?
+            .scheme()
+            .to_string();
+        if scheme != "http" && scheme != "https" {
+            return Err(format!(
+                "The timestamp authority URL must use HTTP or HTTPS: {}",
+                url
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::signatures::SignatureStatus;
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        path::PathBuf,
+        thread,
+    };
+
+    const TST_INFO: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.16.1.4");
+    const TEST_POLICY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.99999.1");
+
+    #[derive(Sequence)]
+    struct TstInfo {
+        version: u8,
+        policy: ObjectIdentifier,
+        message_imprint: MessageImprint,
+        serial_number: u64,
+        gen_time: GeneralizedTime,
+        nonce: u64,
+    }
+
+    #[derive(Sequence)]
+    struct PkiStatusInfo {
+        status: u8,
+    }
+
+    #[derive(Sequence)]
+    struct TimeStampResponse {
+        status: PkiStatusInfo,
+        time_stamp_token: ContentInfo,
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    /// A timestamp authority on a local port that grants every request at
+    /// `time`, signing with the test TSA key.
+    struct TimestampAuthority {
+        url: String,
+        time: Duration,
+    }
+
+    impl TimestampAuthority {
+        fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/tsa", listener.local_addr().unwrap());
+            let time = Duration::from_secs(now().as_secs());
+            let key =
+                SigningKey::from_pkcs8_der(&fs::read(fixture("tsa.key.der")).unwrap()).unwrap();
+            let certificate =
+                Certificate::from_der(&fs::read(fixture("tsa.der")).unwrap()).unwrap();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    answer(stream, &key, &certificate, time);
+                }
+            });
+            TimestampAuthority { url, time }
+        }
+    }
+
+    fn answer(mut stream: TcpStream, key: &SigningKey, certificate: &Certificate, time: Duration) {
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        let body_start = loop {
+            let read = stream.read(&mut buffer).unwrap();
+            assert!(read > 0, "connection closed before the request ended");
+            request.extend_from_slice(&buffer[..read]);
+            if let Some(end) = find(&request, b"\r\n\r\n", 0) {
+                break end + 4;
+            }
+        };
+        let length: usize = String::from_utf8_lossy(&request[..body_start])
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse().ok())
+            .unwrap();
+        while request.len() < body_start + length {
+            let read = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..read]);
+        }
+        let request = TimeStampRequest::from_der(&request[body_start..]).unwrap();
+        let response = grant(request, key, certificate, time).unwrap();
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/timestamp-reply\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            response.len()
+        );
+        stream.write_all(head.as_bytes()).unwrap();
+        stream.write_all(&response).unwrap();
+    }
+
+    fn grant(
+        request: TimeStampRequest,
+        key: &SigningKey,
+        certificate: &Certificate,
+        time: Duration,
+    ) -> Result<Vec<u8>, der::Error> {
+        let info = TstInfo {
+            version: 1,
+            policy: TEST_POLICY,
+            message_imprint: request.message_imprint,
+            serial_number: 1,
+            gen_time: GeneralizedTime::from_unix_duration(time)?,
+            nonce: request.nonce,
+        }
+        .to_der()?;
+        let signed_attributes = SetOfVec::try_from(vec![
+            attribute(CONTENT_TYPE, Any::encode_from(&TST_INFO)?).unwrap(),
+            attribute(
+                MESSAGE_DIGEST,
+                Any::encode_from(&OctetString::new(sha256(&info))?)?,
+            )
+            .unwrap(),
+        ])?;
+        let key = PrivateKey::Ecdsa(key.clone());
+        let signature = key.sign(&signed_attributes.to_der()?).unwrap();
+        let digest_algorithm = AlgorithmIdentifierOwned {
+            oid: SHA256,
+            parameters: None,
+        };
+        let signed_data = SignedData {
+            version: CmsVersion::V3,
+            digest_algorithms: SetOfVec::try_from(vec![digest_algorithm.clone()])?,
+            encap_content_info: EncapsulatedContentInfo {
+                econtent_type: TST_INFO,
+                econtent: Some(Any::encode_from(&OctetString::new(info)?)?),
+            },
+            certificates: Some(CertificateSet(SetOfVec::try_from(vec![
+                CertificateChoices::Certificate(certificate.clone()),
+            ])?)),
+            crls: None,
+            signer_infos: SignerInfos(SetOfVec::try_from(vec![SignerInfo {
+                version: CmsVersion::V1,
+                sid: SignerIdentifier::IssuerAndSerialNumber(IssuerAndSerialNumber {
+                    issuer: certificate.tbs_certificate.issuer.clone(),
+                    serial_number: certificate.tbs_certificate.serial_number.clone(),
+                }),
+                digest_alg: digest_algorithm,
+                signed_attrs: Some(signed_attributes),
+                signature_algorithm: key.signature_algorithm(),
+                signature: OctetString::new(signature)?,
+                unsigned_attrs: None,
+            }])?),
+        };
+        TimeStampResponse {
+            status: PkiStatusInfo { status: 0 },
+            time_stamp_token: ContentInfo {
+                content_type: SIGNED_DATA,
+                content: Any::encode_from(&signed_data)?,
+            },
+        }
+        .to_der()
+    }
+
+    fn one_page_pdf(path: &Path) {
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let content_id = document.add_object(Stream::new(dictionary! {}, b"BT ET".to_vec()));
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            "Contents" => content_id,
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        document.save(path).unwrap();
+    }
+
+    #[test]
+    fn signs_with_a_timestamp_and_verifies() {
+        let directory = tempfile::tempdir().unwrap();
+        let input = directory.path().join("peticao.pdf");
+        let output = directory.path().join("peticao-assinado.pdf");
+        one_page_pdf(&input);
+        let authority = TimestampAuthority::start();
+        let settings = SignatureSettings {
+            certificate_path: Some(fixture("signer.p12").display().to_string()),
+            timestamp_url: Some(authority.url.clone()),
+            ..Default::default()
+        };
+        let options = SigningOptions {
+            password: "teste".to_string(),
+            stamp: None,
+            reason: Some("Protocolo".to_string()),
+        };
+
+        let signer = Signer::load(&settings, &options).unwrap();
+        let (bytes, document, page_id) = open_for_signing(&input, 1).unwrap();
+        let report = tauri::async_runtime::block_on(signer.sign_opened(
+            bytes,
+            document,
+            page_id,
+            Local::now().fixed_offset(),
+            None,
+            &output,
+        ))
+        .unwrap();
+        assert_eq!(report.signer_name, "FULANO DE TAL:12345678909");
+        assert_eq!(report.timestamp_time, Some(format_time(authority.time)));
+
+        let root = Certificate::from_der(&fs::read(fixture("ca.der")).unwrap()).unwrap();
+        let reports = inspect_signatures(&output, &[root]).unwrap();
+        assert_eq!(reports.len(), 1);
+        let inspected = &reports[0];
+        assert_eq!(inspected.field_name, report.field_name);
+        assert_eq!(inspected.reason.as_deref(), Some("Protocolo"));
+        assert!(inspected.covers_whole_document);
+        assert!(inspected.digest_matches);
+        assert!(inspected.signature_valid);
+        assert!(inspected.trusted, "{:?}", inspected.issues);
+        assert_eq!(inspected.status, SignatureStatus::Valid);
+    }
+
+    #[test]
+    fn rejects_a_wrong_password() {
+        let settings = SignatureSettings {
+            certificate_path: Some(fixture("signer.p12").display().to_string()),
+            ..Default::default()
+        };
+        let options = SigningOptions {
+            password: "errada".to_string(),
+            stamp: None,
+            reason: None,
+        };
+        let error = Signer::load(&settings, &options).err().unwrap();
+        assert_eq!(error, WRONG_PASSWORD);
+    }
+}
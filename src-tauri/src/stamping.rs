@@ -0,0 +1,703 @@
+use chrono::{Datelike, Local};
+use log::{debug, error, warn};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tauri::AppHandle;
+
+use super::catalogue::{allocate_bates_numbers, release_bates_numbers};
+use super::dates::PartialDate;
+use super::models::settings::{
+    BatesSettings, Stamp, StampKind, StampPages, StampPosition, StampSettings,
+};
+use super::models::workflows::BatesRange;
+use super::naming::{FieldValue, NamingContext, Template};
+use super::ocr::MAGICK_UTILITY;
+use super::signing::inherited;
+use super::utilities::call_utility;
+
+/// Resolution stamps are rendered at, in dots per inch.
+const STAMP_DENSITY: f64 = 300.0;
+const BATES_FIELD: &str = "bates";
+
+/// Bates numbers taken from the app-wide counter of a case for one document. They are
+/// given back when dropped, unless `keep` was called once the document is in
+/// place.
+pub struct BatesReservation {
+    handle: AppHandle,
+    prefix: String,
+    digits: usize,
+    first: u64,
+    count: u64,
+    kept: bool,
+}
+
+impl BatesReservation {
+    pub fn take(
+        handle: &AppHandle,
+        settings: &BatesSettings,
+        prefix: Option<&str>,
+        count: u64,
+    ) -> Result<Self, String> {
+        let prefix = prefix.unwrap_or(&settings.prefix).trim().to_string();
+        let first = allocate_bates_numbers(handle, &prefix, count, settings.start)?;
+        Ok(Self {
+            handle: handle.clone(),
+            prefix,
+            digits: settings.digits,
+            first,
+            count,
+            kept: false,
+        })
+    }
+
+    /// The number of page `page_number` (1-based), e.g. `ABC-000123`.
+    pub fn number(&self, page_number: u32) -> String {
+        let number = self.first + u64::from(page_number.saturating_sub(1));
+        if self.prefix.is_empty() {
+            format!("{:0width$}", number, width = self.digits)
+        } else {
+            format!("{}-{:0width$}", self.prefix, number, width = self.digits)
+        }
+    }
+
+    pub fn range(&self) -> BatesRange {
+        BatesRange {
+            first: self.number(1),
+            last: self.number(self.count.max(1) as u32),
+        }
+    }
+
+    pub fn keep(mut self) {
+        self.kept = true;
+    }
+}
+
+impl Drop for BatesReservation {
+    fn drop(&mut self) {
+        if self.kept {
+            return;
+        }
+        if let Err(e) =
+            release_bates_numbers(&self.handle, &self.prefix, self.first, self.count)
+        {
+            warn!(
+                "Failed to give back Bates numbers of {}: {}",
+                self.prefix, e
+            );
+        }
+    }
+}
+
+/// The stamps of settings named by a process stage, in the stage's order.
+pub fn select_stamps<'a>(
+    settings: &'a StampSettings,
+    names: &[String],
+) -> Result<Vec<&'a Stamp>, String> {
+    names
+        .iter()
+        .map(|name| {
+            settings
+                .stamps
+                .iter()
+                .find(|stamp| &stamp.name == name)
+                .ok_or_else(|| format!("Stamp not found: {}", name))
+        })
+        .collect()
+}
+
+/// Whether any of `stamps` prints Bates numbers, so the document needs some.
+pub fn uses_bates(stamps: &[&Stamp]) -> Result<bool, String> {
+    for stamp in stamps.iter().filter(|stamp| stamp.kind == StampKind::Text) {
+        if Template::parse(&stamp.text)?.uses_field(BATES_FIELD) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn applies_to(stamp: &Stamp, page_number: u32, page_count: u32) -> bool {
+    match stamp.pages {
+        StampPages::All => true,
+        StampPages::First => page_number == 1,
+        StampPages::Last => page_number == page_count,
+        StampPages::Odd => page_number % 2 == 1,
+        StampPages::Even => page_number % 2 == 0,
+        StampPages::Selected => stamp.page_numbers.contains(&page_number),
+    }
+}
+
+fn parse_colour(colour: &str) -> Result<[f64; 3], String> {
+    let hex = colour.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid colour, expected #RRGGBB: {}", colour));
+    }
+    let channel = |index: usize| {
+        u8::from_str_radix(&hex[index..index + 2], 16).map_or(0.0, |value| f64::from(value) / 255.0)
+    };
+    Ok([channel(0), channel(2), channel(4)])
+}
+
+/// Pixels read from a PAM file, interleaved by channel.
+struct Pixmap {
+    width: u32,
+    height: u32,
+    channels: usize,
+    alpha: bool,
+    pixels: Vec<u8>,
+}
+
+fn read_pam(bytes: &[u8]) -> Result<Pixmap, String> {
+    const END_OF_HEADER: &[u8] = b"ENDHDR\n";
+    let header_end = bytes
+        .windows(END_OF_HEADER.len())
+        .position(|window| window == END_OF_HEADER)
+        .ok_or_else(|| "The rendered stamp has no PAM header".to_string())?;
+    let header = String::from_utf8_lossy(&bytes[..header_end]);
+    let mut fields = HashMap::new();
+    for line in header.lines().skip(1) {
+        if let Some((key, value)) = line.trim().split_once(' ') {
+            fields.insert(key, value.trim());
+        }
+    }
+    let number = |key: &str| -> Result<u32, String> {
+        fields
+            .get(key)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("The rendered stamp has no {}", key))
+    };
+    let (width, height, channels) = (number("WIDTH")?, number("HEIGHT")?, number("DEPTH")?);
+    if number("MAXVAL")? != 255 || !(1..=4).contains(&channels) {
+        return Err("The rendered stamp is not an 8-bit image".to_string());
+    }
+    let pixels = bytes[header_end + END_OF_HEADER.len()..].to_vec();
+    if pixels.len() != (width * height * channels) as usize {
+        return Err("The rendered stamp has an unexpected size".to_string());
+    }
+    Ok(Pixmap {
+        width,
+        height,
+        channels: channels as usize,
+        alpha: fields
+            .get("TUPLTYPE")
+            .is_some_and(|tuple_type| tuple_type.ends_with("_ALPHA")),
+        pixels,
+    })
+}
+
+async fn render(
+    handle: &AppHandle,
+    mut args: Vec<String>,
+    scratch: &Path,
+) -> Result<Pixmap, String> {
+    args.extend([
+        "-depth".to_owned(),
+        "8".to_owned(),
+        format!("pam:{}", scratch.display()),
+    ]);
+    if !call_utility(handle.clone(), MAGICK_UTILITY.to_owned(), args, false).await {
+        error!("Failed to render a stamp");
+        return Err("Failed to render a stamp".to_string());
+    }
+    let bytes =
+        fs::read(scratch).map_err(|e| format!("Failed to read {}: {}", scratch.display(), e))?;
+    read_pam(&bytes)
+}
+
+fn to_points(pixels: u32) -> f64 {
+    f64::from(pixels) * 72.0 / STAMP_DENSITY
+}
+
+/// Renders `text` in black on white and turns it into a stencil mask, which
+/// is painted with the fill colour and needs no transparency.
+async fn text_image(
+    handle: &AppHandle,
+    text: &str,
+    size: f64,
+    scratch: &Path,
+) -> Result<(Stream, f64, f64), String> {
+    // `%` starts an escape and a leading `@` reads a file in label text.
+    let mut text = text.replace('%', "%%");
+    if text.starts_with('@') {
+        text.insert(0, '\\');
+    }
+    let args = vec![
+        "-density".to_owned(),
+        STAMP_DENSITY.to_string(),
+        "-background".to_owned(),
+        "white".to_owned(),
+        "-fill".to_owned(),
+        "black".to_owned(),
+        "-pointsize".to_owned(),
+        format!("{:.2}", size),
+        format!("label:{}", text),
+        "-colorspace".to_owned(),
+        "Gray".to_owned(),
+        "-alpha".to_owned(),
+        "off".to_owned(),
+    ];
+    let pixmap = render(handle, args, scratch).await?;
+    let row_bytes = (pixmap.width as usize + 7) / 8;
+    let mut mask = vec![0xFF; row_bytes * pixmap.height as usize];
+    for (index, pixel) in pixmap.pixels.chunks(pixmap.channels).enumerate() {
+        if pixel[0] < 128 {
+            let (row, column) = (index / pixmap.width as usize, index % pixmap.width as usize);
+            mask[row * row_bytes + column / 8] &= !(0x80 >> (column % 8));
+        }
+    }
+    let mut stream = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => pixmap.width as i64,
+            "Height" => pixmap.height as i64,
+            "ImageMask" => true,
+            "BitsPerComponent" => 1,
+        },
+        mask,
+    );
+    stream
+        .compress()
+        .map_err(|e| format!("Failed to compress a stamp: {}", e))?;
+    Ok((stream, to_points(pixmap.width), to_points(pixmap.height)))
+}
+
+/// Renders the image file of an image stamp `width` points wide. Its alpha
+/// channel, if any, becomes a soft mask.
+async fn picture_image(
+    handle: &AppHandle,
+    document: &mut Document,
+    path: &str,
+    width: f64,
+    scratch: &Path,
+) -> Result<(Stream, f64, f64, bool), String> {
+    if !Path::new(path).is_file() {
+        return Err(format!("Stamp image not found: {}", path));
+    }
+    let args = vec![
+        format!("{}[0]", path),
+        "-resize".to_owned(),
+        format!(
+            "{}x",
+            ((width * STAMP_DENSITY / 72.0).round() as u32).max(1)
+        ),
+    ];
+    let pixmap = render(handle, args, scratch).await?;
+    let colours = if pixmap.alpha {
+        pixmap.channels - 1
+    } else {
+        pixmap.channels
+    };
+    let mut colour = Vec::with_capacity(pixmap.pixels.len());
+    let mut alpha = Vec::new();
+    for pixel in pixmap.pixels.chunks(pixmap.channels) {
+        colour.extend_from_slice(&pixel[..colours]);
+        if pixmap.alpha {
+            alpha.push(pixel[colours]);
+        }
+    }
+    let mut image = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => pixmap.width as i64,
+        "Height" => pixmap.height as i64,
+        "ColorSpace" => if colours == 1 { "DeviceGray" } else { "DeviceRGB" },
+        "BitsPerComponent" => 8,
+    };
+    let translucent = alpha.iter().any(|&value| value < 255);
+    if translucent {
+        let mut mask = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => pixmap.width as i64,
+                "Height" => pixmap.height as i64,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            alpha,
+        );
+        mask.compress()
+            .map_err(|e| format!("Failed to compress a stamp: {}", e))?;
+        image.set("SMask", document.add_object(mask));
+    }
+    let mut stream = Stream::new(image, colour);
+    stream
+        .compress()
+        .map_err(|e| format!("Failed to compress a stamp: {}", e))?;
+    Ok((
+        stream,
+        to_points(pixmap.width),
+        to_points(pixmap.height),
+        translucent,
+    ))
+}
+
+/// The displayed size of a page and the matrix from displayed coordinates,
+/// from the bottom left corner with y up, to user space.
+fn page_frame(document: &Document, page_id: ObjectId) -> (f64, f64, [f64; 6]) {
+    let page_box: Vec<f64> = [b"CropBox".as_slice(), b"MediaBox"]
+        .iter()
+        .find_map(|key| {
+            inherited(document, page_id, key)
+                .and_then(|page_box| document.dereference(page_box).ok())
+                .and_then(|(_, page_box)| page_box.as_array().ok())
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(|value| value.as_float().ok().map(f64::from))
+                        .collect::<Vec<f64>>()
+                })
+                .filter(|values| values.len() == 4)
+        })
+        .unwrap_or_else(|| vec![0.0, 0.0, 612.0, 792.0]);
+    let [left, bottom, right, top] = [
+        page_box[0].min(page_box[2]),
+        page_box[1].min(page_box[3]),
+        page_box[0].max(page_box[2]),
+        page_box[1].max(page_box[3]),
+    ];
+    let rotation = inherited(document, page_id, b"Rotate")
+        .and_then(|rotation| rotation.as_i64().ok())
+        .unwrap_or(0)
+        .rem_euclid(360);
+    let (width, height) = (right - left, top - bottom);
+    match rotation {
+        90 => (height, width, [0.0, 1.0, -1.0, 0.0, right, bottom]),
+        180 => (width, height, [-1.0, 0.0, 0.0, -1.0, right, top]),
+        270 => (height, width, [0.0, -1.0, 1.0, 0.0, left, top]),
+        _ => (width, height, [1.0, 0.0, 0.0, 1.0, left, bottom]),
+    }
+}
+
+/// Centre of a stamp whose rotated bounding box is `width` by `height`, on a
+/// displayed page of `page_width` by `page_height`.
+fn anchor(
+    stamp: &Stamp,
+    (width, height): (f64, f64),
+    (page_width, page_height): (f64, f64),
+) -> (f64, f64) {
+    let margin = stamp.margin.max(0.0);
+    let left = margin + width / 2.0;
+    let right = page_width - margin - width / 2.0;
+    let bottom = margin + height / 2.0;
+    let top = page_height - margin - height / 2.0;
+    let (centre_x, centre_y) = (page_width / 2.0, page_height / 2.0);
+    match stamp.position {
+        StampPosition::TopLeft => (left, top),
+        StampPosition::TopCenter => (centre_x, top),
+        StampPosition::TopRight => (right, top),
+        StampPosition::Center => (centre_x, centre_y),
+        StampPosition::BottomLeft => (left, bottom),
+        StampPosition::BottomCenter => (centre_x, bottom),
+        StampPosition::BottomRight => (right, bottom),
+    }
+}
+
+fn matrix(values: [f64; 6]) -> String {
+    // Adding zero turns -0 into 0.
+    let values: Vec<String> = values
+        .iter()
+        .map(|value| format!("{:.4}", value + 0.0))
+        .collect();
+    format!("{} cm", values.join(" "))
+}
+
+/// A name for a new entry of `dictionary` starting with `prefix`.
+fn free_name(dictionary: &Dictionary, prefix: &str) -> String {
+    (1..)
+        .map(|number| format!("{}{}", prefix, number))
+        .find(|name| !dictionary.has(name.as_bytes()))
+        .unwrap_or_default()
+}
+
+/// A resource category of `resources`, copied out of an indirect object.
+fn resource_category(document: &Document, resources: &Dictionary, key: &[u8]) -> Dictionary {
+    resources
+        .get(key)
+        .ok()
+        .and_then(|category| document.dereference(category).ok())
+        .and_then(|(_, category)| category.as_dict().ok())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Files of one stamping run. `scratch` is a workspace file the stamps are
+/// rendered into.
+pub struct StampRequest<'a> {
+    pub input: &'a Path,
+    pub output: &'a Path,
+    pub scratch: &'a Path,
+}
+
+/// Draws `stamps` over the pages of the request's input. Text stamps are
+/// rendered from their template with `context`, plus the page number and its
+/// Bates number from `bates`. With `forbid_transparency`, as for PDF/A-1,
+/// translucent stamps are refused.
+pub async fn stamp_document(
+    handle: &AppHandle,
+    stamps: &[&Stamp],
+    context: &NamingContext,
+    bates: Option<&BatesReservation>,
+    forbid_transparency: bool,
+    request: StampRequest<'_>,
+) -> Result<(), String> {
+    let StampRequest {
+        input,
+        output,
+        scratch,
+    } = request;
+    let mut document = Document::load(input)
+        .map_err(|e| format!("Failed to load PDF {}: {}", input.display(), e))?;
+    if document.is_encrypted() {
+        return Err(format!("Cannot stamp encrypted PDF {}", input.display()));
+    }
+    let templates = stamps
+        .iter()
+        .map(|stamp| match stamp.kind {
+            StampKind::Text => Template::parse(&stamp.text)
+                .map(Some)
+                .map_err(|e| format!("Invalid text of stamp '{}': {}", stamp.name, e)),
+            StampKind::Image => Ok(None),
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let colours = stamps
+        .iter()
+        .map(|stamp| parse_colour(&stamp.colour))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut context = context.clone();
+    let today = Local::now().date_naive();
+    context.insert(
+        "today",
+        FieldValue::Date(PartialDate::new(
+            Some(today.year()),
+            Some(today.month()),
+            Some(today.day()),
+        )?),
+    );
+    let pages = document.get_pages();
+    let page_count = pages.len() as u32;
+    let opening_id = document.add_object(Stream::new(Dictionary::new(), b"q\n".to_vec()));
+    // Images by stamp and text, as most stamps read the same on every page.
+    let mut images: HashMap<(usize, String), (ObjectId, f64, f64)> = HashMap::new();
+    let mut graphics_states: HashMap<usize, ObjectId> = HashMap::new();
+    let mut transparency = false;
+
+    for (page_number, page_id) in pages {
+        context.insert("page", FieldValue::Number(u64::from(page_number)));
+        if let Some(bates) = bates {
+            context.insert(BATES_FIELD, FieldValue::Text(bates.number(page_number)));
+        }
+        let (page_width, page_height, page_matrix) = page_frame(&document, page_id);
+        let mut content = String::new();
+        let mut drawn = Vec::new();
+
+        for (index, stamp) in stamps.iter().enumerate() {
+            if !applies_to(stamp, page_number, page_count) {
+                continue;
+            }
+            let text = match &templates[index] {
+                Some(template) => template.render(&context).trim().to_string(),
+                None => String::new(),
+            };
+            if stamp.kind == StampKind::Text && text.is_empty() {
+                continue;
+            }
+            let key = (index, text);
+            let (image_id, width, height) = match images.get(&key) {
+                Some(image) => *image,
+                None => {
+                    let (stream, width, height) = match stamp.kind {
+                        StampKind::Text => text_image(handle, &key.1, stamp.size, scratch).await?,
+                        StampKind::Image => {
+                            let path = stamp
+                                .image_path
+                                .as_deref()
+                                .ok_or_else(|| format!("Stamp '{}' has no image", stamp.name))?;
+                            let (stream, width, height, translucent) =
+                                picture_image(handle, &mut document, path, stamp.size, scratch)
+                                    .await?;
+                            if translucent && forbid_transparency {
+                                return Err(format!(
+                                    "The image of stamp '{}' is translucent, which PDF/A-1 does not allow",
+                                    stamp.name
+                                ));
+                            }
+                            transparency |= translucent;
+                            (stream, width, height)
+                        }
+                    };
+                    let image = (document.add_object(stream), width, height);
+                    images.insert(key, image);
+                    image
+                }
+            };
+
+            let opacity = stamp.opacity.clamp(0.0, 1.0);
+            let graphics_state = if opacity < 1.0 {
+                if forbid_transparency {
+                    return Err(format!(
+                        "Stamp '{}' is translucent, which PDF/A-1 does not allow",
+                        stamp.name
+                    ));
+                }
+                transparency = true;
+                Some(*graphics_states.entry(index).or_insert_with(|| {
+                    document.add_object(dictionary! {
+                        "Type" => "ExtGState",
+                        "ca" => opacity as f32,
+                        "CA" => opacity as f32,
+                    })
+                }))
+            } else {
+                None
+            };
+
+            let (sine, cosine) = stamp.rotation.to_radians().sin_cos();
+            let bounds = (
+                (width * cosine).abs() + (height * sine).abs(),
+                (width * sine).abs() + (height * cosine).abs(),
+            );
+            let (x, y) = anchor(stamp, bounds, (page_width, page_height));
+            drawn.push((image_id, graphics_state));
+            content.push_str("q\n");
+            if graphics_state.is_some() {
+                content.push_str(&format!("/{{gs{}}} gs\n", drawn.len()));
+            }
+            if stamp.kind == StampKind::Text {
+                let [red, green, blue] = colours[index];
+                content.push_str(&format!("{:.3} {:.3} {:.3} rg\n", red, green, blue));
+            }
+            content.push_str(&format!(
+                "{}\n{}\n{}\n{}\n/{{im{}}} Do\nQ\n",
+                matrix(page_matrix),
+                matrix([1.0, 0.0, 0.0, 1.0, x, y]),
+                matrix([cosine, sine, -sine, cosine, 0.0, 0.0]),
+                matrix([width, 0.0, 0.0, height, -width / 2.0, -height / 2.0]),
+                drawn.len()
+            ));
+        }
+        if drawn.is_empty() {
+            continue;
+        }
+
+        // Resources are copied onto the page, as they may be shared with
+        // other pages or inherited from the page tree.
+        let mut resources = inherited(&document, page_id, b"Resources")
+            .and_then(|resources| document.dereference(resources).ok())
+            .and_then(|(_, resources)| resources.as_dict().ok())
+            .cloned()
+            .unwrap_or_default();
+        let mut x_objects = resource_category(&document, &resources, b"XObject");
+        let mut states = resource_category(&document, &resources, b"ExtGState");
+        for (number, (image_id, graphics_state)) in drawn.iter().enumerate() {
+            let name = free_name(&x_objects, "StampIm");
+            content = content.replace(&format!("{{im{}}}", number + 1), &name);
+            x_objects.set(name, *image_id);
+            if let Some(graphics_state) = graphics_state {
+                let name = free_name(&states, "StampGs");
+                content = content.replace(&format!("{{gs{}}}", number + 1), &name);
+                states.set(name, *graphics_state);
+            }
+        }
+        resources.set("XObject", x_objects);
+        if !states.is_empty() {
+            resources.set("ExtGState", states);
+        }
+
+        // Closes the q that opens the page content, so a graphics state it
+        // leaves behind cannot move the stamps.
+        content.insert_str(0, "\nQ\n");
+        let mut stream = Stream::new(Dictionary::new(), content.into_bytes());
+        stream
+            .compress()
+            .map_err(|e| format!("Failed to compress a stamp: {}", e))?;
+        let stamp_id = document.add_object(stream);
+        let page = document
+            .get_dictionary(page_id)
+            .map_err(|e| format!("Failed to read page {}: {}", page_number, e))?;
+        let mut contents = vec![Object::Reference(opening_id)];
+        match page.get(b"Contents") {
+            Ok(Object::Reference(id)) => match document.get_object(*id) {
+                Ok(Object::Array(streams)) => contents.extend(streams.iter().cloned()),
+                _ => contents.push(Object::Reference(*id)),
+            },
+            Ok(Object::Array(streams)) => contents.extend(streams.iter().cloned()),
+            _ => {}
+        }
+        contents.push(Object::Reference(stamp_id));
+        let page = document
+            .get_dictionary_mut(page_id)
+            .map_err(|e| format!("Failed to read page {}: {}", page_number, e))?;
+        page.set("Contents", contents);
+        page.set("Resources", resources);
+    }
+
+    // Soft masks and constant opacity came with PDF 1.4.
+    if transparency && document.version.as_str() < "1.4" {
+        document.version = "1.4".to_string();
+    }
+    document
+        .save(output)
+        .map_err(|e| format!("Failed to save PDF {}: {}", output.display(), e))?;
+    debug!("Stamped {} with {} stamps", output.display(), stamps.len());
+    Ok(())
+}
+
+pub fn validate_stamp_settings(settings: &StampSettings) -> Result<(), String> {
+    for (index, stamp) in settings.stamps.iter().enumerate() {
+        if stamp.name.trim().is_empty() {
+            return Err("Stamp names cannot be empty".to_string());
+        }
+        if settings.stamps[..index]
+            .iter()
+            .any(|other| other.name == stamp.name)
+        {
+            return Err(format!("Duplicate stamp: {}", stamp.name));
+        }
+        match stamp.kind {
+            StampKind::Text => {
+                Template::parse(&stamp.text)
+                    .map_err(|e| format!("Invalid text of stamp '{}': {}", stamp.name, e))?;
+                parse_colour(&stamp.colour)
+                    .map_err(|e| format!("Invalid colour of stamp '{}': {}", stamp.name, e))?;
+            }
+            StampKind::Image => match &stamp.image_path {
+                Some(path) if Path::new(path).is_file() => {}
+                Some(path) => {
+                    return Err(format!(
+                        "Image of stamp '{}' not found: {}",
+                        stamp.name, path
+                    ))
+                }
+                None => return Err(format!("Stamp '{}' has no image", stamp.name)),
+            },
+        }
+        if !(stamp.size > 0.0 && stamp.size <= 1000.0) {
+            return Err(format!(
+                "Size of stamp '{}' must be between 0 and 1000 points",
+                stamp.name
+            ));
+        }
+        if !(0.0..=1.0).contains(&stamp.opacity) {
+            return Err(format!(
+                "Opacity of stamp '{}' must be between 0 and 1",
+                stamp.name
+            ));
+        }
+        if stamp.pages == StampPages::Selected && stamp.page_numbers.contains(&0) {
+            return Err(format!(
+                "Pages of stamp '{}' are counted from 1",
+                stamp.name
+            ));
+        }
+    }
+    if settings.bates.digits > 18 {
+        return Err("Bates numbers can have at most 18 digits".to_string());
+    }
+    Ok(())
+}
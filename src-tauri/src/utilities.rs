@@ -1,4 +1,12 @@
-use std::sync::{Arc, Mutex};
+use log::error;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
 use tauri::{AppHandle, Emitter, Listener};
 use tauri_plugin_shell::{process::CommandEvent, ShellExt};
 
@@ -8,22 +16,32 @@ pub async fn call_utility(
     args: Vec<String>,
     is_sidecar: bool,
 ) -> bool {
-    let (mut rx, child) = if is_sidecar {
+    match try_call_utility(handle, utility, args, is_sidecar).await {
+        Ok(()) => true,
+        Err(e) => {
+            error!("{}", e);
+            false
+        }
+    }
+}
+
+/// Like [`call_utility`], but reports why the utility failed, including when
+/// it could not be started at all (e.g. it is not installed).
+pub async fn try_call_utility(
+    handle: AppHandle,
+    utility: String,
+    args: Vec<String>,
+    is_sidecar: bool,
+) -> Result<(), String> {
+    let spawned = if is_sidecar {
         handle
             .shell()
-            .sidecar(utility)
-            .expect("Sidecard not provided")
-            .args(args)
-            .spawn()
-            .expect("Failed to spawn sidecar process")
+            .sidecar(&utility)
+            .and_then(|command| command.args(args).spawn())
     } else {
-        handle
-            .shell()
-            .command(utility)
-            .args(args)
-            .spawn()
-            .expect("Failed to spawn process")
+        handle.shell().command(&utility).args(args).spawn()
     };
+    let (mut rx, child) = spawned.map_err(|e| format!("Failed to spawn {}: {}", utility, e))?;
 
     let child = Arc::new(Mutex::new(Some(child)));
     let child_clone = Arc::clone(&child);
@@ -37,7 +55,7 @@ pub async fn call_utility(
         }
     });
 
-    let mut is_success = false;
+    let mut exit_code = None;
 
     while let Some(event) = rx.recv().await {
         match event {
@@ -60,9 +78,7 @@ pub async fn call_utility(
             CommandEvent::Terminated(status) => {
                 if let Some(code) = status.code {
                     handle.emit("utility-terminated", code.to_string()).unwrap();
-                    if code == 0 {
-                        is_success = true;
-                    }
+                    exit_code = Some(code);
                 }
                 if let Some(_signal) = status.signal {}
                 break;
@@ -71,7 +87,11 @@ pub async fn call_utility(
         }
     }
 
-    is_success
+    match exit_code {
+        Some(0) => Ok(()),
+        Some(code) => Err(format!("{} exited with code {}", utility, code)),
+        None => Err(format!("{} did not exit normally", utility)),
+    }
 }
 
 pub async fn call_utility2(
@@ -80,22 +100,15 @@ pub async fn call_utility2(
     args: Vec<String>,
     is_sidecar: bool,
 ) -> Result<String, String> {
-    let (mut rx, child) = if is_sidecar {
+    let spawned = if is_sidecar {
         handle
             .shell()
-            .sidecar(utility)
-            .expect("Sidecard not provided")
-            .args(args)
-            .spawn()
-            .expect("Failed to spawn sidecar process")
+            .sidecar(&utility)
+            .and_then(|command| command.args(args).spawn())
     } else {
-        handle
-            .shell()
-            .command(utility)
-            .args(args)
-            .spawn()
-            .expect("Failed to spawn process")
+        handle.shell().command(&utility).args(args).spawn()
     };
+    let (mut rx, child) = spawned.map_err(|e| format!("Failed to spawn {}: {}", utility, e))?;
 
     let child = Arc::new(Mutex::new(Some(child)));
     let child_clone = Arc::clone(&child);
@@ -146,3 +159,20 @@ pub async fn call_utility2(
 
     Ok(stdout_buffer)
 }
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Hex-encoded SHA-256 of a file's contents.
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
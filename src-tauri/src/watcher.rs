@@ -0,0 +1,599 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use lazy_static::lazy_static;
+use log::{debug, error, info, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::extractor::run_extract_document_images_stage;
+use super::history::{fingerprint, record_move};
+use super::models::history::{FileFingerprint, RenameTrigger};
+use super::models::settings::{
+    CollisionPolicy, FileNameSettings, PathSettings, PostIngestAction, WatchFolder, WatchSettings,
+};
+use super::models::watch::{IngestRecord, IngestStatus, IngestStore, WatchStatus};
+use super::models::workflows::ExtractDocumentImagesStage;
+use super::sanitizer::resolve_destination;
+use super::scope::PathScope;
+use super::settings::load_settings;
+use super::utilities::{sha256_file, unix_timestamp};
+
+const INGEST_FILE_NAME: &str = "watch-ingested.json";
+/// Suffix of the data directory the frontend puts next to a document.
+const DATA_DIRECTORY_SUFFIX: &str = "-data";
+const IMAGES_DIRECTORY_NAME: &str = "images";
+/// How far from the end of a PDF the `%%EOF` marker is looked for.
+const EOF_WINDOW: u64 = 1024;
+/// Windows errors of a file another process holds open without sharing.
+const SHARING_VIOLATION: i32 = 32;
+const LOCK_VIOLATION: i32 = 33;
+
+lazy_static! {
+    static ref INGEST_LOCK: Mutex<()> = Mutex::new(());
+    static ref PENDING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+}
+
+fn ingest_path(handle: &AppHandle) -> Result<PathBuf, String> {
+    let data_directory = handle.path().app_data_dir().map_err(|e| {
+        error!("Failed to resolve app data directory: {}", e);
+        format!("Failed to resolve app data directory: {}", e)
+    })?;
+    Ok(data_directory.join(INGEST_FILE_NAME))
+}
+
+fn load_store(path: &Path) -> Result<IngestStore, String> {
+    if !path.exists() {
+        return Ok(IngestStore::default());
+    }
+    let content = fs::read_to_string(path).map_err(|e| {
+        error!("Failed to read ingest log: {}", e);
+        format!("Failed to read ingest log: {}", e)
+    })?;
+    serde_json::from_str(&content).map_err(|e| {
+        error!("Failed to parse ingest log: {}", e);
+        format!("Failed to parse ingest log: {}", e)
+    })
+}
+
+fn save_store(path: &Path, store: &IngestStore) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            error!("Failed to create app data directory: {}", e);
+            format!("Failed to create app data directory: {}", e)
+        })?;
+    }
+    let content = serde_json::to_string_pretty(store).map_err(|e| {
+        error!("Failed to serialise ingest log: {}", e);
+        format!("Failed to serialise ingest log: {}", e)
+    })?;
+    let temporary_path = path.with_extension("json.tmp");
+    fs::write(&temporary_path, content)
+        .and_then(|_| fs::rename(&temporary_path, path))
+        .map_err(|e| {
+            error!("Failed to write ingest log: {}", e);
+            format!("Failed to write ingest log: {}", e)
+        })
+}
+
+fn read_store(handle: &AppHandle) -> Result<IngestStore, String> {
+    let _guard = INGEST_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to lock ingest log: {}", e))?;
+    load_store(&ingest_path(handle)?)
+}
+
+fn append_record(handle: &AppHandle, record: &IngestRecord) -> Result<(), String> {
+    let _guard = INGEST_LOCK
+        .lock()
+        .map_err(|e| format!("Failed to lock ingest log: {}", e))?;
+    let path = ingest_path(handle)?;
+    let mut store = load_store(&path)?;
+    store.records.push(record.clone());
+    save_store(&path, &store)
+}
+
+fn set_pending(paths: Vec<String>) {
+    match PENDING.lock() {
+        Ok(mut pending) => *pending = paths,
+        Err(e) => warn!("Failed to lock pending watch files: {}", e),
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| format!("Invalid glob {}: {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Invalid globs {:?}: {}", patterns, e))
+}
+
+/// Files of a watch folder that its globs select.
+struct FolderScan<'a> {
+    folder: &'a WatchFolder,
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+impl<'a> FolderScan<'a> {
+    fn new(folder: &'a WatchFolder) -> Result<Self, String> {
+        Ok(Self {
+            folder,
+            include: glob_set(&folder.include)?,
+            exclude: glob_set(&folder.exclude)?,
+        })
+    }
+
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        self.collect(Path::new(&self.folder.path), &mut files);
+        files
+    }
+
+    /// Skips hidden folders, the archive and data directories, which hold
+    /// copies and outputs of ingested files.
+    fn collect(&self, directory: &Path, files: &mut Vec<PathBuf>) {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                debug!("Failed to read watch folder {}: {}", directory.display(), e);
+                return;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                let skipped = name.starts_with('.')
+                    || name.ends_with(DATA_DIRECTORY_SUFFIX)
+                    || self
+                        .folder
+                        .archive_path
+                        .as_deref()
+                        .is_some_and(|archive| Path::new(archive) == path);
+                if self.folder.recursive && !skipped {
+                    self.collect(&path, files);
+                }
+            } else if file_type.is_file() && !name.starts_with('.') {
+                let relative = path
+                    .strip_prefix(&self.folder.path)
+                    .unwrap_or(&path)
+                    .to_path_buf();
+                if self.include.is_match(&relative) && !self.exclude.is_match(&relative) {
+                    files.push(path);
+                }
+            }
+        }
+    }
+}
+
+/// Whether a file that stopped changing is fully written: on Windows the
+/// writer must have closed it, and a PDF must end with its `%%EOF` marker.
+fn is_complete(path: &Path) -> bool {
+    if cfg!(windows) {
+        if let Err(e) = OpenOptions::new().append(true).open(path) {
+            if matches!(e.raw_os_error(), Some(SHARING_VIOLATION | LOCK_VIOLATION)) {
+                debug!("{} is still locked", path.display());
+                return false;
+            }
+        }
+    }
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    let mut header = [0; 5];
+    if file.read_exact(&mut header).is_err() {
+        return false;
+    }
+    if &header != b"%PDF-" {
+        // Not a PDF; extraction will say so.
+        return true;
+    }
+    let mut tail = Vec::new();
+    let length = file.metadata().map_or(0, |metadata| metadata.len());
+    file.seek(SeekFrom::Start(length.saturating_sub(EOF_WINDOW)))
+        .and_then(|_| file.read_to_end(&mut tail))
+        .is_ok_and(|_| tail.windows(5).any(|window| window == b"%%EOF"))
+}
+
+/// The data directory the frontend uses for `document`, `<stem>-data` next
+/// to it.
+fn data_directory_for(document: &Path) -> PathBuf {
+    let is_pdf = document
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"));
+    let name = if is_pdf {
+        document.file_stem()
+    } else {
+        document.file_name()
+    }
+    .map(|name| name.to_string_lossy().into_owned())
+    .unwrap_or_default();
+    document.with_file_name(format!("{}{}", name, DATA_DIRECTORY_SUFFIX))
+}
+
+/// Moves `path`, copying across volumes when a rename cannot.
+fn move_file(path: &Path, destination: &Path) -> Result<(), String> {
+    if fs::rename(path, destination).is_ok() {
+        return Ok(());
+    }
+    fs::copy(path, destination)
+        .and_then(|_| fs::remove_file(path))
+        .map_err(|e| {
+            if destination.exists() && path.exists() {
+                let _ = fs::remove_file(destination);
+            }
+            error!(
+                "Failed to move {} to {}: {}",
+                path.display(),
+                destination.display(),
+                e
+            );
+            format!(
+                "Failed to move {} to {}: {}",
+                path.display(),
+                destination.display(),
+                e
+            )
+        })
+}
+
+/// Moves `path` into the archive folder, suffixing the name on collision.
+fn archive(
+    path: &Path,
+    folder: &WatchFolder,
+    file_names: &FileNameSettings,
+) -> Result<PathBuf, String> {
+    let archive = folder
+        .archive_path
+        .as_deref()
+        .ok_or_else(|| format!("Watch folder {} has no archive folder", folder.path))?;
+    fs::create_dir_all(archive)
+        .map_err(|e| format!("Failed to create archive folder {}: {}", archive, e))?;
+    let file_names = FileNameSettings {
+        collision_policy: CollisionPolicy::Suffix,
+        ..file_names.clone()
+    };
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let destination = resolve_destination(Path::new(archive), &file_name, None, &file_names)?;
+    move_file(path, &destination)?;
+    Ok(destination)
+}
+
+fn log_archive_move(handle: &AppHandle, old_path: &Path, new_path: &Path) {
+    if let Err(e) = record_move(
+        handle,
+        None,
+        &old_path.display().to_string(),
+        &new_path.display().to_string(),
+        RenameTrigger::WatchArchive,
+    ) {
+        warn!("Failed to record the move of {}: {}", old_path.display(), e);
+    }
+}
+
+/// Moves an archived file that failed extraction back to where it was
+/// found, suffixing the name if a new file took its place meanwhile.
+fn restore(
+    archived: &Path,
+    source: &Path,
+    file_names: &FileNameSettings,
+) -> Result<PathBuf, String> {
+    let directory = source
+        .parent()
+        .ok_or_else(|| format!("Invalid watch file path: {}", source.display()))?;
+    let file_names = FileNameSettings {
+        collision_policy: CollisionPolicy::Suffix,
+        ..file_names.clone()
+    };
+    let file_name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let destination = resolve_destination(directory, &file_name, None, &file_names)?;
+    move_file(archived, &destination)?;
+    debug!(
+        "Moved {} back to {} after it failed",
+        archived.display(),
+        destination.display()
+    );
+    Ok(destination)
+}
+
+/// Hashes `path` and, unless the content was extracted before, starts its
+/// extraction. The outcome is logged and sent to the frontend, except for a
+/// file already ingested from the same place.
+async fn ingest(
+    handle: &AppHandle,
+    path: &Path,
+    folder: &WatchFolder,
+    file_names: &FileNameSettings,
+) {
+    let source_path = path.display().to_string();
+    let mut record = IngestRecord {
+        sha256: String::new(),
+        source_path: source_path.clone(),
+        document_path: source_path,
+        data_directory: None,
+        status: IngestStatus::Failed,
+        error_message: None,
+        timestamp: unix_timestamp(),
+    };
+    match ingest_file(handle, path, folder, file_names, &mut record).await {
+        Ok(Some(status)) => record.status = status,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to ingest {}: {}", path.display(), e);
+            record.error_message = Some(e);
+        }
+    }
+    if let Err(e) = append_record(handle, &record) {
+        warn!("Failed to record ingestion of {}: {}", path.display(), e);
+    }
+    if let Err(e) = handle.emit("watch-ingested", &record) {
+        warn!("Failed to emit watch-ingested event: {}", e);
+    }
+}
+
+async fn ingest_file(
+    handle: &AppHandle,
+    path: &Path,
+    folder: &WatchFolder,
+    file_names: &FileNameSettings,
+    record: &mut IngestRecord,
+) -> Result<Option<IngestStatus>, String> {
+    record.sha256 = sha256_file(path)?;
+    let store = read_store(handle)?;
+    let known = store
+        .records
+        .iter()
+        .filter(|known| known.sha256 == record.sha256 && known.status != IngestStatus::Failed);
+    // Files left in place are seen again whenever the app starts.
+    if known.clone().any(|known| known.document_path == record.document_path) {
+        return Ok(None);
+    }
+    let original = known
+        .filter(|known| known.status == IngestStatus::Extracted)
+        .map(|known| known.document_path.clone())
+        .next();
+
+    let document_path = match folder.after_ingest {
+        PostIngestAction::LeaveInPlace => path.to_path_buf(),
+        PostIngestAction::Archive => archive(path, folder, file_names)?,
+    };
+    record.document_path = document_path.display().to_string();
+    if folder.after_ingest == PostIngestAction::Archive {
+        log_archive_move(handle, path, &document_path);
+    }
+    if let Some(original) = original {
+        info!("{} is a copy of {}, skipping it", path.display(), original);
+        return Ok(Some(IngestStatus::Duplicate));
+    }
+
+    let data_directory = data_directory_for(&document_path);
+    record.data_directory = Some(data_directory.display().to_string());
+    let stage = ExtractDocumentImagesStage {
+        document_path: record.document_path.clone(),
+        data_directory: data_directory.display().to_string(),
+        images_directory: data_directory
+            .join(IMAGES_DIRECTORY_NAME)
+            .display()
+            .to_string(),
+    };
+    let message = match run_extract_document_images_stage(handle.clone(), stage).await {
+        Ok(message) => message,
+        Err(e) if folder.after_ingest == PostIngestAction::Archive => {
+            // Out of the archive, or the watcher would never see it again.
+            let restored = restore(&document_path, path, file_names)?;
+            log_archive_move(handle, &document_path, &restored);
+            record.document_path = restored.display().to_string();
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+    };
+    info!("Ingested {}: {}", record.document_path, message);
+    Ok(Some(IngestStatus::Extracted))
+}
+
+/// A file whose size and modification time have not changed since `since`.
+struct Candidate {
+    fingerprint: FileFingerprint,
+    since: Instant,
+}
+
+/// Polls the watch folders from settings, which are read again on every
+/// poll so changes apply without a restart. Ready files are ingested one at
+/// a time.
+#[derive(Default)]
+struct Watcher {
+    candidates: HashMap<PathBuf, Candidate>,
+    /// Files ingested or failed, until they change.
+    handled: HashMap<PathBuf, FileFingerprint>,
+}
+
+impl Watcher {
+    async fn poll(&mut self, handle: &AppHandle) -> Duration {
+        let settings = match load_settings(handle) {
+            Ok(settings) => settings,
+            Err(e) => {
+                warn!("Failed to load watch settings: {}", e);
+                return Duration::from_secs(WatchSettings::default().poll_interval_seconds);
+            }
+        };
+        let watch = &settings.watch;
+        let interval = Duration::from_secs(watch.poll_interval_seconds.max(1));
+        if !watch.enabled {
+            self.candidates.clear();
+            set_pending(Vec::new());
+            return interval;
+        }
+        let stable = Duration::from_secs(watch.stable_seconds);
+
+        let mut seen = HashSet::new();
+        let mut ready = Vec::new();
+        for folder in &watch.folders {
+            let scan = match FolderScan::new(folder) {
+                Ok(scan) => scan,
+                Err(e) => {
+                    warn!("Skipping watch folder {}: {}", folder.path, e);
+                    continue;
+                }
+            };
+            for path in scan.files() {
+                let Ok(fingerprint) = fingerprint(&path) else {
+                    continue;
+                };
+                seen.insert(path.clone());
+                if self.handled.get(&path) == Some(&fingerprint) {
+                    continue;
+                }
+                match self.candidates.get(&path) {
+                    Some(candidate) if candidate.fingerprint == fingerprint => {
+                        if candidate.since.elapsed() >= stable && is_complete(&path) {
+                            ready.push((path, folder));
+                        }
+                    }
+                    _ => {
+                        self.candidates.insert(
+                            path,
+                            Candidate {
+                                fingerprint,
+                                since: Instant::now(),
+                            },
+                        );
+                    }
+                }
+            }
+        }
+        self.candidates.retain(|path, _| seen.contains(path));
+        self.handled.retain(|path, _| seen.contains(path));
+
+        for (path, folder) in ready {
+            set_pending(
+                self.candidates
+                    .keys()
+                    .map(|path| path.display().to_string())
+                    .collect(),
+            );
+            if let Some(candidate) = self.candidates.remove(&path) {
+                self.handled.insert(path.clone(), candidate.fingerprint);
+            }
+            ingest(handle, &path, folder, &settings.file_names).await;
+            // A file moved back after failing keeps its place among the
+            // handled ones, so it is tried again only once it changes or the
+            // app restarts.
+            if let Ok(fingerprint) = fingerprint(&path) {
+                self.handled.insert(path, fingerprint);
+            }
+        }
+        set_pending(
+            self.candidates
+                .keys()
+                .map(|path| path.display().to_string())
+                .collect(),
+        );
+        interval
+    }
+}
+
+/// Starts polling the watch folders in the background for the lifetime of
+/// the app.
+pub fn start_watcher(handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut watcher = Watcher::default();
+        loop {
+            let interval = watcher.poll(&handle).await;
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+pub fn validate_watch_settings(
+    settings: &WatchSettings,
+    paths: &PathSettings,
+) -> Result<(), String> {
+    if settings.poll_interval_seconds == 0 {
+        return Err("The watch poll interval must be at least 1 second".to_string());
+    }
+    // Ingested documents become scope roots, so the folders must already be
+    // inside one the user picked.
+    let scope = PathScope::new(
+        paths.allowed_roots.iter().map(PathBuf::from),
+        paths.allow_network_paths,
+    );
+    for (index, folder) in settings.folders.iter().enumerate() {
+        let path = Path::new(&folder.path);
+        if !path.is_absolute() {
+            return Err(format!("Watch folder is not absolute: {}", folder.path));
+        }
+        if folder.path.replace('/', "\\").starts_with(r"\\") && !paths.allow_network_paths {
+            return Err(format!(
+                "Watch folder {} is a network path, which settings do not allow",
+                folder.path
+            ));
+        }
+        if settings.folders[..index]
+            .iter()
+            .any(|other| other.path == folder.path)
+        {
+            return Err(format!("Duplicate watch folder: {}", folder.path));
+        }
+        scope
+            .check_new(&folder.path)
+            .map_err(|e| format!("Watch folder {}: {}", folder.path, e))?;
+        glob_set(&folder.include)
+            .and_then(|_| glob_set(&folder.exclude))
+            .map_err(|e| format!("Watch folder {}: {}", folder.path, e))?;
+        if folder.after_ingest == PostIngestAction::Archive {
+            let archive = folder.archive_path.as_deref().ok_or_else(|| {
+                format!("Watch folder {} needs an archive folder", folder.path)
+            })?;
+            if !Path::new(archive).is_absolute() {
+                return Err(format!("Archive folder is not absolute: {}", archive));
+            }
+            scope
+                .check_new(archive)
+                .map_err(|e| format!("Archive folder {}: {}", archive, e))?;
+            if Path::new(archive) == path {
+                return Err(format!(
+                    "The archive folder of {} cannot be the folder itself",
+                    folder.path
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_watch_status(handle: AppHandle) -> Result<WatchStatus, String> {
+    let enabled = load_settings(&handle)?.watch.enabled;
+    let pending = PENDING
+        .lock()
+        .map_err(|e| format!("Failed to lock pending watch files: {}", e))?
+        .clone();
+    let mut ingested = read_store(&handle)?.records;
+    ingested.reverse();
+    Ok(WatchStatus {
+        enabled,
+        pending,
+        ingested,
+    })
+}
@@ -0,0 +1,150 @@
+use log::{debug, error, warn};
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+
+const WORKSPACE_DIRECTORY_NAME: &str = ".processing";
+/// Workspaces older than this were left behind by a crash and are removed.
+const STALE_WORKSPACE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The steps of the document process stage, named in error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessStep {
+    Prepare,
+    ExtractPages,
+    Ocr,
+    Optimise,
+    Stamp,
+    WriteMetadata,
+    VerifyPdfa,
+    Split,
+    Sign,
+    ExportSidecar,
+    Finalise,
+}
+
+impl fmt::Display for ProcessStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessStep::Prepare => write!(f, "Preparing the output"),
+            ProcessStep::ExtractPages => write!(f, "Page extraction"),
+            ProcessStep::Ocr => write!(f, "OCR"),
+            ProcessStep::Optimise => write!(f, "Optimisation"),
+            ProcessStep::Stamp => write!(f, "Stamping"),
+            ProcessStep::WriteMetadata => write!(f, "Writing metadata"),
+            ProcessStep::VerifyPdfa => write!(f, "PDF/A verification"),
+            ProcessStep::Split => write!(f, "Splitting"),
+            ProcessStep::Sign => write!(f, "Signing"),
+            ProcessStep::ExportSidecar => write!(f, "Writing the sidecar"),
+            ProcessStep::Finalise => write!(f, "Moving the document into place"),
+        }
+    }
+}
+
+impl ProcessStep {
+    pub fn fail(self, error: impl fmt::Display) -> String {
+        format!("{} failed: {}", self, error)
+    }
+}
+
+/// A private directory inside `<data_directory>/.processing` for the
+/// intermediate files of one run. It is removed when dropped, so nothing is
+/// left behind whichever step fails; only `commit` moves a file out of it.
+pub struct ProcessingWorkspace {
+    directory: PathBuf,
+}
+
+impl ProcessingWorkspace {
+    pub fn new(data_directory: &Path) -> Result<Self, String> {
+        let root = data_directory.join(WORKSPACE_DIRECTORY_NAME);
+        purge_stale(&root);
+        let directory = root.join(Uuid::new_v4().to_string());
+        fs::create_dir_all(&directory).map_err(|e| {
+            error!("Failed to create processing directory: {}", e);
+            format!("Failed to create processing directory: {}", e)
+        })?;
+        Ok(Self { directory })
+    }
+
+    pub fn path(&self, file_name: &str) -> PathBuf {
+        self.directory.join(file_name)
+    }
+
+    /// Moves `file` from the workspace to `destination`. Unless `overwrite`
+    /// is set, a file that appeared at `destination` since it was resolved
+    /// is left alone and the commit fails.
+    pub fn commit(&self, file: &Path, destination: &Path, overwrite: bool) -> Result<(), String> {
+        let result = if overwrite {
+            fs::rename(file, destination)
+        } else {
+            move_new(file, destination)
+        };
+        result.map_err(|e| {
+            error!("Failed to move {} into place: {}", destination.display(), e);
+            format!("Failed to move {} into place: {}", destination.display(), e)
+        })
+    }
+}
+
+/// Moves `file` to `destination` only if nothing is there, which a rename
+/// does not check on Unix.
+fn move_new(file: &Path, destination: &Path) -> io::Result<()> {
+    match fs::hard_link(file, destination) {
+        Ok(()) => return fs::remove_file(file),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(e),
+        // Volumes without hard links, such as FAT, get a fresh copy instead.
+        Err(_) => {}
+    }
+    let mut target = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(destination)?;
+    let copied = File::open(file).and_then(|mut source| io::copy(&mut source, &mut target));
+    drop(target);
+    if let Err(e) = copied {
+        let _ = fs::remove_file(destination);
+        return Err(e);
+    }
+    fs::remove_file(file)
+}
+
+impl Drop for ProcessingWorkspace {
+    fn drop(&mut self) {
+        match fs::remove_dir_all(&self.directory) {
+            Ok(()) => debug!("Removed processing directory {}", self.directory.display()),
+            Err(e) => warn!(
+                "Failed to remove processing directory {}: {}",
+                self.directory.display(),
+                e
+            ),
+        }
+    }
+}
+
+fn purge_stale(root: &Path) {
+    let Ok(entries) = fs::read_dir(root) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let is_stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > STALE_WORKSPACE_AGE);
+        if is_stale {
+            if let Err(e) = fs::remove_dir_all(entry.path()) {
+                warn!(
+                    "Failed to remove stale processing directory {}: {}",
+                    entry.path().display(),
+                    e
+                );
+            }
+        }
+    }
+}
@@ -66,9 +66,79 @@ export class PagePreprocessStageResultModel
   }
 }
 
+export interface DuplicateCandidate {
+  documentId: string;
+  documentPath: string | null;
+  dataDirectory: string;
+  summary: string;
+  similarity: number;
+  pageSimilarity: number | null;
+  matchedPages: number;
+  textSimilarity: number | null;
+}
+
+export type SignatureStatus =
+  | "valid"
+  | "untrusted"
+  | "modified"
+  | "invalid"
+  | "unsupported";
+
+export interface CertificateSummary {
+  subject: string;
+  issuer: string;
+  serialNumber: string;
+  notBefore: string;
+  notAfter: string;
+  trusted: boolean;
+}
+
+export type ValidityBasis = "claimedSigningTime" | "now";
+
+export interface SignatureReport {
+  fieldName: string;
+  signerName: string | null;
+  signingTime: string | null;
+  reason: string | null;
+  location: string | null;
+  subFilter: string | null;
+  certificateChain: CertificateSummary[];
+  coversWholeDocument: boolean;
+  digestMatches: boolean;
+  signatureValid: boolean;
+  trusted: boolean;
+  validityCheckedAt: string | null;
+  validityBasis: ValidityBasis | null;
+  status: SignatureStatus;
+  issues: string[];
+}
+
+export interface SignatureStamp {
+  pageNumber: number;
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+export interface SigningOptions {
+  password: string;
+  stamp?: SignatureStamp | null;
+  reason?: string | null;
+}
+
+export interface SigningReport {
+  fieldName: string;
+  signerName: string;
+  signingTime: string;
+  timestampTime: string | null;
+}
+
 export interface PagePreprocessStageSuccess extends PagePreprocessStage {
   pagePreprocessStageResult: PagePreprocessStageResult;
   pageNumberPrefix: string;
+  duplicates?: DuplicateCandidate[];
+  signatures?: SignatureReport[];
 }
 
 export class PagePreprocessStageSuccessModel
@@ -123,9 +193,39 @@ export class PagePreprocessStageErrorModel implements PagePreprocessStageError {
   }
 }
 
+export interface PageReference {
+  documentPath: string;
+  pageNumber: number;
+  rotation?: number;
+}
+
+export interface SplitOptions {
+  maxBytes?: number | null;
+  maxPages?: number | null;
+}
+
 export interface DocumentProcessStage extends PagePreprocessStageSuccess {
   documentPath: string;
   fileName: string;
+  sourcePages?: PageReference[];
+  ocrProfile?: string;
+  optimisationProfile?: string;
+  targetMaxBytes?: number;
+  split?: SplitOptions | null;
+  identifiers?: ExtractedIdentifier[];
+  keepSignedOriginal?: boolean | null;
+  stamps?: string[];
+  batesPrefix?: string | null;
+  signing?: SigningOptions | null;
+}
+
+export type IdentifierKind = "cpf" | "cnpj" | "cnj" | "oab" | "cep" | "amount";
+
+export interface ExtractedIdentifier {
+  kind: IdentifierKind;
+  value: string;
+  valid: boolean;
+  pages: number[];
 }
 
 export class DocumentProcessStageModel implements DocumentProcessStage {
@@ -159,8 +259,38 @@ export class DocumentProcessStageModel implements DocumentProcessStage {
   }
 }
 
-export interface DocumentProcessStageSuccess extends DocumentProcessStage {
+export interface PdfaReport {
+  expectedLevel: string | null;
+  claimedLevel: string | null;
+  conforms: boolean;
+  issues: string[];
+}
+
+export interface OptimisationReport {
+  profile: string;
+  originalBytes: number;
+  optimisedBytes: number;
+  targetMaxBytes: number | null;
+  attempts: number;
+  targetMet: boolean;
+}
+
+export interface BatesRange {
+  first: string;
+  last: string;
+}
+
+export interface DocumentProcessStageSuccess
+  extends Omit<DocumentProcessStage, "documentPath"> {
   pagePreprocessStageResult: PagePreprocessStageResult;
+  pdfaReport?: PdfaReport | null;
+  optimisationReport?: OptimisationReport | null;
+  partPaths: string[];
+  duplicates?: DuplicateCandidate[];
+  invalidatedSignatures?: SignatureReport[];
+  signedOriginalPaths?: string[];
+  signingReports?: SigningReport[];
+  batesRange?: BatesRange | null;
 }
 
 export class DocumentProcessStageSuccessModel
@@ -171,7 +301,7 @@ export class DocumentProcessStageSuccessModel
   dataDirectory: string;
   imagesDirectory: string;
   pagePreprocessStageResult: PagePreprocessStageResultModel;
-  documentPath: string;
+  partPaths: string[];
   fileName: string;
   pageNumberPrefix: string;
   constructor(
@@ -180,7 +310,7 @@ export class DocumentProcessStageSuccessModel
     dataDirectory: string,
     imagesDirectory: string,
     pagePreprocessStageResult: PagePreprocessStageResult,
-    documentPath: string,
+    partPaths: string[],
     fileName: string,
     pageNumberPrefix: string,
   ) {
@@ -196,7 +326,7 @@ export class DocumentProcessStageSuccessModel
       pagePreprocessStageResult.summary,
       pagePreprocessStageResult.suggested_file_name,
     );
-    this.documentPath = documentPath;
+    this.partPaths = partPaths;
     this.fileName = fileName;
     this.pageNumberPrefix = pageNumberPrefix;
   }
@@ -255,7 +385,7 @@ export class FinishedDocumentProcessStageModel
   dataDirectory: string;
   imagesDirectory: string;
   pagePreprocessStageResult: PagePreprocessStageResult;
-  documentPath: string;
+  partPaths: string[];
   fileName: string;
   fileNameHistory: string[];
   pageNumberPrefix: string;
@@ -265,7 +395,7 @@ export class FinishedDocumentProcessStageModel
     dataDirectory: string,
     imagesDirectory: string,
     pagePreprocessStageResult: PagePreprocessStageResult,
-    documentPath: string,
+    partPaths: string[],
     fileName: string,
     fileNameHistory: string[],
     pageNumberPrefix: string,
@@ -275,7 +405,7 @@ export class FinishedDocumentProcessStageModel
     this.dataDirectory = dataDirectory;
     this.imagesDirectory = imagesDirectory;
     this.pagePreprocessStageResult = pagePreprocessStageResult;
-    this.documentPath = documentPath;
+    this.partPaths = partPaths;
     this.fileName = fileName;
     this.fileNameHistory = fileNameHistory;
     this.pageNumberPrefix = pageNumberPrefix;
@@ -298,6 +428,24 @@ export class InProcessInstanceModel {
   }
 }
 
+export type IngestStatus = "extracted" | "duplicate" | "failed";
+
+export interface IngestRecord {
+  sha256: string;
+  sourcePath: string;
+  documentPath: string;
+  dataDirectory?: string | null;
+  status: IngestStatus;
+  errorMessage?: string | null;
+  timestamp: number;
+}
+
+export interface WatchStatus {
+  enabled: boolean;
+  pending: string[];
+  ingested: IngestRecord[];
+}
+
 interface SetupState {
   documentPath: string;
   documentProxy: PDFDocumentProxy | undefined;
@@ -322,6 +470,8 @@ interface SetupState {
   documentProcessStageSuccessList: DocumentProcessStageSuccessModel[];
   documentProcessStageErrorList: DocumentProcessStageErrorModel[];
   finishedDocumentsProcessStage: FinishedDocumentProcessStageModel[];
+  watchQueue: IngestRecord[];
+  watchPending: string[];
 }
 
 class GlobalSetupState {
@@ -349,6 +499,8 @@ class GlobalSetupState {
     documentProcessStageSuccessList: [],
     documentProcessStageErrorList: [],
     finishedDocumentsProcessStage: [],
+    watchQueue: [],
+    watchPending: [],
   });
 
   constructor(documentPath: string) {
@@ -357,8 +509,8 @@ class GlobalSetupState {
 
   get documentClonePath() {
     const documentPath = this.state.documentPath;
-    const dataDirectory = documentPath.endsWith(".pdf")
-      ? documentPath.replace(".pdf", "-data")
+    const dataDirectory = /\.pdf$/i.test(documentPath)
+      ? documentPath.replace(/\.pdf$/i, "-data")
       : documentPath + "-data";
     const documentClonePath = `${dataDirectory}\\${documentPath.split("\\").pop()}`;
     return documentClonePath;
@@ -366,8 +518,8 @@ class GlobalSetupState {
 
   get dataDirectory() {
     const documentPath = this.state.documentPath;
-    const dataDirectory = documentPath.endsWith(".pdf")
-      ? documentPath.replace(".pdf", "-data")
+    const dataDirectory = /\.pdf$/i.test(documentPath)
+      ? documentPath.replace(/\.pdf$/i, "-data")
       : documentPath + "-data";
     return dataDirectory;
   }
@@ -400,22 +552,22 @@ class GlobalSetupState {
         document.pageNumberPrefix + "-",
         "",
       );
-      const newDocumentPath = await invoke<string>("run_update_file_name", {
+      const newPartPaths = await invoke<string[]>("run_update_file_name", {
         newFileName: cleanNewFileName,
-        documentPath: document.documentPath,
+        partPaths: document.partPaths,
+        documentId: document.id,
+      });
+      const fileNameHistory = await invoke<string[]>("get_file_name_history", {
+        documentId: document.id,
+        documentPath: newPartPaths[0],
       });
-      const fileNameHistory = document.fileNameHistory.includes(
-        cleanNewFileName,
-      )
-        ? document.fileNameHistory
-        : [...document.fileNameHistory, cleanNewFileName];
       const newFinishedDocumentStage = new FinishedDocumentProcessStageModel(
         document.id,
         document.selectedPages,
         document.dataDirectory,
         document.imagesDirectory,
         document.pagePreprocessStageResult,
-        newDocumentPath,
+        newPartPaths,
         cleanNewFileName,
         fileNameHistory,
         document.pageNumberPrefix,
@@ -431,6 +583,38 @@ class GlobalSetupState {
     }
   }
 
+  /**
+   * Queues a document extracted from a watch folder, unless it is open or
+   * queued already. Duplicates and failures are not worth opening.
+   */
+  enqueueIngested(record: IngestRecord) {
+    if (record.status !== "extracted") return;
+    if (
+      record.documentPath === this.state.documentPath ||
+      this.state.watchQueue.some(
+        (queued) => queued.documentPath === record.documentPath,
+      )
+    )
+      return;
+    this.state.watchQueue.push(record);
+  }
+
+  /** Opens the oldest queued document from the watch folders. */
+  openNextIngested() {
+    const record = this.state.watchQueue.shift();
+    if (record) this.state.documentPath = record.documentPath;
+  }
+
+  async refreshWatchStatus() {
+    try {
+      const status = await invoke<WatchStatus>("get_watch_status");
+      this.state.watchPending = status.enabled ? status.pending : [];
+    } catch (error) {
+      console.error("Error reading watch status:", error);
+    }
+  }
+
+  // The watch queue outlives the open document, so it is not cleared here.
   async clearState() {
     await this.state.documentProxy?.destroy();
     this.state.documentPath = "";